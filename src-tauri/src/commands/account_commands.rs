@@ -1,5 +1,5 @@
 use crate::models::{EmailAccount, EmailAccountWithPassword};
-use crate::services::{ImapService, SmtpService, StorageService};
+use crate::services::{ImapPool, ImapService, MailWatchService, SmtpService, StorageService};
 use tauri::State;
 
 pub type StorageState<'a> = State<'a, std::sync::Arc<StorageService>>;
@@ -36,7 +36,13 @@ pub async fn list_accounts(storage: StorageState<'_>) -> Result<Vec<EmailAccount
 }
 
 #[tauri::command]
-pub async fn delete_account(storage: StorageState<'_>, id: String) -> Result<(), String> {
+pub async fn delete_account(
+    storage: StorageState<'_>,
+    watcher: State<'_, std::sync::Arc<MailWatchService>>,
+    id: String,
+) -> Result<(), String> {
+    watcher.stop_account(&id);
+    ImapPool::global().clear_account(&id);
     storage.delete_account(&id)
 }
 
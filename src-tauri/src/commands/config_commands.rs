@@ -1,9 +1,20 @@
-use crate::models::{AppConfig, FilterRule};
-use crate::services::StorageService;
-use tauri::State;
+use super::email_commands::get_account_with_password;
+use crate::models::{AppConfig, EmailSummary, FilterRule, SavedSearch, SearchQuery};
+use crate::services::{
+    ImapService, ManageSieveService, RuleEngine, RuleRunResult, SieveImport, SieveScriptInfo, SieveService,
+    StorageService,
+};
+use std::collections::HashSet;
+use tauri::{AppHandle, Emitter, State};
 
 pub type StorageState<'a> = State<'a, std::sync::Arc<StorageService>>;
 
+/// 追溯执行规则时每批处理的邮件数
+const RULE_BATCH_SIZE: usize = 50;
+
+/// 上传到服务器的 Sieve 脚本默认名称
+const DEFAULT_SIEVE_SCRIPT_NAME: &str = "mailflow";
+
 #[tauri::command]
 pub async fn get_app_config(storage: StorageState<'_>) -> Result<AppConfig, String> {
     storage.get_config()
@@ -24,12 +35,28 @@ pub async fn get_filter_rules(storage: StorageState<'_>) -> Result<Vec<FilterRul
 
 #[tauri::command]
 pub async fn save_filter_rule(
-    rule: FilterRule,
+    mut rule: FilterRule,
     storage: StorageState<'_>,
 ) -> Result<(), String> {
+    RuleEngine::validate_rule(&rule)?;
+    let rules = storage.get_filter_rules()?;
+
+    // 新规则默认排在最后
+    if !rules.iter().any(|r| r.id == rule.id) {
+        rule.priority = rules.iter().map(|r| r.priority + 1).max().unwrap_or(0);
+    }
+
     storage.save_filter_rule(&rule)
 }
 
+#[tauri::command]
+pub async fn reorder_filter_rules(
+    ids: Vec<String>,
+    storage: StorageState<'_>,
+) -> Result<Vec<FilterRule>, String> {
+    storage.reorder_filter_rules(&ids)
+}
+
 #[tauri::command]
 pub async fn delete_filter_rule(
     id: String,
@@ -38,6 +65,217 @@ pub async fn delete_filter_rule(
     storage.delete_filter_rule(&id)
 }
 
+/// 列出已保存的搜索，每个搜索以 `folder_name()` 作为虚拟文件夹传给 `fetch_emails`
+#[tauri::command]
+pub async fn get_saved_searches(storage: StorageState<'_>) -> Result<Vec<SavedSearch>, String> {
+    storage.get_saved_searches()
+}
+
+#[tauri::command]
+pub async fn save_saved_search(
+    search: SavedSearch,
+    storage: StorageState<'_>,
+) -> Result<SavedSearch, String> {
+    if search.name.trim().is_empty() {
+        return Err("搜索名称不能为空".to_string());
+    }
+    // 保存前检查查询语句，避免虚拟文件夹无法打开
+    SearchQuery::parse(&search.query)?.folders()?;
+
+    storage.save_saved_search(&search)?;
+    Ok(search)
+}
+
+#[tauri::command]
+pub async fn delete_saved_search(
+    id: String,
+    storage: StorageState<'_>,
+) -> Result<(), String> {
+    storage.delete_saved_search(&id)
+}
+
+/// 对文件夹试运行或追溯执行一条规则（规则可以尚未保存）
+///
+/// 试运行只读取本地缓存的邮件摘要和详情，返回匹配的 UID；
+/// 追溯执行按批次遍历服务器上的整个文件夹并执行规则动作。
+#[tauri::command]
+pub async fn run_filter_rule(
+    rule: FilterRule,
+    account_id: String,
+    folder: String,
+    dry_run: bool,
+    batch_size: Option<usize>,
+    app: AppHandle,
+    storage: StorageState<'_>,
+) -> Result<RuleRunResult, String> {
+    RuleEngine::validate_rule(&rule)?;
+
+    let rule_id = rule.id.clone();
+    let engine = RuleEngine::for_rule(rule);
+    let mut details = storage.get_cached_email_details(&account_id, &folder)?;
+
+    if dry_run {
+        let summaries = storage.get_cached_email_summaries(&account_id, &folder)?
+            .map(|cached| cached.emails)
+            .unwrap_or_default();
+
+        return Ok(RuleRunResult {
+            dry_run: true,
+            matched_uids: engine.dry_run(&account_id, &folder, &summaries, &details),
+            executions: Vec::new(),
+        });
+    }
+
+    let (account, password) = get_account_with_password(&storage, &account_id)?;
+    let imap_service = ImapService::new(account, password);
+    let batch_size = batch_size.unwrap_or(RULE_BATCH_SIZE).max(1);
+
+    let mut seen = HashSet::new();
+    let mut offset = 0;
+    let mut matched_uids = Vec::new();
+    let mut executions = Vec::new();
+    let mut updated = Vec::new();
+    let mut removed = Vec::new();
+
+    loop {
+        let batch = imap_service.fetch_folder_emails(&folder, batch_size, offset).await?;
+        let fetched = batch.len();
+
+        // 跳过已处理的邮件，避免服务器忽略偏移时重复处理
+        let batch: Vec<EmailSummary> = batch
+            .into_iter()
+            .filter(|email| seen.insert(email.uid))
+            .collect();
+        if batch.is_empty() {
+            break;
+        }
+
+        engine.load_details(&imap_service, &folder, &batch, &mut details).await;
+
+        let batch_uids: Vec<u32> = batch.iter().map(|email| email.uid).collect();
+        let outcome = engine.apply(&imap_service, &account_id, &folder, batch, &details).await;
+        let executed: HashSet<u32> = outcome.executions.iter().map(|execution| execution.uid).collect();
+        matched_uids.extend(outcome.executions.iter().map(|execution| execution.uid));
+
+        // 记录被规则修改、移走或删除的邮件，稍后只更新这些缓存
+        let kept: HashSet<u32> = outcome.remaining.iter().map(|email| email.uid).collect();
+        removed.extend(batch_uids.into_iter().filter(|uid| !kept.contains(uid)));
+        updated.extend(outcome.remaining.iter().filter(|email| executed.contains(&email.uid)).cloned());
+
+        // 被移动或删除的邮件不再占用偏移
+        offset += outcome.remaining.len();
+        executions.extend(outcome.executions);
+
+        let progress = serde_json::json!({
+            "rule_id": rule_id,
+            "account_id": account_id,
+            "folder": folder,
+            "processed": seen.len(),
+            "matched": matched_uids.len(),
+        });
+        if let Err(e) = app.emit("rules://progress", progress) {
+            eprintln!("发送规则进度事件失败: {}", e);
+        }
+
+        if fetched < batch_size {
+            break;
+        }
+    }
+
+    // 移入目标文件夹的邮件 UID 更大，由目标文件夹的下次增量同步取得
+    storage
+        .update_cached_emails(&account_id, &folder, &updated, &removed)
+        .map_err(|e| format!("规则已执行，但更新本地缓存失败: {}", e))?;
+
+    Ok(RuleRunResult {
+        dry_run: false,
+        matched_uids,
+        executions,
+    })
+}
+
+/// 将已启用的过滤规则导出为 Sieve 脚本，指定账户时只导出适用于该账户的规则
+#[tauri::command]
+pub async fn export_sieve_script(
+    account_id: Option<String>,
+    storage: StorageState<'_>,
+) -> Result<String, String> {
+    let rules = storage.get_filter_rules()?;
+    Ok(SieveService::export_rules(&rules, account_id.as_deref()))
+}
+
+/// 导入 Sieve 脚本并保存为过滤规则，新规则排在已有规则之后
+#[tauri::command]
+pub async fn import_sieve_script(
+    script: String,
+    storage: StorageState<'_>,
+) -> Result<SieveImport, String> {
+    let mut imported = SieveService::import_script(&script)?;
+
+    let base = storage.get_filter_rules()?
+        .iter()
+        .map(|r| r.priority + 1)
+        .max()
+        .unwrap_or(0);
+
+    for rule in imported.rules.iter_mut() {
+        rule.priority += base;
+        storage.save_filter_rule(rule)?;
+    }
+
+    Ok(imported)
+}
+
+#[tauri::command]
+pub async fn list_sieve_scripts(
+    account_id: String,
+    storage: StorageState<'_>,
+) -> Result<Vec<SieveScriptInfo>, String> {
+    let (account, password) = get_account_with_password(&storage, &account_id)?;
+    ManageSieveService::from_account(&account, password).list_scripts().await
+}
+
+/// 将适用于该账户的已启用规则生成 Sieve 脚本并上传激活，
+/// 使规则在桌面应用关闭时也能在服务器端运行
+///
+/// 服务器同时只能启用一个脚本：已启用其他脚本（如网页邮箱生成的规则）时，
+/// 只有 replace_active 为 true 才会替换，否则不上传并返回错误
+#[tauri::command]
+pub async fn sync_sieve_rules(
+    account_id: String,
+    script_name: Option<String>,
+    replace_active: Option<bool>,
+    storage: StorageState<'_>,
+) -> Result<Vec<SieveScriptInfo>, String> {
+    let (account, password) = get_account_with_password(&storage, &account_id)?;
+    let rules = storage.get_filter_rules()?;
+    let script = SieveService::export_rules(&rules, Some(&account_id));
+    let name = script_name.unwrap_or_else(|| DEFAULT_SIEVE_SCRIPT_NAME.to_string());
+
+    let service = ManageSieveService::from_account(&account, password);
+    let active = service.list_scripts().await?
+        .into_iter()
+        .find(|script| script.active && script.name != name);
+    if let Some(active) = active {
+        if !replace_active.unwrap_or(false) {
+            return Err(format!("服务器上已启用脚本 '{}'，确认替换后才能启用新的规则", active.name));
+        }
+    }
+
+    service.upload_script(&name, &script, true).await?;
+    service.list_scripts().await
+}
+
+#[tauri::command]
+pub async fn delete_sieve_script(
+    account_id: String,
+    script_name: String,
+    storage: StorageState<'_>,
+) -> Result<(), String> {
+    let (account, password) = get_account_with_password(&storage, &account_id)?;
+    ManageSieveService::from_account(&account, password).delete_script(&script_name).await
+}
+
 #[tauri::command]
 pub async fn clear_email_cache(
     account_id: String,
@@ -53,11 +291,13 @@ pub async fn export_data(storage: StorageState<'_>) -> Result<String, String> {
     let config = storage.get_config()?;
     let accounts = storage.list_accounts()?;
     let rules = storage.get_filter_rules()?;
+    let saved_searches = storage.get_saved_searches()?;
 
     let export_data = serde_json::json!({
         "config": config,
         "accounts": accounts,
         "filter_rules": rules,
+        "saved_searches": saved_searches,
         "exported_at": chrono::Utc::now().to_rfc3339()
     });
 
@@ -114,5 +354,14 @@ pub async fn import_data(
         }
     }
 
+    // 导入已保存的搜索
+    if let Some(searches) = import_data.get("saved_searches") {
+        let searches: Vec<SavedSearch> = serde_json::from_value(searches.clone())
+            .map_err(|e| format!("解析已保存的搜索失败: {}", e))?;
+        for search in searches {
+            storage.save_saved_search(&search)?;
+        }
+    }
+
     Ok(())
 }
@@ -1,14 +1,24 @@
-use crate::models::{Email, EmailAccount, EmailSummary, FolderSyncState};
-use crate::services::{ImapService, SmtpService, StorageService};
-use tauri::State;
+use crate::models::{
+    AccountError, Attachment, Email, EmailAccount, EmailSource, EmailSummary, FolderSearchResult, LocalSearchHit, SavedSearch,
+    SearchCriteria, SearchQuery, SpecialFolder, UnifiedEmailList,
+};
+use crate::services::{data_uri, replace_cid_urls, ImapService, SmtpService, StorageService, SyncManager, SyncService};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, State};
+use tokio::task::JoinSet;
 
 pub type StorageState<'a> = State<'a, std::sync::Arc<StorageService>>;
 
 /// 缓存有效期（秒）- 5分钟
 const CACHE_TTL_SECONDS: i64 = 300;
 
+/// 每个文件夹默认返回的搜索结果数
+const SEARCH_RESULT_LIMIT: usize = 100;
+
 /// 获取账户和密码的辅助函数
-fn get_account_with_password(
+pub(crate) fn get_account_with_password(
     storage: &StorageState<'_>,
     account_id: &str,
 ) -> Result<(EmailAccount, String), String> {
@@ -37,98 +47,192 @@ pub async fn fetch_emails(
     limit: usize,
     offset: usize,
     force_refresh: Option<bool>,
+    app: AppHandle,
     storage: StorageState<'_>,
+    sync_manager: State<'_, std::sync::Arc<SyncManager>>,
 ) -> Result<Vec<EmailSummary>, String> {
-    let force = force_refresh.unwrap_or(false);
-    let now = chrono::Utc::now().timestamp();
-
-    // 1. 检查本地缓存（非强制刷新时）
-    if !force {
-        if let Some(cached) = storage.get_cached_email_summaries(&account_id, &folder)? {
-            // 检查缓存是否过期
-            if now - cached.last_updated < CACHE_TTL_SECONDS {
-                // 缓存有效，直接返回
-                let emails: Vec<EmailSummary> = cached.emails
-                    .into_iter()
-                    .skip(offset)
-                    .take(limit)
-                    .collect();
-                if !emails.is_empty() {
-                    return Ok(emails);
-                }
-            }
-        }
+    // 虚拟文件夹（已保存的搜索）只从本地缓存解析，可跨越多个账户
+    if let Some(id) = SavedSearch::id_from_folder(&folder) {
+        let search = storage.get_saved_search(id)?
+            .ok_or_else(|| format!("已保存的搜索不存在: {}", id))?;
+        return storage.list_saved_search(&search, offset, limit);
     }
 
     let (account, password) = get_account_with_password(&storage, &account_id)?;
-    let imap_service = ImapService::new(account.clone(), password);
+    let imap_service = ImapService::new(account, password);
 
-    // 2. 检查同步状态
-    let sync_state = storage.get_folder_sync_state(&account_id, &folder)?;
+    load_folder_page(
+        &app, &storage, &sync_manager, &imap_service, &account_id, &folder,
+        offset, limit, force_refresh.unwrap_or(false),
+    ).await
+}
 
-    // 3. 获取当前文件夹状态
-    let (uid_validity, uid_next) = imap_service.get_folder_status(&folder).await?;
+/// 返回文件夹的一页邮件：缓存过期时先与服务器同步，离线时直接使用缓存
+#[allow(clippy::too_many_arguments)]
+async fn load_folder_page(
+    app: &AppHandle,
+    storage: &StorageService,
+    sync_manager: &SyncManager,
+    imap_service: &ImapService,
+    account_id: &str,
+    folder: &str,
+    offset: usize,
+    limit: usize,
+    force: bool,
+) -> Result<Vec<EmailSummary>, String> {
+    let now = chrono::Utc::now().timestamp();
 
-    // 4. 判断是否需要全量同步
-    let need_full_sync = force || sync_state.is_none() ||
-        sync_state.as_ref().map(|s| s.uid_validity) != Some(uid_validity);
+    // 离线时直接返回缓存，不论是否过期
+    if sync_manager.is_offline() {
+        return match storage.get_cached_email_summaries(account_id, folder)? {
+            Some(cached) => Ok(cached.emails.into_iter().skip(offset).take(limit).collect()),
+            None => Err("当前处于离线状态，且没有本地缓存".to_string()),
+        };
+    }
 
-    let emails = if need_full_sync {
-        // 全量同步
-        let fetched_emails = imap_service.fetch_emails(&folder, limit, offset).await?;
+    // 1. 缓存未过期时不与服务器同步（非强制刷新时）
+    let cache_fresh = !force && storage.get_cached_email_summaries(account_id, folder)?
+        .is_some_and(|cached| now - cached.last_updated < CACHE_TTL_SECONDS);
 
-        // 缓存结果
-        storage.cache_email_summaries(&account_id, &folder, &fetched_emails)?;
+    // 2. 与服务器同步（全量或增量），同一文件夹已在同步时共享其结果
+    if !cache_fresh {
+        sync_manager.sync_folder(
+            app, storage, imap_service, account_id, folder, limit, force,
+        ).await?;
+    }
 
-        // 更新同步状态
-        let last_uid = fetched_emails.first().map(|e| e.uid).unwrap_or(0);
-        let state = FolderSyncState {
-            account_id: account_id.clone(),
-            folder: folder.clone(),
-            last_uid,
-            uid_validity,
-            last_sync_time: now,
-        };
-        storage.save_folder_sync_state(&state)?;
-
-        fetched_emails
-    } else {
-        // 增量同步
-        let state = sync_state.unwrap();
-        let last_uid = state.last_uid;
-
-        // 获取新邮件
-        let new_emails = if uid_next > last_uid + 1 {
-            imap_service.fetch_new_emails(&folder, last_uid, limit).await?
-        } else {
-            Vec::new()
-        };
+    // 3. 从缓存返回请求的页面，超出缓存范围时向服务器取得更早的邮件
+    SyncService::load_page(storage, imap_service, account_id, folder, offset, limit).await
+}
 
-        if !new_emails.is_empty() {
-            // 追加到缓存
-            storage.append_cached_emails(&account_id, &folder, &new_emails)?;
+/// 统一收件箱：合并所有账户中同一用途文件夹（默认收件箱）的邮件，按日期从新到旧排列
+///
+/// 各账户并行同步；某个账户无法连接时使用它的本地缓存，并在 errors 中报告原因。
+/// 邮件 ID 由账户 ID 和 UID 组成，在各账户之间不会重复。
+#[tauri::command]
+pub async fn fetch_unified_emails(
+    folder: Option<SpecialFolder>,
+    limit: usize,
+    offset: usize,
+    force_refresh: Option<bool>,
+    app: AppHandle,
+    storage: StorageState<'_>,
+    sync_manager: State<'_, std::sync::Arc<SyncManager>>,
+) -> Result<UnifiedEmailList, String> {
+    let role = folder.unwrap_or_default();
+    let force = force_refresh.unwrap_or(false);
 
-            // 更新同步状态
-            let new_last_uid = new_emails.iter().map(|e| e.uid).max().unwrap_or(last_uid);
-            let updated_state = FolderSyncState {
-                account_id: account_id.clone(),
-                folder: folder.clone(),
-                last_uid: new_last_uid,
-                uid_validity,
-                last_sync_time: now,
-            };
-            storage.save_folder_sync_state(&updated_state)?;
+    // 每个账户取前 offset + limit 封，合并后再分页
+    let wanted = offset + limit;
+    let mut tasks = JoinSet::new();
+    for account in storage.list_accounts()? {
+        let app = app.clone();
+        let storage = storage.inner().clone();
+        let sync_manager = sync_manager.inner().clone();
+        tasks.spawn(async move {
+            let account_id = account.id.clone();
+            let result = load_unified_account(&app, &storage, &sync_manager, account, role, wanted, force).await;
+            (account_id, result)
+        });
+    }
+
+    let mut emails = Vec::new();
+    let mut errors = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (account_id, (account_emails, error)) = match joined {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("加载账户邮件的任务异常结束: {}", e);
+                continue;
+            }
+        };
+        emails.extend(account_emails);
+        if let Some(error) = error {
+            errors.push(AccountError { account_id, error });
         }
+    }
 
-        // 从缓存返回
-        if let Some(cached) = storage.get_cached_email_summaries(&account_id, &folder)? {
-            cached.emails.into_iter().skip(offset).take(limit).collect()
-        } else {
-            new_emails
+    emails.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.id.cmp(&b.id)));
+    errors.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+
+    Ok(UnifiedEmailList {
+        emails: emails.into_iter().skip(offset).take(limit).collect(),
+        errors,
+    })
+}
+
+/// 加载一个账户在统一收件箱中的邮件，失败时退回本地缓存并返回错误原因
+async fn load_unified_account(
+    app: &AppHandle,
+    storage: &StorageService,
+    sync_manager: &SyncManager,
+    account: EmailAccount,
+    role: SpecialFolder,
+    wanted: usize,
+    force: bool,
+) -> (Vec<EmailSummary>, Option<String>) {
+    let account_id = account.id.clone();
+    let password = match storage.get_password(&account) {
+        Ok(Some(password)) => password,
+        Ok(None) => return (Vec::new(), Some("未找到账户密码，请重新添加账户".to_string())),
+        Err(e) => return (Vec::new(), Some(e)),
+    };
+    let imap_service = ImapService::new(account, password);
+
+    let folder = match resolve_special_folder(storage, sync_manager, &imap_service, &account_id, role).await {
+        Ok(folder) => folder,
+        Err(e) => return (Vec::new(), Some(e)),
+    };
+
+    let (emails, error) = match load_folder_page(
+        app, storage, sync_manager, &imap_service, &account_id, &folder, 0, wanted, force,
+    ).await {
+        Ok(emails) => (emails, None),
+        Err(e) => {
+            let cached = storage.get_cached_email_summaries(&account_id, &folder)
+                .ok()
+                .flatten()
+                .map(|cached| cached.emails.into_iter().take(wanted).collect())
+                .unwrap_or_default();
+            (cached, Some(e))
         }
     };
 
-    Ok(emails)
+    let emails = emails
+        .into_iter()
+        .map(|email| EmailSummary {
+            source: Some(EmailSource {
+                account_id: account_id.clone(),
+                folder: folder.clone(),
+            }),
+            ..email
+        })
+        .collect();
+    (emails, error)
+}
+
+/// 特殊用途文件夹在账户中的实际名称，识别结果保存在本地供离线时使用
+async fn resolve_special_folder(
+    storage: &StorageService,
+    sync_manager: &SyncManager,
+    imap_service: &ImapService,
+    account_id: &str,
+    role: SpecialFolder,
+) -> Result<String, String> {
+    if role == SpecialFolder::Inbox {
+        return Ok("INBOX".to_string());
+    }
+    if let Some(folder) = storage.get_special_folder(account_id, role)? {
+        return Ok(folder);
+    }
+    if sync_manager.is_offline() {
+        return Err("当前处于离线状态，且尚未识别该账户的文件夹".to_string());
+    }
+
+    let folder = imap_service.find_special_folder(role).await?
+        .ok_or_else(|| format!("账户中没有 {} 文件夹", role.as_str()))?;
+    storage.save_special_folder(account_id, role, &folder)?;
+    Ok(folder)
 }
 
 #[tauri::command]
@@ -161,6 +265,160 @@ pub async fn fetch_email_detail(
     Ok(email)
 }
 
+/// 返回用于显示的 HTML 正文，`cid:` 引用的内嵌图片替换为 data URI；没有 HTML 正文时返回 None
+///
+/// 内嵌资源首次显示时从服务器下载并缓存，离线且未缓存的图片保留原引用
+#[tauri::command]
+pub async fn get_email_html(
+    account_id: String,
+    folder: String,
+    uid: u32,
+    storage: StorageState<'_>,
+    sync_manager: State<'_, std::sync::Arc<SyncManager>>,
+) -> Result<Option<String>, String> {
+    let email = match storage.get_cached_email_detail(&account_id, &folder, uid)? {
+        Some(email) => email,
+        None => {
+            let (account, password) = get_account_with_password(&storage, &account_id)?;
+            let email = ImapService::new(account, password).fetch_email_detail(&folder, uid).await?;
+            storage.cache_email_detail(&account_id, &folder, uid, &email)?;
+            email
+        }
+    };
+
+    let html = match email.html_body {
+        Some(html) => html,
+        None => return Ok(None),
+    };
+
+    let mut resources = HashMap::new();
+    let mut pending = false;
+    for resource in &email.inline_resources {
+        match storage.get_inline_resource(&account_id, &folder, uid, &resource.content_id)? {
+            // 空内容表示邮件中没有这个资源，不再重复下载整封邮件
+            Some(data) if data.is_empty() => {}
+            Some(data) => {
+                resources.insert(resource.content_id.clone(), data);
+            }
+            None => pending = true,
+        }
+    }
+
+    if pending && !sync_manager.is_offline() {
+        // 下载失败时仍显示正文，未取得的图片保留原引用
+        let fetched = match get_account_with_password(&storage, &account_id) {
+            Ok((account, password)) => {
+                ImapService::new(account, password).fetch_inline_resources(&folder, uid).await
+            }
+            Err(e) => Err(e),
+        };
+        match fetched {
+            Ok(fetched) => {
+                for (content_id, data) in fetched {
+                    storage.cache_inline_resource(&account_id, &folder, uid, &content_id, &data)?;
+                    resources.insert(content_id, data);
+                }
+                for resource in &email.inline_resources {
+                    if !resources.contains_key(&resource.content_id) {
+                        storage.cache_inline_resource(&account_id, &folder, uid, &resource.content_id, &[])?;
+                    }
+                }
+            }
+            Err(e) => eprintln!("下载内嵌资源失败 (UID: {}): {}", uid, e),
+        }
+    }
+
+    Ok(Some(replace_cid_urls(&html, |content_id| {
+        let resource = email.inline_resources.iter().find(|r| r.content_id == content_id)?;
+        resources.get(content_id).map(|data| data_uri(&resource.content_type, data))
+    })))
+}
+
+/// 下载附件，以二进制形式返回解码后的内容
+#[tauri::command]
+pub async fn download_attachment(
+    account_id: String,
+    folder: String,
+    uid: u32,
+    part_id: String,
+    storage: StorageState<'_>,
+) -> Result<tauri::ipc::Response, String> {
+    let (account, password) = get_account_with_password(&storage, &account_id)?;
+    let imap_service = ImapService::new(account, password);
+
+    let (_, data) = imap_service.fetch_attachment(&folder, uid, &part_id).await?;
+    Ok(tauri::ipc::Response::new(data))
+}
+
+/// 将附件保存到用户选择的路径；路径为目录时使用附件的文件名，同名文件已存在时加上序号
+///
+/// 不会覆盖已有文件
+#[tauri::command]
+pub async fn save_attachment(
+    account_id: String,
+    folder: String,
+    uid: u32,
+    part_id: String,
+    path: String,
+    storage: StorageState<'_>,
+) -> Result<Attachment, String> {
+    let (account, password) = get_account_with_password(&storage, &account_id)?;
+    let imap_service = ImapService::new(account, password);
+
+    let (attachment, data) = imap_service.fetch_attachment(&folder, uid, &part_id).await?;
+
+    let mut path = PathBuf::from(path);
+    if path.is_dir() {
+        check_file_name(&attachment.filename)?;
+        path = unique_path(&path, &attachment.filename);
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => format!("文件已存在: {}", path.display()),
+            _ => format!("保存附件失败: {}", e),
+        })?;
+    file.write_all(&data)
+        .map_err(|e| format!("保存附件失败: {}", e))?;
+
+    Ok(attachment)
+}
+
+/// 附件文件名来自邮件，只能是目录中的普通文件名：
+/// 不能含路径分隔符、冒号（Windows 的盘符和备用数据流）或控制字符，也不能是 `.` 或 `..`
+fn check_file_name(name: &str) -> Result<(), String> {
+    let invalid = name.is_empty()
+        || name == "."
+        || name == ".."
+        || name.chars().any(|c| c.is_control() || matches!(c, '/' | '\\' | ':'));
+    if invalid {
+        return Err(format!("附件文件名无效: {}", name));
+    }
+    Ok(())
+}
+
+/// 目录中尚不存在的文件路径，同名文件已存在时改为 `名称 (1).扩展名` 等
+fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+
+    let name = Path::new(name);
+    let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = name
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|i| dir.join(format!("{} ({}){}", stem, i, extension)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
 #[tauri::command]
 pub async fn mark_email_read(
     account_id: String,
@@ -216,6 +474,86 @@ pub async fn move_email(
     result
 }
 
+/// 在服务器上搜索一个或多个文件夹（默认收件箱），每个文件夹的结果按时间从新到旧排列
+#[tauri::command]
+pub async fn search_emails(
+    account_id: String,
+    criteria: SearchCriteria,
+    folders: Option<Vec<String>>,
+    limit: Option<usize>,
+    storage: StorageState<'_>,
+) -> Result<Vec<FolderSearchResult>, String> {
+    let (account, password) = get_account_with_password(&storage, &account_id)?;
+    let imap_service = ImapService::new(account, password);
+
+    let folders = folders
+        .filter(|folders| !folders.is_empty())
+        .unwrap_or_else(|| vec!["INBOX".to_string()]);
+    let limit = limit.unwrap_or(SEARCH_RESULT_LIMIT);
+
+    let mut results = Vec::new();
+    for folder in folders {
+        let emails = imap_service.search_emails(&folder, &criteria, limit).await?;
+        results.push(FolderSearchResult { folder, emails });
+    }
+
+    Ok(results)
+}
+
+/// 在本地缓存的邮件中全文搜索（离线可用），不指定账户时搜索全部账户
+#[tauri::command]
+pub async fn search_local_emails(
+    query: String,
+    account_id: Option<String>,
+    limit: Option<usize>,
+    storage: StorageState<'_>,
+) -> Result<Vec<LocalSearchHit>, String> {
+    storage.search_local(&query, account_id.as_deref(), limit.unwrap_or(SEARCH_RESULT_LIMIT))
+}
+
+/// 按查询语句（如 `from:alice subject:"周报" is:unread`）搜索邮件
+/// server 为 true 时在服务器端搜索指定账户（离线时改为搜索本地缓存），否则只筛选本地缓存
+#[tauri::command]
+pub async fn query_emails(
+    query: String,
+    account_id: Option<String>,
+    server: Option<bool>,
+    limit: Option<usize>,
+    storage: StorageState<'_>,
+    sync_manager: State<'_, std::sync::Arc<SyncManager>>,
+) -> Result<Vec<LocalSearchHit>, String> {
+    let query = SearchQuery::parse(&query)?;
+    let limit = limit.unwrap_or(SEARCH_RESULT_LIMIT);
+
+    let account_id = match account_id {
+        Some(account_id) if server.unwrap_or(false) && !sync_manager.is_offline() => account_id,
+        account_id => return storage.search_cached(&query, account_id.as_slice(), limit),
+    };
+
+    let (account, password) = get_account_with_password(&storage, &account_id)?;
+    let imap_service = ImapService::new(account, password);
+
+    let mut folders = query.folders()?;
+    if folders.is_empty() {
+        folders.push("INBOX".to_string());
+    }
+
+    let mut hits = Vec::new();
+    for folder in folders {
+        for email in imap_service.query_emails(&folder, &query, limit).await? {
+            hits.push(LocalSearchHit {
+                account_id: account_id.clone(),
+                folder: folder.clone(),
+                email,
+            });
+        }
+    }
+
+    hits.sort_by(|a, b| b.email.date.cmp(&a.email.date));
+    hits.truncate(limit);
+    Ok(hits)
+}
+
 #[tauri::command]
 pub async fn send_email(
     account_id: String,
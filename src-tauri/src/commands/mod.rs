@@ -3,8 +3,10 @@ pub mod email_commands;
 pub mod account_commands;
 pub mod ai_commands;
 pub mod config_commands;
+pub mod sync_commands;
 
 pub use email_commands::*;
 pub use account_commands::*;
 pub use ai_commands::*;
 pub use config_commands::*;
+pub use sync_commands::*;
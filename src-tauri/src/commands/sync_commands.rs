@@ -0,0 +1,106 @@
+use super::email_commands::get_account_with_password;
+use crate::services::{
+    ImapService, MailWatchInfo, MailWatchService, StorageService, SyncManager, SyncStatus,
+};
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+pub type StorageState<'a> = State<'a, Arc<StorageService>>;
+pub type WatchState<'a> = State<'a, Arc<MailWatchService>>;
+pub type SyncState<'a> = State<'a, Arc<SyncManager>>;
+
+/// 未指定文件夹时监听收件箱
+const DEFAULT_WATCH_FOLDER: &str = "INBOX";
+
+/// 开始在后台监听文件夹的新邮件，已在监听时返回 false
+#[tauri::command]
+pub async fn start_mail_watch(
+    account_id: String,
+    folder: Option<String>,
+    app: AppHandle,
+    storage: StorageState<'_>,
+    watcher: WatchState<'_>,
+    sync_manager: SyncState<'_>,
+) -> Result<bool, String> {
+    let (account, password) = get_account_with_password(&storage, &account_id)?;
+    let folder = folder.unwrap_or_else(|| DEFAULT_WATCH_FOLDER.to_string());
+
+    Ok(watcher.start(
+        app,
+        storage.inner().clone(),
+        sync_manager.inner().clone(),
+        account,
+        password,
+        folder,
+    ))
+}
+
+/// 停止监听文件夹，未在监听时返回 false
+#[tauri::command]
+pub async fn stop_mail_watch(
+    account_id: String,
+    folder: Option<String>,
+    watcher: WatchState<'_>,
+) -> Result<bool, String> {
+    let folder = folder.unwrap_or_else(|| DEFAULT_WATCH_FOLDER.to_string());
+    Ok(watcher.stop(&account_id, &folder))
+}
+
+#[tauri::command]
+pub async fn list_mail_watches(watcher: WatchState<'_>) -> Result<Vec<MailWatchInfo>, String> {
+    Ok(watcher.list())
+}
+
+/// 开始定期后台同步，已在运行时返回 false
+#[tauri::command]
+pub async fn start_background_sync(
+    app: AppHandle,
+    storage: StorageState<'_>,
+    sync_manager: SyncState<'_>,
+) -> Result<bool, String> {
+    Ok(sync_manager.start(app, storage.inner().clone()))
+}
+
+/// 停止定期后台同步，未在运行时返回 false
+#[tauri::command]
+pub async fn stop_background_sync(sync_manager: SyncState<'_>) -> Result<bool, String> {
+    Ok(sync_manager.stop())
+}
+
+#[tauri::command]
+pub async fn get_sync_status(sync_manager: SyncState<'_>) -> Result<SyncStatus, String> {
+    Ok(sync_manager.status())
+}
+
+/// 前端报告网络状态；离线时暂停所有同步，恢复在线后立即同步一轮
+#[tauri::command]
+pub async fn set_offline_mode(offline: bool, sync_manager: SyncState<'_>) -> Result<(), String> {
+    sync_manager.set_offline(offline);
+    Ok(())
+}
+
+/// 在后台同步一个文件夹并立即返回，结果通过 `sync://progress` 和 `mail://new` 事件报告
+#[tauri::command]
+pub async fn request_sync(
+    account_id: String,
+    folder: Option<String>,
+    app: AppHandle,
+    storage: StorageState<'_>,
+    sync_manager: SyncState<'_>,
+) -> Result<(), String> {
+    let (account, password) = get_account_with_password(&storage, &account_id)?;
+    let folder = folder.unwrap_or_else(|| DEFAULT_WATCH_FOLDER.to_string());
+    let storage = storage.inner().clone();
+    let sync_manager = sync_manager.inner().clone();
+    let limit = storage.get_config()?.ui_config.emails_per_page as usize;
+
+    tauri::async_runtime::spawn(async move {
+        let imap_service = ImapService::new(account, password);
+        // 失败已通过进度事件报告
+        let _ = sync_manager.sync_folder(
+            &app, &storage, &imap_service, &account_id, &folder, limit, false,
+        ).await;
+    });
+
+    Ok(())
+}
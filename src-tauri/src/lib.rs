@@ -6,7 +6,7 @@ pub mod models;
 pub mod services;
 mod commands;
 
-use services::StorageService;
+use services::{MailWatchService, StorageService, SyncManager};
 use std::sync::Arc;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -16,9 +16,27 @@ pub fn run() {
         .expect("Failed to initialize storage service");
     let storage = Arc::new(storage);
 
+    // 后台新邮件监听
+    let watcher = Arc::new(MailWatchService::new());
+
+    // 后台同步管理器
+    let sync_manager = Arc::new(SyncManager::new());
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .manage(storage)
+        .manage(storage.clone())
+        .manage(watcher)
+        .manage(sync_manager.clone())
+        .setup(move |app| {
+            // 按配置自动开始定期同步
+            let sync_enabled = storage.get_config()
+                .map(|config| config.sync_config.enabled)
+                .unwrap_or(false);
+            if sync_enabled {
+                sync_manager.start(app.handle().clone(), storage.clone());
+            }
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             // 账户管理命令
             commands::add_account,
@@ -31,11 +49,28 @@ pub fn run() {
             // 邮件操作命令
             commands::fetch_folders,
             commands::fetch_emails,
+            commands::fetch_unified_emails,
             commands::fetch_email_detail,
+            commands::get_email_html,
+            commands::download_attachment,
+            commands::save_attachment,
             commands::mark_email_read,
             commands::delete_email,
             commands::move_email,
+            commands::search_emails,
+            commands::search_local_emails,
+            commands::query_emails,
             commands::send_email,
+            // 新邮件监听命令
+            commands::start_mail_watch,
+            commands::stop_mail_watch,
+            commands::list_mail_watches,
+            // 后台同步命令
+            commands::start_background_sync,
+            commands::stop_background_sync,
+            commands::get_sync_status,
+            commands::set_offline_mode,
+            commands::request_sync,
             // AI功能命令
             commands::classify_email_ai,
             commands::summarize_email,
@@ -50,6 +85,16 @@ pub fn run() {
             commands::get_filter_rules,
             commands::save_filter_rule,
             commands::delete_filter_rule,
+            commands::reorder_filter_rules,
+            commands::export_sieve_script,
+            commands::import_sieve_script,
+            commands::list_sieve_scripts,
+            commands::sync_sieve_rules,
+            commands::delete_sieve_script,
+            commands::run_filter_rule,
+            commands::get_saved_searches,
+            commands::save_saved_search,
+            commands::delete_saved_search,
             commands::clear_email_cache,
             commands::export_data,
             commands::import_data,
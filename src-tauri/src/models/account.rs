@@ -11,6 +11,12 @@ pub struct EmailAccount {
     pub smtp_port: u16,
     pub name: String,
     pub is_default: bool,
+    /// ManageSieve 服务器，未设置时使用 IMAP 服务器
+    #[serde(default)]
+    pub sieve_server: Option<String>,
+    /// ManageSieve 端口，未设置时使用 4190
+    #[serde(default)]
+    pub sieve_port: Option<u16>,
 }
 
 /// 用于创建账户时的临时结构（包含密码）
@@ -30,6 +36,8 @@ impl EmailAccountWithPassword {
             smtp_port: 465,
             name,
             is_default: false,
+            sieve_server: None,
+            sieve_port: None,
         };
         Self { account, password }
     }
@@ -44,6 +52,8 @@ impl EmailAccountWithPassword {
             smtp_port: 465,
             name,
             is_default: false,
+            sieve_server: None,
+            sieve_port: None,
         };
         Self { account, password }
     }
@@ -58,6 +68,8 @@ impl EmailAccountWithPassword {
             smtp_port: 587,
             name,
             is_default: false,
+            sieve_server: None,
+            sieve_port: None,
         };
         Self { account, password }
     }
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
@@ -6,6 +7,8 @@ pub struct AppConfig {
     pub default_account_id: Option<String>,
     pub ai_config: AiConfig,
     pub ui_config: UiConfig,
+    #[serde(default)]
+    pub sync_config: SyncConfig,
 }
 
 impl Default for AppConfig {
@@ -15,6 +18,7 @@ impl Default for AppConfig {
             default_account_id: None,
             ai_config: AiConfig::default(),
             ui_config: UiConfig::default(),
+            sync_config: SyncConfig::default(),
         }
     }
 }
@@ -63,13 +67,97 @@ impl Default for UiConfig {
     }
 }
 
+/// 后台同步配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncConfig {
+    /// 启动时自动开始后台同步，默认关闭，需要用户在设置中开启
+    pub enabled: bool,
+    /// 默认同步间隔（秒）
+    pub interval_seconds: u64,
+    /// 每个账户定期同步的文件夹
+    pub folders: Vec<String>,
+    /// 按账户 ID 覆盖的同步间隔（秒）
+    #[serde(default)]
+    pub account_intervals: HashMap<String, u64>,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_seconds: 300,
+            folders: vec!["INBOX".to_string()],
+            account_intervals: HashMap::new(),
+        }
+    }
+}
+
+impl SyncConfig {
+    /// 账户的同步间隔（秒）
+    pub fn interval_for(&self, account_id: &str) -> u64 {
+        self.account_intervals
+            .get(account_id)
+            .copied()
+            .unwrap_or(self.interval_seconds)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FilterRule {
     pub id: String,
     pub name: String,
-    pub conditions: Vec<FilterCondition>,
+    pub conditions: Vec<ConditionNode>,
+    /// 顶层条件的组合方式，旧规则没有该字段时默认为全部满足
+    #[serde(default)]
+    pub match_type: MatchType,
     pub actions: Vec<FilterAction>,
     pub enabled: bool,
+    /// 评估顺序，数值小的先执行
+    #[serde(default)]
+    pub priority: i32,
+    /// 匹配后不再执行后续规则
+    #[serde(default)]
+    pub stop_processing: bool,
+    /// 仅对这些账户生效，为空表示所有账户
+    #[serde(default)]
+    pub account_ids: Vec<String>,
+    /// 仅对这些文件夹生效，为空表示所有文件夹
+    #[serde(default)]
+    pub folders: Vec<String>,
+}
+
+impl FilterRule {
+    /// 规则是否适用于指定账户和文件夹
+    pub fn applies_to(&self, account_id: &str, folder: &str) -> bool {
+        (self.account_ids.is_empty() || self.account_ids.iter().any(|id| id == account_id))
+            && (self.folders.is_empty() || self.folders.iter().any(|f| f == folder))
+    }
+}
+
+/// 条件组合方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum MatchType {
+    /// 全部满足（AND）
+    #[default]
+    All,
+    /// 任一满足（OR）
+    Any,
+}
+
+/// 条件树节点：单个条件、条件组或取反
+///
+/// 单个条件沿用旧的 `{field, operator, value}` 格式，因此已保存的规则无需迁移。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ConditionNode {
+    Group {
+        group: MatchType,
+        conditions: Vec<ConditionNode>,
+    },
+    Not {
+        not: Box<ConditionNode>,
+    },
+    Condition(FilterCondition),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,22 +167,43 @@ pub struct FilterCondition {
     pub value: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FilterField {
     From,
     To,
+    Cc,
     Subject,
     Body,
     Date,
+    /// 任意邮件头（按名称匹配，不区分大小写）
+    Header(String),
+    /// 邮件大小（字节）
+    Size,
+    /// 是否有附件，值为 "true" / "false"
+    HasAttachment,
+    /// AI 分类结果
+    Category,
+    Folder,
+    Account,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FilterOperator {
     Contains,
     NotContains,
     Equals,
     NotEquals,
     Regex,
+    /// 日期早于指定日期（YYYY-MM-DD 或 RFC 3339）
+    Before,
+    /// 日期晚于指定日期（YYYY-MM-DD 或 RFC 3339）
+    After,
+    /// 日期在最近 N 天内
+    WithinDays,
+    /// 大小大于指定值（支持 K/M/G 后缀）
+    GreaterThan,
+    /// 大小小于指定值（支持 K/M/G 后缀）
+    LessThan,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
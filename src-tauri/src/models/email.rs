@@ -1,13 +1,20 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use std::fmt;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Email {
     pub id: String,
     pub uid: u32,
     pub subject: String,
+    /// 发件人（`名称 <地址>`），用于显示
     pub from: String,
+    /// 每个收件人一项，格式同 from
     pub to: Vec<String>,
+    #[serde(default)]
+    pub cc: Vec<String>,
+    #[serde(default)]
+    pub addresses: EmailAddresses,
     pub date: DateTime<Utc>,
     pub body: String,
     pub html_body: Option<String>,
@@ -18,6 +25,135 @@ pub struct Email {
     pub category: Option<String>,
     pub has_attachment: bool,
     pub size: u64,
+    /// 全部邮件头（已展开折叠行并解码，保留原有顺序），用于“查看邮件头”
+    #[serde(default)]
+    pub headers: HeaderMap,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// HTML 正文通过 `cid:` 引用的内嵌资源（如签名中的图片），不在附件中列出
+    #[serde(default)]
+    pub inline_resources: Vec<InlineResource>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailHeader {
+    pub name: String,
+    pub value: String,
+}
+
+/// 按原有顺序保存的邮件头，名称不区分大小写，同名的头（如 Received）可出现多次
+///
+/// 序列化为 `EmailHeader` 数组，与之前的格式相同
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct HeaderMap(Vec<EmailHeader>);
+
+impl HeaderMap {
+    /// 第一个同名邮件头的值
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.as_str())
+    }
+
+    /// 全部同名邮件头的值，按出现顺序
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.0
+            .iter()
+            .filter(move |h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.as_str())
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, EmailHeader> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl FromIterator<EmailHeader> for HeaderMap {
+    fn from_iter<I: IntoIterator<Item = EmailHeader>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl Email {
+    /// 按名称查找邮件头（不区分大小写），返回第一个匹配值
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    /// 由邮件详情生成列表中使用的摘要
+    pub fn to_summary(&self) -> EmailSummary {
+        EmailSummary {
+            id: self.id.clone(),
+            uid: self.uid,
+            subject: self.subject.clone(),
+            from: self.from.clone(),
+            date: self.date.to_rfc3339(),
+            is_read: self.is_read,
+            is_starred: self.is_starred,
+            has_attachment: self.has_attachment,
+            category: self.category.clone(),
+            preview: self.body.chars().take(200).collect::<String>().replace('\n', " "),
+            body: self.body.chars().take(1000).collect(),
+            size: self.size,
+            tags: Vec::new(),
+            source: None,
+            addresses: self.addresses.clone(),
+        }
+    }
+}
+
+/// 邮件地址，name 为解码后的显示名称
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Address {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub email: String,
+}
+
+impl Address {
+    /// 列表中显示的名称，没有名称时为邮件地址
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.email)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) if !self.email.is_empty() => write!(f, "{} <{}>", name, self.email),
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "{}", self.email),
+        }
+    }
+}
+
+/// 邮件头中的结构化地址
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmailAddresses {
+    #[serde(default)]
+    pub from: Vec<Address>,
+    /// 代发时的实际发送者（Sender）
+    #[serde(default)]
+    pub sender: Vec<Address>,
+    #[serde(default)]
+    pub reply_to: Vec<Address>,
+    #[serde(default)]
+    pub to: Vec<Address>,
+    #[serde(default)]
+    pub cc: Vec<Address>,
+    /// 只有自己发出的邮件才可能有 Bcc
+    #[serde(default)]
+    pub bcc: Vec<Address>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,16 +169,47 @@ pub struct EmailSummary {
     pub category: Option<String>,
     pub preview: String,
     pub body: String,
+    #[serde(default)]
+    pub size: u64,
+    /// 过滤规则添加的本地标签
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// 邮件所在的账户和文件夹，只在跨文件夹的列表（如虚拟文件夹）中填写
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<EmailSource>,
+    #[serde(default)]
+    pub addresses: EmailAddresses,
+}
+
+/// 邮件所在位置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmailSource {
+    pub account_id: String,
+    pub folder: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Attachment {
+    /// MIME 部分编号（如 `2`、`1.3`），下载附件时用于定位
     pub id: String,
     pub filename: String,
+    /// 解码后的字节数
     pub size: u64,
     pub content_type: String,
 }
 
+/// multipart/related 中的内嵌资源，以 Content-ID 标识
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InlineResource {
+    /// 去掉尖括号的 Content-ID，即 HTML 中 `cid:` 之后的部分
+    pub content_id: String,
+    /// MIME 部分编号，缓存中没有内容时据此从服务器下载
+    pub part_id: String,
+    pub content_type: String,
+    /// 解码后的字节数
+    pub size: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum EmailCategory {
     Spam,
@@ -77,6 +244,70 @@ impl EmailCategory {
     }
 }
 
+/// 特殊用途文件夹（RFC 6154），各账户中的实际名称不同
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SpecialFolder {
+    #[default]
+    Inbox,
+    Sent,
+    Drafts,
+    Trash,
+    Junk,
+    Archive,
+}
+
+impl SpecialFolder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SpecialFolder::Inbox => "inbox",
+            SpecialFolder::Sent => "sent",
+            SpecialFolder::Drafts => "drafts",
+            SpecialFolder::Trash => "trash",
+            SpecialFolder::Junk => "junk",
+            SpecialFolder::Archive => "archive",
+        }
+    }
+
+    /// LIST 响应中标记该用途的属性
+    pub fn attribute(&self) -> &'static str {
+        match self {
+            SpecialFolder::Inbox => "\\Inbox",
+            SpecialFolder::Sent => "\\Sent",
+            SpecialFolder::Drafts => "\\Drafts",
+            SpecialFolder::Trash => "\\Trash",
+            SpecialFolder::Junk => "\\Junk",
+            SpecialFolder::Archive => "\\Archive",
+        }
+    }
+
+    /// 服务器不支持 SPECIAL-USE 时按常见名称查找
+    pub fn common_names(&self) -> &'static [&'static str] {
+        match self {
+            SpecialFolder::Inbox => &["INBOX"],
+            SpecialFolder::Sent => &["Sent", "Sent Messages", "Sent Items", "Sent Mail"],
+            SpecialFolder::Drafts => &["Drafts", "Draft"],
+            SpecialFolder::Trash => &["Trash", "Deleted Messages", "Deleted Items", "Deleted"],
+            SpecialFolder::Junk => &["Junk", "Spam", "Junk E-mail", "Bulk Mail"],
+            SpecialFolder::Archive => &["Archive", "Archives"],
+        }
+    }
+}
+
+/// 统一收件箱中某个账户加载失败的原因，其余账户的邮件照常返回
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountError {
+    pub account_id: String,
+    pub error: String,
+}
+
+/// 统一收件箱的一页邮件，每封邮件的 source 记录所在账户和文件夹
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnifiedEmailList {
+    pub emails: Vec<EmailSummary>,
+    pub errors: Vec<AccountError>,
+}
+
 /// 文件夹同步状态
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FolderSyncState {
@@ -85,6 +316,9 @@ pub struct FolderSyncState {
     pub last_uid: u32,
     pub uid_validity: u32,
     pub last_sync_time: i64,
+    /// 上次同步时的 HIGHESTMODSEQ（RFC 7162），0 表示未记录或服务器不支持 CONDSTORE
+    #[serde(default)]
+    pub highest_modseq: u64,
 }
 
 /// 缓存的邮件摘要列表
@@ -94,4 +328,7 @@ pub struct CachedEmailList {
     pub folder: String,
     pub emails: Vec<EmailSummary>,
     pub last_updated: i64,
+    /// 缓存是否已包含文件夹中最早的邮件，为 true 时翻页不再请求服务器
+    #[serde(default)]
+    pub reached_oldest: bool,
 }
@@ -2,7 +2,9 @@
 pub mod email;
 pub mod account;
 pub mod config;
+pub mod search;
 
 pub use email::*;
 pub use account::*;
 pub use config::*;
+pub use search::*;
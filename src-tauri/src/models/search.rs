@@ -0,0 +1,116 @@
+use crate::models::EmailSummary;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// 服务器端搜索条件，各条件之间为“与”关系
+/// 日期格式为 YYYY-MM-DD
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchCriteria {
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+    #[serde(default)]
+    pub subject: Option<String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    /// 在邮件头和正文中搜索
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub since: Option<String>,
+    #[serde(default)]
+    pub before: Option<String>,
+    /// Some(true) 只搜索已读邮件，Some(false) 只搜索未读邮件
+    #[serde(default)]
+    pub seen: Option<bool>,
+    #[serde(default)]
+    pub flagged: Option<bool>,
+    /// 邮件大小下限（字节）
+    #[serde(default)]
+    pub larger: Option<u32>,
+}
+
+/// 一个文件夹中的搜索结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderSearchResult {
+    pub folder: String,
+    pub emails: Vec<EmailSummary>,
+}
+
+/// 本地全文搜索的一条结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalSearchHit {
+    pub account_id: String,
+    pub folder: String,
+    pub email: EmailSummary,
+}
+
+/// 搜索框查询语句（如 `from:alice subject:"周报" is:unread`）解析得到的语法树
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum SearchQuery {
+    /// 空查询，匹配全部邮件
+    All,
+    And(Vec<SearchQuery>),
+    Or(Vec<SearchQuery>),
+    Not(Box<SearchQuery>),
+    Term(SearchTerm),
+}
+
+/// 查询中的单个条件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum SearchTerm {
+    /// 不带字段的词，在邮件头和正文中搜索
+    Text(String),
+    From(String),
+    To(String),
+    Cc(String),
+    Subject(String),
+    Body(String),
+    HasAttachment,
+    Read,
+    Unread,
+    Starred,
+    Unstarred,
+    /// 早于该日期（不含）
+    Before(NaiveDate),
+    /// 不早于该日期（含）
+    After(NaiveDate),
+    /// 大于该字节数
+    Larger(u64),
+    /// 小于该字节数
+    Smaller(u64),
+    /// 限定搜索的文件夹
+    Folder(String),
+    /// 过滤规则添加的标签（IMAP 关键字）
+    Tag(String),
+}
+
+/// 虚拟文件夹名称的前缀，`fetch_emails` 遇到该前缀时从本地缓存解析已保存的搜索
+pub const VIRTUAL_FOLDER_PREFIX: &str = "virtual:";
+
+/// 已保存的搜索，作为虚拟文件夹显示（如“所有账户中来自重要联系人的未读邮件”）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub id: String,
+    pub name: String,
+    /// 查询语句，语法见 `SearchQuery::parse`
+    pub query: String,
+    /// 搜索的账户，为空表示所有账户
+    #[serde(default)]
+    pub account_ids: Vec<String>,
+}
+
+impl SavedSearch {
+    /// 作为文件夹使用时的名称
+    pub fn folder_name(&self) -> String {
+        format!("{}{}", VIRTUAL_FOLDER_PREFIX, self.id)
+    }
+
+    /// 从虚拟文件夹名称中取出已保存搜索的 ID，普通文件夹返回 None
+    pub fn id_from_folder(folder: &str) -> Option<&str> {
+        folder.strip_prefix(VIRTUAL_FOLDER_PREFIX)
+    }
+}
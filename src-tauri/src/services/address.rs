@@ -0,0 +1,216 @@
+use crate::models::Address;
+use crate::services::mime::decode_rfc2047;
+
+/// 地址列表中的词法单元
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// 原子或引号字符串（已去掉引号和转义）
+    Word(String),
+    /// 括号中的注释，旧式写法 `alice@example.com (Alice)` 用它作为名称
+    Comment(String),
+    /// 尖括号中的地址
+    Angle(String),
+    Comma,
+    Colon,
+    Semicolon,
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ',' => tokens.push(Token::Comma),
+            ':' => tokens.push(Token::Colon),
+            ';' => tokens.push(Token::Semicolon),
+            '"' => {
+                let mut word = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => word.extend(chars.next()),
+                        _ => word.push(c),
+                    }
+                }
+                tokens.push(Token::Word(word));
+            }
+            '(' => {
+                // 注释可以嵌套
+                let mut comment = String::new();
+                let mut depth = 1;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        '\\' => {
+                            comment.extend(chars.next());
+                            continue;
+                        }
+                        _ => {}
+                    }
+                    comment.push(c);
+                }
+                tokens.push(Token::Comment(comment));
+            }
+            '<' => {
+                let mut addr = String::new();
+                for c in chars.by_ref() {
+                    if c == '>' {
+                        break;
+                    }
+                    addr.push(c);
+                }
+                tokens.push(Token::Angle(addr));
+            }
+            c if c.is_whitespace() => {}
+            _ => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, ',' | ':' | ';' | '"' | '(' | '<') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    tokens
+}
+
+/// 正在解析的一个地址
+#[derive(Default)]
+struct Mailbox {
+    words: Vec<String>,
+    comment: Option<String>,
+    angle: Option<String>,
+}
+
+impl Mailbox {
+    fn finish(self) -> Option<Address> {
+        let (name, email) = match self.angle {
+            // 去掉过时的源路由（`<@relay:alice@example.com>`）
+            Some(angle) => {
+                let email = angle.rsplit(':').next().unwrap_or_default();
+                (self.words.join(" "), email.split_whitespace().collect::<String>())
+            }
+            None => (String::new(), self.words.concat()),
+        };
+
+        let name = decode_rfc2047(name.trim());
+        let name = Some(name)
+            .filter(|name| !name.is_empty())
+            .or_else(|| self.comment.map(|comment| decode_rfc2047(comment.trim())))
+            .filter(|name| !name.is_empty() && *name != email);
+
+        if email.is_empty() && name.is_none() {
+            return None;
+        }
+        Some(Address { name, email })
+    }
+}
+
+/// 解析 RFC 5322 地址列表（From、To、Cc 等邮件头的值）
+///
+/// 支持引号中的逗号、注释、组（`团队: a@x, b@y;`，只保留其中的成员）、
+/// 不带尖括号的地址和 RFC 2047 编码的名称；格式不完整时尽量保留能识别的部分
+pub fn parse_address_list(input: &str) -> Vec<Address> {
+    let mut addresses = Vec::new();
+    let mut current = Mailbox::default();
+
+    for token in tokenize(input) {
+        match token {
+            Token::Word(word) => current.words.push(word),
+            Token::Comment(comment) => {
+                if !comment.trim().is_empty() {
+                    current.comment = Some(comment);
+                }
+            }
+            Token::Angle(addr) => current.angle = Some(addr),
+            // 组名之后是成员列表
+            Token::Colon if current.angle.is_none() => current = Mailbox::default(),
+            Token::Colon => {}
+            Token::Comma | Token::Semicolon => {
+                addresses.extend(std::mem::take(&mut current).finish());
+            }
+        }
+    }
+
+    addresses.extend(current.finish());
+    addresses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(name: Option<&str>, email: &str) -> Address {
+        Address {
+            name: name.map(str::to_string),
+            email: email.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_common_forms() {
+        assert_eq!(
+            parse_address_list("\"Smith, John\" <john@example.com>, alice@example.com,Bob <bob@example.com>"),
+            vec![
+                addr(Some("Smith, John"), "john@example.com"),
+                addr(None, "alice@example.com"),
+                addr(Some("Bob"), "bob@example.com"),
+            ],
+        );
+        // 未加引号的多词名称和转义的引号
+        assert_eq!(
+            parse_address_list("Li  Na <li.na@example.com>, \"Tom \\\"T\\\" Lee\" <tom@example.com>"),
+            vec![addr(Some("Li Na"), "li.na@example.com"), addr(Some("Tom \"T\" Lee"), "tom@example.com")],
+        );
+        assert!(parse_address_list("").is_empty());
+        assert!(parse_address_list(" , ,").is_empty());
+    }
+
+    #[test]
+    fn test_parse_groups_and_comments() {
+        assert_eq!(
+            parse_address_list("项目组: a@example.com, \"B\" <b@example.com>;, c@example.com"),
+            vec![addr(None, "a@example.com"), addr(Some("B"), "b@example.com"), addr(None, "c@example.com")],
+        );
+        assert!(parse_address_list("undisclosed-recipients:;").is_empty());
+
+        // 旧式注释作为名称，名称之外的注释被忽略
+        assert_eq!(
+            parse_address_list("wang@example.cn (Wang (Fang)), Lee <lee@example.com> (work)"),
+            vec![addr(Some("Wang (Fang)"), "wang@example.cn"), addr(Some("Lee"), "lee@example.com")],
+        );
+        assert_eq!(
+            parse_address_list("<@relay.example.com:alice@example.com>"),
+            vec![addr(None, "alice@example.com")],
+        );
+    }
+
+    #[test]
+    fn test_parse_encoded_names() {
+        assert_eq!(
+            parse_address_list("\"=?GBK?B?1cXOsA==?=\" <zhangwei@163.com>, =?UTF-8?B?5p2O?= =?UTF-8?B?5aic?= <li@example.com>"),
+            vec![addr(Some("张伟"), "zhangwei@163.com"), addr(Some("李娜"), "li@example.com")],
+        );
+        // 解码后含逗号的名称不会被拆开
+        assert_eq!(
+            parse_address_list("=?UTF-8?Q?Smith=2C_John?= <john@example.com>"),
+            vec![addr(Some("Smith, John"), "john@example.com")],
+        );
+        // 名称与地址相同时不重复
+        assert_eq!(
+            parse_address_list("\"bob@example.com\" <bob@example.com>"),
+            vec![addr(None, "bob@example.com")],
+        );
+    }
+}
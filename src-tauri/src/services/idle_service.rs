@@ -0,0 +1,383 @@
+use crate::models::EmailAccount;
+use crate::services::imap_raw::{quote_mailbox, ImapResponse, RawImapConnection};
+use crate::services::{ImapService, StorageService, SyncManager};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Runtime};
+
+/// IDLE 最长保持时间（秒），RFC 2177 建议不超过 29 分钟重新发起
+const IDLE_RENEW_SECONDS: u64 = 25 * 60;
+
+/// 不支持 IDLE 时的 NOOP 轮询间隔（秒）
+const NOOP_POLL_SECONDS: u64 = 60;
+
+/// 断线重连的最短/最长等待时间（秒）
+const RETRY_MIN_SECONDS: u64 = 5;
+const RETRY_MAX_SECONDS: u64 = 300;
+
+/// 检测到新邮件时每次增量同步的最大邮件数
+const WATCH_SYNC_LIMIT: usize = 50;
+
+/// 轮询间隔和重连等待时间
+#[derive(Debug, Clone, Copy)]
+struct WatchTiming {
+    poll: Duration,
+    retry_min: Duration,
+    retry_max: Duration,
+}
+
+impl Default for WatchTiming {
+    fn default() -> Self {
+        Self {
+            poll: Duration::from_secs(NOOP_POLL_SECONDS),
+            retry_min: Duration::from_secs(RETRY_MIN_SECONDS),
+            retry_max: Duration::from_secs(RETRY_MAX_SECONDS),
+        }
+    }
+}
+
+/// 正在监听的文件夹
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MailWatchInfo {
+    pub account_id: String,
+    pub folder: String,
+}
+
+/// 监听连接的读超时，以便在 IDLE 期间及时响应停止请求
+const WATCH_READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 进入 IDLE 直到收到新邮件通知、到达续期时间或被要求停止
+/// 返回是否收到了新邮件通知
+fn idle(conn: &mut RawImapConnection, stop: &AtomicBool) -> Result<bool, String> {
+    let tag = conn.next_tag();
+    conn.send(&format!("{} IDLE", tag))?;
+
+    let mut changed = false;
+    loop {
+        let response = conn.wait_response()?;
+        if response.tag == "+" {
+            break;
+        }
+        if response.tag == tag {
+            return Err(format!("IDLE失败: {}", response.text));
+        }
+        changed |= is_new_mail(&response);
+    }
+
+    let started = Instant::now();
+    while !changed
+        && !stop.load(Ordering::SeqCst)
+        && started.elapsed() < Duration::from_secs(IDLE_RENEW_SECONDS)
+    {
+        if let Some(response) = conn.read_response()? {
+            changed |= is_new_mail(&response);
+        }
+    }
+
+    conn.send("DONE")?;
+    let responses = conn.wait_tagged(&tag)?;
+    Ok(changed || responses.iter().any(is_new_mail))
+}
+
+/// 等待一个轮询周期后发送 NOOP
+/// 部分服务器（如163）不会在 NOOP 中报告 EXISTS，因此每个周期都触发一次增量同步，
+/// 增量同步先比较 UIDNEXT，没有新邮件时代价很小
+fn poll(conn: &mut RawImapConnection, interval: Duration, stop: &AtomicBool) -> Result<bool, String> {
+    if !sleep_unless_stopped(interval, stop) {
+        return Ok(false);
+    }
+    conn.run("NOOP")?;
+    Ok(true)
+}
+
+/// 后台新邮件监听：每个账户/文件夹一个线程
+/// 服务器支持 IDLE 时使用 IDLE，否则定期 NOOP 轮询；检测到新邮件后执行增量同步，
+/// 新邮件通过 `mail://new` 事件推送给前端
+pub struct MailWatchService {
+    watchers: Mutex<HashMap<(String, String), Arc<AtomicBool>>>,
+    timing: WatchTiming,
+}
+
+impl Default for MailWatchService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MailWatchService {
+    pub fn new() -> Self {
+        Self {
+            watchers: Mutex::new(HashMap::new()),
+            timing: WatchTiming::default(),
+        }
+    }
+
+    /// 开始监听文件夹，已在监听时返回 false
+    pub fn start<R: Runtime>(
+        &self,
+        app: AppHandle<R>,
+        storage: Arc<StorageService>,
+        sync_manager: Arc<SyncManager>,
+        account: EmailAccount,
+        password: String,
+        folder: String,
+    ) -> bool {
+        let key = (account.id.clone(), folder.clone());
+        let mut watchers = self.watchers.lock().unwrap();
+        if watchers.contains_key(&key) {
+            return false;
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        watchers.insert(key, stop.clone());
+
+        let timing = self.timing;
+        thread::spawn(move || {
+            let imap_service = ImapService::new(account.clone(), password.clone());
+            let sync = || {
+                let result = tauri::async_runtime::block_on(sync_manager.sync_folder(
+                    &app, &storage, &imap_service, &account.id, &folder, WATCH_SYNC_LIMIT, false,
+                ));
+                if let Err(e) = result {
+                    eprintln!("后台同步失败 ({} / {}): {}", account.id, folder, e);
+                }
+            };
+            run_watcher(&account, &password, &folder, &stop, timing, &sync);
+        });
+
+        true
+    }
+
+    /// 停止监听文件夹，未在监听时返回 false
+    pub fn stop(&self, account_id: &str, folder: &str) -> bool {
+        let key = (account_id.to_string(), folder.to_string());
+        match self.watchers.lock().unwrap().remove(&key) {
+            Some(stop) => {
+                stop.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 停止某个账户的全部监听
+    pub fn stop_account(&self, account_id: &str) {
+        self.watchers.lock().unwrap().retain(|(id, _), stop| {
+            if id == account_id {
+                stop.store(true, Ordering::SeqCst);
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    pub fn list(&self) -> Vec<MailWatchInfo> {
+        let mut list: Vec<MailWatchInfo> = self.watchers
+            .lock()
+            .unwrap()
+            .keys()
+            .map(|(account_id, folder)| MailWatchInfo {
+                account_id: account_id.clone(),
+                folder: folder.clone(),
+            })
+            .collect();
+        list.sort_by(|a, b| (&a.account_id, &a.folder).cmp(&(&b.account_id, &b.folder)));
+        list
+    }
+}
+
+/// 监听线程主循环：连接失败或断线后按指数退避重连
+fn run_watcher(
+    account: &EmailAccount,
+    password: &str,
+    folder: &str,
+    stop: &AtomicBool,
+    timing: WatchTiming,
+    on_change: &dyn Fn(),
+) {
+    let mut retry = timing.retry_min;
+
+    while !stop.load(Ordering::SeqCst) {
+        match watch_folder(account, password, folder, stop, timing.poll, on_change) {
+            Ok(()) => break,
+            Err(e) => {
+                eprintln!(
+                    "邮件监听中断 ({} / {})，{} 秒后重连: {}",
+                    account.id, folder, retry.as_secs_f32(), e
+                );
+                if !sleep_unless_stopped(retry, stop) {
+                    break;
+                }
+                retry = (retry * 2).min(timing.retry_max);
+            }
+        }
+    }
+}
+
+/// 建立连接并监听，直到 stop 置位（返回 Ok）或连接出错
+fn watch_folder(
+    account: &EmailAccount,
+    password: &str,
+    folder: &str,
+    stop: &AtomicBool,
+    poll_interval: Duration,
+    on_change: &dyn Fn(),
+) -> Result<(), String> {
+    let mut conn = RawImapConnection::open(account, password, WATCH_READ_TIMEOUT)?;
+    let supports_idle = conn.capabilities()?.iter().any(|c| c == "IDLE");
+
+    conn.run(&format!("SELECT {}", quote_mailbox(folder)))
+        .map_err(|e| format!("选择文件夹 '{}' 失败: {}", folder, e))?;
+
+    // 连接（或重连）后先同步一次，补上断线期间的新邮件
+    on_change();
+
+    while !stop.load(Ordering::SeqCst) {
+        let changed = if supports_idle {
+            idle(&mut conn, stop)?
+        } else {
+            poll(&mut conn, poll_interval, stop)?
+        };
+        if changed && !stop.load(Ordering::SeqCst) {
+            on_change();
+        }
+    }
+
+    conn.logout();
+    Ok(())
+}
+
+/// 分段睡眠以便及时响应停止请求，被停止时返回 false
+fn sleep_unless_stopped(duration: Duration, stop: &AtomicBool) -> bool {
+    let started = Instant::now();
+    while started.elapsed() < duration {
+        if stop.load(Ordering::SeqCst) {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(500).min(duration.saturating_sub(started.elapsed())));
+    }
+    !stop.load(Ordering::SeqCst)
+}
+
+/// `* <n> EXISTS` 表示文件夹中有新邮件
+fn is_new_mail(response: &ImapResponse) -> bool {
+    response.kind().as_deref() == Some("EXISTS")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::imap_service::tests::{account, mailboxes, spawn_server, MockMessage};
+    use crate::services::test_support::storage;
+    use std::sync::atomic::AtomicUsize;
+
+    /// 测试用的较短间隔
+    const FAST: WatchTiming = WatchTiming {
+        poll: Duration::from_millis(100),
+        retry_min: Duration::from_millis(100),
+        retry_max: Duration::from_millis(150),
+    };
+
+    fn wait_for(condition: impl Fn() -> bool) {
+        for _ in 0..100 {
+            if condition() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        panic!("等待超时");
+    }
+
+    fn cached_uids(storage: &StorageService, account: &EmailAccount) -> Vec<u32> {
+        storage.get_cached_email_summaries(&account.id, "INBOX").unwrap()
+            .map(|cached| cached.emails.iter().map(|e| e.uid).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_watch_polls_with_noop_without_idle() {
+        let storage = Arc::new(storage());
+        let state = mailboxes(&[10]);
+        let account = account("watch@example.com", spawn_server(Arc::clone(&state)));
+        let watcher = MailWatchService { watchers: Mutex::new(HashMap::new()), timing: FAST };
+        let app = tauri::test::mock_app();
+        let start = || watcher.start(
+            app.handle().clone(),
+            storage.clone(),
+            Arc::new(SyncManager::new()),
+            account.clone(),
+            "secret".to_string(),
+            "INBOX".to_string(),
+        );
+
+        assert!(start());
+        assert!(!start());
+        // 连接后先同步一次
+        wait_for(|| cached_uids(&storage, &account) == [10]);
+
+        // 替身服务器不支持 IDLE，NOOP 轮询时同步到新邮件
+        {
+            let mut state = state.lock().unwrap();
+            state.folders.get_mut("INBOX").unwrap().push(MockMessage::new(20));
+            state.uid_next = 21;
+        }
+        wait_for(|| cached_uids(&storage, &account) == [20, 10]);
+        assert!(state.lock().unwrap().noops > 0);
+
+        assert!(watcher.stop(&account.id, "INBOX"));
+        assert!(!watcher.stop(&account.id, "INBOX"));
+        assert!(watcher.list().is_empty());
+        // 监听线程登出后退出
+        wait_for(|| state.lock().unwrap().logouts == 1);
+    }
+
+    #[test]
+    fn test_watch_reconnects_with_backoff() {
+        let state = mailboxes(&[]);
+        state.lock().unwrap().fail_logins = 3;
+        let account = account("retry@example.com", spawn_server(Arc::clone(&state)));
+        let stop = AtomicBool::new(false);
+        let changes = AtomicUsize::new(0);
+
+        let started = Instant::now();
+        run_watcher(&account, "secret", "INBOX", &stop, FAST, &|| {
+            changes.fetch_add(1, Ordering::SeqCst);
+            stop.store(true, Ordering::SeqCst);
+        });
+
+        // 三次登录失败后依次等待 100、150、150 毫秒：等待时间翻倍，但不超过上限
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(400), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(700), "{:?}", elapsed);
+        assert_eq!(changes.load(Ordering::SeqCst), 1);
+        let state = state.lock().unwrap();
+        assert_eq!((state.fail_logins, state.logins, state.logouts), (0, 1, 1));
+    }
+
+    #[test]
+    fn test_watch_stops_during_backoff() {
+        let state = mailboxes(&[]);
+        state.lock().unwrap().fail_logins = usize::MAX;
+        let account = account("stop@example.com", spawn_server(Arc::clone(&state)));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let handle = thread::spawn({
+            let stop = stop.clone();
+            let timing = WatchTiming { retry_min: Duration::from_secs(60), ..FAST };
+            move || run_watcher(&account, "secret", "INBOX", &stop, timing, &|| panic!("不应登录成功"))
+        });
+        wait_for(|| state.lock().unwrap().fail_logins < usize::MAX);
+
+        // 重连等待中被停止时不必等到下次重连
+        let stopped = Instant::now();
+        stop.store(true, Ordering::SeqCst);
+        handle.join().unwrap();
+        assert!(stopped.elapsed() < Duration::from_secs(2));
+        assert_eq!(state.lock().unwrap().fail_logins, usize::MAX - 1);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::services::imap_raw::{mailbox_name, RawImapConnection};
+use native_tls::TlsStream;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// IMAP 连接，只有连接本机（如本地邮件桥接程序）时才使用明文
+pub enum ImapStream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl Read for ImapStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            ImapStream::Plain(s) => s.read(buf),
+            ImapStream::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for ImapStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            ImapStream::Plain(s) => s.write(buf),
+            ImapStream::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            ImapStream::Plain(s) => s.flush(),
+            ImapStream::Tls(s) => s.flush(),
+        }
+    }
+}
+
+pub type ImapSession = imap::Session<ImapStream>;
+
+/// 闲置超过该时间（秒）的会话复用前先发送 NOOP 确认连接仍然可用
+const HEALTH_CHECK_SECONDS: u64 = 60;
+
+/// 闲置超过该时间（秒）的会话直接丢弃，服务器通常在 30 分钟无操作后自动登出
+const MAX_IDLE_SECONDS: u64 = 10 * 60;
+
+/// 每个账户最多保留的闲置会话数
+const MAX_IDLE_SESSIONS: usize = 3;
+
+/// 连接池中的已登录会话，记录当前选中的文件夹以避免重复 SELECT
+pub struct PooledSession {
+    pub session: ImapSession,
+    selected: Option<String>,
+    capabilities: HashMap<String, bool>,
+    last_used: Instant,
+}
+
+impl PooledSession {
+    pub fn new(session: ImapSession) -> Self {
+        Self {
+            session,
+            selected: None,
+            capabilities: HashMap::new(),
+            last_used: Instant::now(),
+        }
+    }
+
+    /// 选中文件夹，已选中时不重复发送 SELECT
+    pub fn select(&mut self, folder: &str) -> Result<(), String> {
+        if self.selected.as_deref() == Some(folder) {
+            return Ok(());
+        }
+        self.reselect(folder).map(|_| ())
+    }
+
+    /// 重新 SELECT 文件夹并返回最新的文件夹状态
+    pub fn reselect(&mut self, folder: &str) -> Result<imap::types::Mailbox, String> {
+        self.selected = None;
+
+        // imap 库会给名称加引号，这里只做修改版 UTF-7 编码
+        let name = mailbox_name(folder);
+        let session = &mut self.session;
+        let mailbox = session
+            .select(&name)
+            .or_else(|_| session.select(format!("\"{}\"", name)))
+            .map_err(|e| format!("选择文件夹 '{}' 失败: {}", folder, e))?;
+
+        self.selected = Some(folder.to_string());
+        Ok(mailbox)
+    }
+
+    /// 服务器是否支持某项扩展（如 UIDPLUS、MOVE），结果按会话缓存
+    pub fn has_capability(&mut self, name: &str) -> bool {
+        if let Some(supported) = self.capabilities.get(name) {
+            return *supported;
+        }
+
+        let supported = self.session
+            .capabilities()
+            .map(|caps| caps.has_str(name))
+            .unwrap_or(false);
+        self.capabilities.insert(name.to_string(), supported);
+        supported
+    }
+
+    /// 连接是否仍然可用
+    pub fn is_alive(&mut self) -> bool {
+        self.session.noop().is_ok()
+    }
+
+    fn close(mut self) {
+        let _ = self.session.logout();
+    }
+}
+
+/// 池中闲置的直接收发命令的连接
+struct IdleRawConnection {
+    conn: RawImapConnection,
+    last_used: Instant,
+}
+
+/// 按账户保存已登录 IMAP 会话的连接池，所有 ImapService 实例共享
+pub struct ImapPool {
+    idle: Mutex<HashMap<String, Vec<PooledSession>>>,
+    /// 直接收发命令的连接（按 CONDSTORE/QRESYNC 同步标志时使用），每个账户保留一个
+    raw: Mutex<HashMap<String, IdleRawConnection>>,
+}
+
+impl ImapPool {
+    fn new() -> Self {
+        Self {
+            idle: Mutex::new(HashMap::new()),
+            raw: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn global() -> &'static ImapPool {
+        static POOL: OnceLock<ImapPool> = OnceLock::new();
+        POOL.get_or_init(ImapPool::new)
+    }
+
+    /// 取出账户的一个可用会话，没有时返回 None
+    /// 闲置过久的会话会被丢弃，闲置较久的会话先用 NOOP 检查，服务器已断开的连接不会被取出
+    pub fn checkout(&self, account_id: &str) -> Option<PooledSession> {
+        loop {
+            let mut pooled = self.idle.lock().unwrap().get_mut(account_id)?.pop()?;
+
+            let idle = pooled.last_used.elapsed();
+            if idle >= Duration::from_secs(MAX_IDLE_SECONDS) {
+                pooled.close();
+                continue;
+            }
+            if idle >= Duration::from_secs(HEALTH_CHECK_SECONDS) && !pooled.is_alive() {
+                continue;
+            }
+
+            return Some(pooled);
+        }
+    }
+
+    /// 归还会话；超过闲置上限时登出最早归还的会话
+    pub fn checkin(&self, account_id: &str, mut pooled: PooledSession) {
+        pooled.last_used = Instant::now();
+        // imap 库把未请求的响应（EXISTS、EXPUNGE 等）放进通道，没有人读取，
+        // 长期复用的会话不清空会一直累积
+        while pooled.session.unsolicited_responses.try_recv().is_ok() {}
+
+        let evicted = {
+            let mut idle = self.idle.lock().unwrap();
+            let sessions = idle.entry(account_id.to_string()).or_default();
+            sessions.push(pooled);
+            if sessions.len() > MAX_IDLE_SESSIONS {
+                Some(sessions.remove(0))
+            } else {
+                None
+            }
+        };
+
+        if let Some(evicted) = evicted {
+            evicted.close();
+        }
+    }
+
+    /// 取出账户闲置的直接收发命令的连接，闲置过久或已断开时返回 None（规则同 `checkout`）
+    pub fn checkout_raw(&self, account_id: &str) -> Option<RawImapConnection> {
+        let IdleRawConnection { mut conn, last_used } = self.raw.lock().unwrap().remove(account_id)?;
+
+        let idle = last_used.elapsed();
+        if idle >= Duration::from_secs(MAX_IDLE_SECONDS) {
+            conn.logout();
+            return None;
+        }
+        if idle >= Duration::from_secs(HEALTH_CHECK_SECONDS) && conn.run("NOOP").is_err() {
+            return None;
+        }
+
+        Some(conn)
+    }
+
+    /// 归还直接收发命令的连接，账户已有闲置连接时登出多余的一个
+    pub fn checkin_raw(&self, account_id: &str, conn: RawImapConnection) {
+        let idle = IdleRawConnection { conn, last_used: Instant::now() };
+        let replaced = self.raw.lock().unwrap().insert(account_id.to_string(), idle);
+        if let Some(replaced) = replaced {
+            replaced.conn.logout();
+        }
+    }
+
+    /// 登出并移除账户的全部闲置会话（删除账户时调用）
+    pub fn clear_account(&self, account_id: &str) {
+        let sessions = self.idle.lock().unwrap().remove(account_id);
+        for pooled in sessions.into_iter().flatten() {
+            pooled.close();
+        }
+        if let Some(idle) = self.raw.lock().unwrap().remove(account_id) {
+            idle.conn.logout();
+        }
+    }
+}
@@ -0,0 +1,798 @@
+use crate::models::EmailAccount;
+use crate::services::imap_pool::ImapStream;
+use crate::services::imap_service::{needs_imap_id, open_imap_stream, IMAP_ID_COMMAND};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::time::{Duration, Instant};
+
+/// 等待服务器响应的超时时间（秒）
+const RESPONSE_TIMEOUT_SECONDS: u64 = 60;
+
+/// 响应中的一个值
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImapValue {
+    Nil,
+    /// 原子、数字、标志（如 `\Seen`）以及 `BODY[HEADER]<0>` 这样的属性名
+    Atom(String),
+    /// 带引号的字符串或字面量，保留原始字节
+    Bytes(Vec<u8>),
+    List(Vec<ImapValue>),
+}
+
+impl ImapValue {
+    pub fn as_atom(&self) -> Option<&str> {
+        match self {
+            ImapValue::Atom(atom) => Some(atom),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<u64> {
+        self.as_atom().and_then(|atom| atom.parse().ok())
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            ImapValue::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[ImapValue]> {
+        match self {
+            ImapValue::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// 原子或字符串的文本形式
+    pub fn as_text(&self) -> Option<String> {
+        match self {
+            ImapValue::Atom(atom) => Some(atom.clone()),
+            ImapValue::Bytes(bytes) => Some(String::from_utf8_lossy(bytes).to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// 一条完整的服务器响应（包括其中的字面量）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImapResponse {
+    /// `*`（未标记响应）、`+`（继续请求）或命令标签
+    pub tag: String,
+    /// 状态响应为 `[OK]` 等状态字，其他响应为全部数据
+    pub values: Vec<ImapValue>,
+    /// 状态响应中方括号内的响应码，如 `[UIDNEXT 31]`
+    pub code: Vec<ImapValue>,
+    /// 状态响应或继续请求后的说明文字
+    pub text: String,
+}
+
+impl ImapResponse {
+    /// 状态响应的状态字（OK/NO/BAD/BYE/PREAUTH）
+    pub fn status(&self) -> Option<&str> {
+        match self.values.as_slice() {
+            [ImapValue::Atom(word)] if is_status(word) => Some(word.as_str()),
+            _ => None,
+        }
+    }
+
+    /// 未标记数据响应的类型，如 `* 3 EXISTS` 为 EXISTS，`* SEARCH 1 2` 为 SEARCH
+    pub fn kind(&self) -> Option<String> {
+        if self.tag != "*" {
+            return None;
+        }
+        let word = match self.values.first()? {
+            ImapValue::Atom(atom) if atom.parse::<u64>().is_ok() => self.values.get(1)?.as_atom()?,
+            ImapValue::Atom(atom) => atom.as_str(),
+            _ => return None,
+        };
+        Some(word.to_ascii_uppercase())
+    }
+}
+
+fn is_status(word: &str) -> bool {
+    matches!(
+        word.to_ascii_uppercase().as_str(),
+        "OK" | "NO" | "BAD" | "BYE" | "PREAUTH"
+    )
+}
+
+/// 一条 FETCH 响应
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchData {
+    pub seq: u32,
+    pub attributes: Vec<(String, ImapValue)>,
+}
+
+impl FetchData {
+    pub fn from_response(response: &ImapResponse) -> Option<Self> {
+        if response.kind().as_deref() != Some("FETCH") {
+            return None;
+        }
+        let seq = response.values.first()?.as_number()? as u32;
+        let items = response.values.get(2)?.as_list()?;
+
+        let attributes = items
+            .chunks(2)
+            .filter_map(|pair| match pair {
+                [ImapValue::Atom(name), value] => Some((name.to_ascii_uppercase(), value.clone())),
+                _ => None,
+            })
+            .collect();
+
+        Some(Self { seq, attributes })
+    }
+
+    /// 按名称查找属性（不区分大小写）
+    pub fn get(&self, name: &str) -> Option<&ImapValue> {
+        let name = name.to_ascii_uppercase();
+        self.attributes.iter().find(|(n, _)| *n == name).map(|(_, v)| v)
+    }
+
+    pub fn uid(&self) -> Option<u32> {
+        self.get("UID")?.as_number().map(|uid| uid as u32)
+    }
+
+    pub fn flags(&self) -> Vec<String> {
+        self.get("FLAGS")
+            .and_then(ImapValue::as_list)
+            .map(|flags| flags.iter().filter_map(ImapValue::as_text).collect())
+            .unwrap_or_default()
+    }
+
+    /// 邮件原文（RFC822、BODY[] 或 BINARY[]），按字节原样返回
+    pub fn message(&self) -> Option<&[u8]> {
+        ["BODY[]", "RFC822", "BINARY[]"]
+            .iter()
+            .find_map(|name| self.get(name))
+            .and_then(ImapValue::as_bytes)
+    }
+}
+
+/// 从连接中按字节读取完整响应
+/// 行尾为 `{n}` 时紧接着读取 n 个字节的字面量，字面量中的换行、`)` 或像标签的行都不会截断响应
+#[derive(Debug, Default)]
+pub struct ResponseReader {
+    buffer: Vec<u8>,
+    /// 当前行在 buffer 中的起点
+    line_start: usize,
+    /// 尚未读取的字面量字节数
+    literal_remaining: usize,
+}
+
+impl ResponseReader {
+    /// 读取一条完整响应的原始字节
+    /// 读超时返回 Ok(None)，已读到的部分保留到下次调用
+    pub fn read<R: BufRead>(&mut self, reader: &mut R) -> std::io::Result<Option<Vec<u8>>> {
+        loop {
+            if self.literal_remaining > 0 {
+                let available = match reader.fill_buf() {
+                    Ok(available) => available,
+                    Err(e) if is_timeout(&e) => return Ok(None),
+                    Err(e) => return Err(e),
+                };
+                if available.is_empty() {
+                    return Err(ErrorKind::UnexpectedEof.into());
+                }
+                let n = available.len().min(self.literal_remaining);
+                self.buffer.extend_from_slice(&available[..n]);
+                reader.consume(n);
+                self.literal_remaining -= n;
+                continue;
+            }
+
+            match reader.read_until(b'\n', &mut self.buffer) {
+                Ok(_) if self.buffer.ends_with(b"\n") => {
+                    match literal_length(&self.buffer[self.line_start..]) {
+                        Some(length) => {
+                            self.literal_remaining = length;
+                            self.line_start = self.buffer.len() + length;
+                        }
+                        None => {
+                            self.line_start = 0;
+                            return Ok(Some(std::mem::take(&mut self.buffer)));
+                        }
+                    }
+                }
+                Ok(_) => return Err(ErrorKind::UnexpectedEof.into()),
+                Err(e) if is_timeout(&e) => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+fn is_timeout(e: &std::io::Error) -> bool {
+    matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+/// 行尾的字面量长度：`{n}`、非同步字面量 `{n+}` 或 BINARY 的 `~{n}`
+fn literal_length(line: &[u8]) -> Option<usize> {
+    let line = line.strip_suffix(b"\n")?;
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let inner = line.strip_suffix(b"}")?;
+    let open = inner.iter().rposition(|b| *b == b'{')?;
+    let digits = &inner[open + 1..];
+    let digits = digits.strip_suffix(b"+").unwrap_or(digits);
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+/// 解析一段数据中的全部响应
+pub fn parse_responses(data: &[u8]) -> Result<Vec<ImapResponse>, String> {
+    let mut parser = Parser { data, pos: 0 };
+    let mut responses = Vec::new();
+    while parser.pos < data.len() {
+        responses.push(parser.response()?);
+    }
+    Ok(responses)
+}
+
+/// 解析一条响应
+pub fn parse_response(data: &[u8]) -> Result<ImapResponse, String> {
+    Parser { data, pos: 0 }.response()
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn at_line_end(&self) -> bool {
+        matches!(self.peek(), None | Some(b'\r') | Some(b'\n'))
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == Some(b' ') {
+            self.pos += 1;
+        }
+    }
+
+    fn finish_line(&mut self) {
+        if self.peek() == Some(b'\r') {
+            self.pos += 1;
+        }
+        if self.peek() == Some(b'\n') {
+            self.pos += 1;
+        }
+    }
+
+    /// 读到行尾的说明文字
+    fn rest_of_line(&mut self) -> String {
+        let start = self.pos;
+        while !self.at_line_end() {
+            self.pos += 1;
+        }
+        String::from_utf8_lossy(&self.data[start..self.pos]).trim().to_string()
+    }
+
+    fn response(&mut self) -> Result<ImapResponse, String> {
+        let tag = self.atom()?;
+        self.skip_spaces();
+
+        let mut response = ImapResponse {
+            tag,
+            values: Vec::new(),
+            code: Vec::new(),
+            text: String::new(),
+        };
+
+        if response.tag == "+" {
+            response.text = self.rest_of_line();
+            self.finish_line();
+            return Ok(response);
+        }
+
+        while !self.at_line_end() {
+            let value = self.value()?;
+            let is_status_word = response.values.is_empty()
+                && value.as_atom().is_some_and(is_status);
+            response.values.push(value);
+
+            // 状态响应的说明文字可能含有不成对的括号或引号，不再按值解析
+            if is_status_word {
+                self.skip_spaces();
+                if self.peek() == Some(b'[') {
+                    self.pos += 1;
+                    response.code = self.values_until(b']')?;
+                }
+                response.text = self.rest_of_line();
+                break;
+            }
+            self.skip_spaces();
+        }
+
+        self.finish_line();
+        Ok(response)
+    }
+
+    fn values_until(&mut self, end: u8) -> Result<Vec<ImapValue>, String> {
+        let mut values = Vec::new();
+        loop {
+            self.skip_spaces();
+            match self.peek() {
+                Some(b) if b == end => {
+                    self.pos += 1;
+                    return Ok(values);
+                }
+                None => return Err("响应意外结束".to_string()),
+                _ => values.push(self.value()?),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<ImapValue, String> {
+        match self.peek() {
+            Some(b'(') => {
+                self.pos += 1;
+                Ok(ImapValue::List(self.values_until(b')')?))
+            }
+            Some(b'"') => self.quoted(),
+            Some(b'{') => self.literal(),
+            Some(b'~') if self.data.get(self.pos + 1) == Some(&b'{') => {
+                self.pos += 1;
+                self.literal()
+            }
+            Some(_) => {
+                let atom = self.atom()?;
+                if atom.eq_ignore_ascii_case("NIL") {
+                    Ok(ImapValue::Nil)
+                } else {
+                    Ok(ImapValue::Atom(atom))
+                }
+            }
+            None => Err("响应意外结束".to_string()),
+        }
+    }
+
+    fn quoted(&mut self) -> Result<ImapValue, String> {
+        self.pos += 1;
+        let mut bytes = Vec::new();
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(ImapValue::Bytes(bytes));
+                }
+                Some(b'\\') => {
+                    let escaped = *self.data.get(self.pos + 1).ok_or("字符串未结束")?;
+                    bytes.push(escaped);
+                    self.pos += 2;
+                }
+                Some(b'\r') | Some(b'\n') | None => return Err("字符串未结束".to_string()),
+                Some(b) => {
+                    bytes.push(b);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn literal(&mut self) -> Result<ImapValue, String> {
+        let close = self.data[self.pos..]
+            .iter()
+            .position(|b| *b == b'}')
+            .ok_or("字面量长度未结束")?;
+        let spec = String::from_utf8_lossy(&self.data[self.pos + 1..self.pos + close]).to_string();
+        let length: usize = spec
+            .trim_end_matches('+')
+            .parse()
+            .map_err(|_| format!("无效的字面量长度: {}", spec))?;
+        self.pos += close + 1;
+
+        if self.data[self.pos..].starts_with(b"\r\n") {
+            self.pos += 2;
+        } else if self.peek() == Some(b'\n') {
+            self.pos += 1;
+        } else {
+            return Err("字面量长度后缺少换行".to_string());
+        }
+
+        let end = self.pos + length;
+        if end > self.data.len() {
+            return Err(format!(
+                "字面量不完整: 需要 {} 字节，只有 {} 字节",
+                length,
+                self.data.len() - self.pos
+            ));
+        }
+        let bytes = self.data[self.pos..end].to_vec();
+        self.pos = end;
+        Ok(ImapValue::Bytes(bytes))
+    }
+
+    /// 原子一直读到空格、括号、引号或行尾；方括号内（如 `BODY[HEADER.FIELDS (FROM)]`）的内容整体计入，
+    /// 紧跟的 `<0>` 部分范围也计入
+    fn atom(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            match b {
+                b' ' | b'(' | b')' | b']' | b'"' | b'\r' | b'\n' => break,
+                b'[' => {
+                    let close = self.data[self.pos..]
+                        .iter()
+                        .position(|b| *b == b']')
+                        .ok_or("方括号未结束")?;
+                    self.pos += close + 1;
+                    if self.peek() == Some(b'<') {
+                        let close = self.data[self.pos..]
+                            .iter()
+                            .position(|b| *b == b'>')
+                            .ok_or("部分范围未结束")?;
+                        self.pos += close + 1;
+                    }
+                }
+                _ => self.pos += 1,
+            }
+        }
+
+        if self.pos == start {
+            return Err(format!("无法解析的响应内容: {}", String::from_utf8_lossy(&self.data[start..])));
+        }
+        Ok(String::from_utf8_lossy(&self.data[start..self.pos]).to_string())
+    }
+}
+
+/// 直接收发 IMAP 命令的连接，用于 imap crate 不支持的扩展（IDLE 的可中断等待等）
+/// 所有响应都经过 ResponseReader 按字节读取，读超时由调用方设置
+pub struct RawImapConnection {
+    reader: BufReader<ImapStream>,
+    responses: ResponseReader,
+    tag: u32,
+}
+
+impl RawImapConnection {
+    /// 连接并登录；read_timeout 决定 read_response 多久返回一次 None
+    pub fn open(account: &EmailAccount, password: &str, read_timeout: Duration) -> Result<Self, String> {
+        let stream = open_imap_stream(account, Some(read_timeout))?;
+        let mut conn = Self {
+            reader: BufReader::new(stream),
+            responses: ResponseReader::default(),
+            tag: 0,
+        };
+
+        // 读取欢迎信息
+        let greeting = conn.wait_response()?;
+        if !matches!(greeting.status(), Some("OK") | Some("PREAUTH")) {
+            return Err(format!("IMAP服务器拒绝连接: {}", greeting.text));
+        }
+
+        conn.run(&format!("LOGIN {} {}", quote(&account.email), quote(password)))
+            .map_err(|e| format!("IMAP登录失败: {}", e))?;
+
+        // 网易邮箱要求发送ID命令
+        if needs_imap_id(account) {
+            conn.run(IMAP_ID_COMMAND)?;
+        }
+
+        Ok(conn)
+    }
+
+    pub fn next_tag(&mut self) -> String {
+        self.tag += 1;
+        format!("R{:03}", self.tag)
+    }
+
+    pub fn send(&mut self, line: &str) -> Result<(), String> {
+        let stream = self.reader.get_mut();
+        stream
+            .write_all(format!("{}\r\n", line).as_bytes())
+            .and_then(|_| stream.flush())
+            .map_err(|e| format!("发送命令失败: {}", e))
+    }
+
+    /// 读取一条响应，读超时返回 None
+    pub fn read_response(&mut self) -> Result<Option<ImapResponse>, String> {
+        match self.responses.read(&mut self.reader) {
+            Ok(Some(data)) => parse_response(&data).map(Some),
+            Ok(None) => Ok(None),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Err("服务器关闭了连接".to_string()),
+            Err(e) => Err(format!("读取响应失败: {}", e)),
+        }
+    }
+
+    /// 读取一条响应，超过 RESPONSE_TIMEOUT_SECONDS 未收到则返回错误
+    pub fn wait_response(&mut self) -> Result<ImapResponse, String> {
+        let started = Instant::now();
+        loop {
+            if let Some(response) = self.read_response()? {
+                return Ok(response);
+            }
+            if started.elapsed() >= Duration::from_secs(RESPONSE_TIMEOUT_SECONDS) {
+                return Err("等待服务器响应超时".to_string());
+            }
+        }
+    }
+
+    /// 读取到标签响应为止，返回此前的未标记响应；标签响应不是 OK 时返回错误
+    pub fn wait_tagged(&mut self, tag: &str) -> Result<Vec<ImapResponse>, String> {
+        let mut responses = Vec::new();
+        loop {
+            let response = self.wait_response()?;
+            if response.tag == tag {
+                return match response.status() {
+                    Some(status) if status.eq_ignore_ascii_case("OK") => Ok(responses),
+                    Some(status) => Err(format!("{} {}", status, response.text)),
+                    None => Err("无效的标签响应".to_string()),
+                };
+            }
+            responses.push(response);
+        }
+    }
+
+    /// 执行一条命令，返回未标记响应
+    ///
+    /// 命令中的同步字面量（行尾的 `{n}`）先发送到行尾，收到服务器的继续请求后再发送字面量内容；
+    /// 非同步字面量 `{n+}` 直接随命令发送
+    pub fn run(&mut self, command: &str) -> Result<Vec<ImapResponse>, String> {
+        let tag = self.next_tag();
+        let command = format!("{} {}", tag, command);
+        let mut responses = Vec::new();
+
+        let (mut sent, mut pos) = (0, 0);
+        while let Some(offset) = command[pos..].find("\r\n") {
+            let line_end = pos + offset + 2;
+            let literal = literal_length(&command.as_bytes()[pos..line_end]);
+            if literal.is_some() && !command[..line_end - 2].ends_with("+}") {
+                self.send(&command[sent..line_end - 2])?;
+                responses.extend(self.wait_continuation(&tag)?);
+                sent = line_end;
+            }
+            // 跳过字面量内容，其中的换行不是命令的一部分
+            pos = (line_end + literal.unwrap_or(0)).min(command.len());
+        }
+
+        self.send(&command[sent..])?;
+        responses.extend(self.wait_tagged(&tag)?);
+        Ok(responses)
+    }
+
+    /// 等待发送字面量前的继续请求，返回期间的未标记响应；服务器拒绝命令时返回错误
+    fn wait_continuation(&mut self, tag: &str) -> Result<Vec<ImapResponse>, String> {
+        let mut responses = Vec::new();
+        loop {
+            let response = self.wait_response()?;
+            if response.tag == "+" {
+                return Ok(responses);
+            }
+            if response.tag == tag {
+                return Err(format!("{} {}", response.status().unwrap_or("BAD"), response.text));
+            }
+            responses.push(response);
+        }
+    }
+
+    /// 服务器声明的能力（大写）
+    pub fn capabilities(&mut self) -> Result<Vec<String>, String> {
+        let responses = self.run("CAPABILITY")?;
+        Ok(responses
+            .iter()
+            .filter(|r| r.kind().as_deref() == Some("CAPABILITY"))
+            .flat_map(|r| r.values.iter().skip(1).filter_map(ImapValue::as_atom))
+            .map(str::to_ascii_uppercase)
+            .collect())
+    }
+
+    pub fn logout(mut self) {
+        let _ = self.run("LOGOUT");
+    }
+}
+
+/// 解析 UID 集合（如 `41,43:45`），用于 VANISHED 和 SEARCH 结果
+pub fn parse_uid_set(set: &str) -> Result<Vec<u32>, String> {
+    let parse = |v: &str| v.parse::<u32>().map_err(|_| format!("无效的UID集合: {}", set));
+
+    let mut uids = Vec::new();
+    for part in set.split(',').filter(|part| !part.is_empty()) {
+        match part.split_once(':') {
+            Some((a, b)) => {
+                let (a, b) = (parse(a)?, parse(b)?);
+                uids.extend(a.min(b)..=a.max(b));
+            }
+            None => uids.push(parse(part)?),
+        }
+    }
+    Ok(uids)
+}
+
+/// 将参数转为 IMAP 带引号字符串
+pub fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// 将文件夹名称转为 IMAP 带引号字符串，非 ASCII 字符按修改版 UTF-7（RFC 3501 5.1.3）编码
+///
+/// LIST 返回的名称已是编码后的 ASCII 形式，原样发送；只有含非 ASCII 字符的名称
+/// （如过滤规则中手动填写的“归档”）才需要编码
+pub fn quote_mailbox(name: &str) -> String {
+    quote(&mailbox_name(name))
+}
+
+/// 文件夹名称发送给服务器时的形式（不带引号），编码规则同 `quote_mailbox`
+pub fn mailbox_name(name: &str) -> String {
+    if name.is_ascii() {
+        name.to_string()
+    } else {
+        encode_mailbox_name(name)
+    }
+}
+
+fn encode_mailbox_name(name: &str) -> String {
+    // 连续的非 ASCII 字符按 UTF-16BE 合并编码为一段 `&...-`，base64 中的 `/` 换成 `,`
+    fn flush(pending: &mut Vec<u16>, encoded: &mut String) {
+        if pending.is_empty() {
+            return;
+        }
+        let bytes: Vec<u8> = pending.iter().flat_map(|unit| unit.to_be_bytes()).collect();
+        encoded.push('&');
+        encoded.push_str(&base64::encode_config(&bytes, base64::STANDARD_NO_PAD).replace('/', ","));
+        encoded.push('-');
+        pending.clear();
+    }
+
+    let mut encoded = String::new();
+    let mut pending = Vec::new();
+    for c in name.chars() {
+        if (' '..='~').contains(&c) {
+            flush(&mut pending, &mut encoded);
+            match c {
+                '&' => encoded.push_str("&-"),
+                _ => encoded.push(c),
+            }
+        } else {
+            pending.extend_from_slice(c.encode_utf16(&mut [0; 2]));
+        }
+    }
+    flush(&mut pending, &mut encoded);
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// 按给定大小分块返回数据，模拟网络分多次到达
+    struct Chunked {
+        data: Vec<u8>,
+        pos: usize,
+        chunk: usize,
+    }
+
+    impl std::io::Read for Chunked {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.chunk.min(buf.len()).min(self.data.len() - self.pos);
+            buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+            self.pos += n;
+            Ok(n)
+        }
+    }
+
+    fn read_all(data: &[u8], chunk: usize) -> Vec<Vec<u8>> {
+        let mut reader = BufReader::with_capacity(chunk, Chunked { data: data.to_vec(), pos: 0, chunk });
+        let mut responses = ResponseReader::default();
+        let mut out = Vec::new();
+        while let Some(response) = responses.read(&mut reader).unwrap() {
+            out.push(response);
+            if reader.fill_buf().unwrap().is_empty() {
+                break;
+            }
+        }
+        out
+    }
+
+    fn literal_fetch(seq: u32, uid: u32, body: &[u8]) -> Vec<u8> {
+        let mut data = format!("* {} FETCH (UID {} FLAGS (\\Seen) BODY[] {{{}}}\r\n", seq, uid, body.len()).into_bytes();
+        data.extend_from_slice(body);
+        data.extend_from_slice(b")\r\n");
+        data
+    }
+
+    #[test]
+    fn test_literal_with_protocol_like_lines() {
+        // 正文里有单独的 ")"、以标签开头的行和另一个 FETCH 的样子
+        let body = b"Subject: tricky\r\n\r\n)\r\nA005 OK FETCH completed\r\n* 9 FETCH (UID 99)\r\n{12}\r\nend\r\n";
+        let mut data = literal_fetch(1, 101, body);
+        data.extend_from_slice(b"A005 OK FETCH completed\r\n");
+
+        for chunk in [1, 3, 7, 4096] {
+            let raw = read_all(&data, chunk);
+            assert_eq!(raw.len(), 2, "chunk size {}", chunk);
+
+            let fetch = FetchData::from_response(&parse_response(&raw[0]).unwrap()).unwrap();
+            assert_eq!(fetch.seq, 1);
+            assert_eq!(fetch.uid(), Some(101));
+            assert_eq!(fetch.flags(), vec!["\\Seen".to_string()]);
+            assert_eq!(fetch.message(), Some(&body[..]));
+
+            let done = parse_response(&raw[1]).unwrap();
+            assert_eq!(done.tag, "A005");
+            assert_eq!(done.status(), Some("OK"));
+        }
+    }
+
+    #[test]
+    fn test_multibyte_and_non_utf8_bytes_kept_exactly() {
+        // UTF-8 中文被分块读取拆开，GBK 字节不是合法 UTF-8，都必须原样返回
+        let mut body = "主题：周报\r\n\r\n你好，世界".as_bytes().to_vec();
+        body.extend_from_slice(&[0xC4, 0xE3, 0xBA, 0xC3]);
+        let data = literal_fetch(2, 7, &body);
+
+        for chunk in [1, 2, 5] {
+            let raw = read_all(&data, chunk);
+            let fetch = FetchData::from_response(&parse_response(&raw[0]).unwrap()).unwrap();
+            assert_eq!(fetch.message().unwrap(), body.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_sections_quoted_strings_and_nil() {
+        let data = b"* 3 FETCH (UID 12 RFC822.SIZE 2048 BODY[HEADER.FIELDS (SUBJECT FROM)]<0> {9}\r\nSubject:\n ENVELOPE (NIL \"say \\\"hi\\\"\" NIL))\r\n";
+        let fetch = FetchData::from_response(&parse_response(data).unwrap()).unwrap();
+
+        assert_eq!(fetch.get("rfc822.size").and_then(ImapValue::as_number), Some(2048));
+        assert_eq!(
+            fetch.get("BODY[HEADER.FIELDS (SUBJECT FROM)]<0>").and_then(ImapValue::as_bytes),
+            Some(&b"Subject:\n"[..])
+        );
+        let envelope = fetch.get("ENVELOPE").and_then(ImapValue::as_list).unwrap();
+        assert_eq!(envelope[0], ImapValue::Nil);
+        assert_eq!(envelope[1].as_bytes(), Some(&b"say \"hi\""[..]));
+    }
+
+    #[test]
+    fn test_status_responses_and_mixed_buffer() {
+        let mut data = b"* 4 EXISTS\r\n* OK [HIGHESTMODSEQ 715194045007] Highest (unbalanced \"text\r\n".to_vec();
+        data.extend(literal_fetch(4, 40, b"x"));
+        data.extend_from_slice(b"+ idling\r\n* BINARY[] ~{3}\r\n\x00\x01\x02\r\n");
+
+        let responses = parse_responses(&data).unwrap();
+        assert_eq!(responses.len(), 5);
+        assert_eq!(responses[0].kind().as_deref(), Some("EXISTS"));
+        assert_eq!(responses[1].status(), Some("OK"));
+        assert_eq!(responses[1].code[0].as_atom(), Some("HIGHESTMODSEQ"));
+        assert_eq!(responses[1].code[1].as_number(), Some(715194045007));
+        assert_eq!(responses[1].text, "Highest (unbalanced \"text");
+        assert_eq!(responses[2].kind().as_deref(), Some("FETCH"));
+        assert_eq!(responses[3].tag, "+");
+        assert_eq!(responses[3].text, "idling");
+        assert_eq!(responses[4].values[1].as_bytes(), Some(&[0u8, 1, 2][..]));
+    }
+
+    #[test]
+    fn test_vanished_uid_set() {
+        let response = parse_response(b"* VANISHED (EARLIER) 41,43:45,9\r\n").unwrap();
+        assert_eq!(response.kind().as_deref(), Some("VANISHED"));
+
+        let set = response.values.last().and_then(ImapValue::as_atom).unwrap();
+        assert_eq!(parse_uid_set(set).unwrap(), vec![41, 43, 44, 45, 9]);
+        assert!(parse_uid_set("3:*").is_err());
+    }
+
+    #[test]
+    fn test_quote_mailbox() {
+        assert_eq!(quote_mailbox("Archive"), "\"Archive\"");
+        assert_eq!(quote_mailbox("已发送"), "\"&XfJT0ZAB-\"");
+        assert_eq!(quote_mailbox("项目/R&D 报告"), "\"&mHl27g-/R&-D &YqVUSg-\"");
+        // LIST 返回的已编码名称原样发送
+        assert_eq!(quote_mailbox("&XfJT0ZAB-"), "\"&XfJT0ZAB-\"");
+        assert_eq!(quote_mailbox("Ordner \"ü\""), "\"Ordner \\\"&APw-\\\"\"");
+    }
+
+    #[test]
+    fn test_truncated_literal_is_an_error() {
+        let data = b"* 1 FETCH (BODY[] {10}\r\nshort)\r\n";
+        assert!(parse_response(data).unwrap_err().contains("字面量不完整"));
+
+        let mut reader = Cursor::new(b"* 1 FETCH (BODY[] {10}\r\nshort".to_vec());
+        let result = ResponseReader::default().read(&mut reader);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+}
@@ -1,491 +1,490 @@
-use crate::models::{Email, EmailSummary, EmailAccount};
+use crate::models::{
+    Address, Attachment, Email, EmailAccount, EmailAddresses, EmailHeader, EmailSummary, HeaderMap, SearchCriteria, SearchQuery,
+    SpecialFolder,
+};
+use crate::services::imap_pool::{ImapPool, ImapSession, ImapStream, PooledSession};
+use crate::services::imap_raw::{parse_uid_set, quote_mailbox, FetchData, ImapResponse, ImapValue, RawImapConnection};
+use crate::services::address::parse_address_list;
+use crate::services::mime::{decode_rfc2047, MimePart};
+use imap_proto::types::{BodyParams, BodyStructure};
 use native_tls::TlsConnector;
-use std::net::TcpStream;
+use std::collections::{HashMap, HashSet};
 use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
 
 pub struct ImapService {
     account: EmailAccount,
     password: String,
 }
 
-// 检查是否是163邮箱
-fn is_163_email(account: &EmailAccount) -> bool {
-    account.imap_server.contains("163.com") || account.email.contains("@163.com")
+/// 已缓存邮件在服务器上的标志变化和删除情况
+#[derive(Debug, Clone, Default)]
+pub struct FlagChanges {
+    /// 服务器当前的 HIGHESTMODSEQ，不支持 CONDSTORE 时为 0
+    pub highest_modseq: u64,
+    /// 需要更新的邮件标志（UID → 标志）
+    pub flags: HashMap<u32, Vec<String>>,
+    /// 已被删除的 UID
+    pub vanished: Vec<u32>,
 }
 
-impl ImapService {
-    pub fn new(account: EmailAccount, password: String) -> Self {
-        Self { account, password }
-    }
-
-    pub async fn connect(&self) -> Result<imap::Session<native_tls::TlsStream<TcpStream>>, String> {
-        // 先建立TCP连接
-        let stream = TcpStream::connect((self.account.imap_server.as_str(), self.account.imap_port))
-            .map_err(|e| format!("TCP连接失败: {}", e))?;
-
-        // 创建TLS连接器（生产环境启用证书验证）
-        let tls = if cfg!(debug_assertions) {
-            // 开发环境：允许自签名证书
-            TlsConnector::builder()
-                .danger_accept_invalid_certs(true)
-                .danger_accept_invalid_hostnames(true)
-                .build()
-                .map_err(|e| format!("TLS创建失败: {}", e))?
-        } else {
-            // 生产环境：严格验证证书
-            TlsConnector::builder()
-                .build()
-                .map_err(|e| format!("TLS创建失败: {}", e))?
-        };
+/// 直接收发命令时的读超时（秒）
+const RAW_READ_TIMEOUT_SECONDS: u64 = 5;
 
-        // 升级到TLS
-        let tls_stream = tls.connect(&self.account.imap_server, stream)
-            .map_err(|e| format!("TLS连接失败: {}", e))?;
+/// 需要发送 ID 命令（RFC 2971）的邮箱服务商
+/// 网易邮箱（163/126/yeah.net）未收到 ID 时会以 "Unsafe Login" 拒绝 SELECT
+const IMAP_ID_DOMAINS: &[&str] = &["163.com", "126.com", "yeah.net", "188.com"];
 
-        // 创建IMAP客户端
-        let client = imap::Client::new(tls_stream);
-
-        // 登录
-        let client = client
-            .login(&self.account.email, &self.password)
-            .map_err(|(e, _)| {
-                if e.to_string().contains("Unsafe Login") || e.to_string().contains("kefu@188.com") {
-                    format!("163邮箱登录失败：请确保已在邮箱设置中开启IMAP服务并使用正确的授权码。错误: {}", e)
-                } else {
-                    format!("IMAP登录失败: {}", e)
-                }
-            })?;
+/// 向服务器表明客户端身份的 ID 命令
+pub(crate) const IMAP_ID_COMMAND: &str = "ID (\"name\" \"MailFlow\" \"version\" \"1.0\")";
 
-        Ok(client)
-    }
+/// 检查账户的服务器是否需要 ID 命令
+pub(crate) fn needs_imap_id(account: &EmailAccount) -> bool {
+    IMAP_ID_DOMAINS.iter().any(|domain| {
+        account.imap_server.ends_with(domain) || account.email.ends_with(&format!("@{}", domain))
+    })
+}
 
-    pub async fn list_folders(&self) -> Result<Vec<String>, String> {
-        let mut client = self.connect().await?;
-
-        let folders = client
-            .list(None, None)
-            .map_err(|e| format!("列出文件夹失败: {}", e))?;
-
-        let mut result = Vec::new();
-        for folder in folders.iter() {
-            let name = folder.name();
-            let name = name.replace("\"", "");
-            if !name.is_empty() && !name.starts_with('.') {
-                result.push(name);
+/// 登录前发送 ID 命令（RFC 2971 允许在任何状态发送）
+///
+/// imap crate 无法解析 `* ID (...)` 响应，因此在交给 `imap::Client` 之前直接读写连接；
+/// 逐字节读取，不会读走属于之后命令的数据
+fn send_imap_id(stream: &mut ImapStream) -> Result<(), String> {
+    const TAG: &str = "ID0";
+
+    stream
+        .write_all(format!("{} {}\r\n", TAG, IMAP_ID_COMMAND).as_bytes())
+        .and_then(|_| stream.flush())
+        .map_err(|e| format!("发送ID命令失败: {}", e))?;
+
+    // 欢迎信息和 ID 响应都是非标记行，读到标记行为止；不支持 ID 的服务器返回 BAD，不影响登录
+    let mut line = Vec::new();
+    loop {
+        let mut byte = [0u8; 1];
+        match stream.read(&mut byte) {
+            Ok(0) => return Err("读取ID响应失败: 连接已关闭".to_string()),
+            Ok(_) => line.push(byte[0]),
+            Err(e) => return Err(format!("读取ID响应失败: {}", e)),
+        }
+        if byte[0] == b'\n' {
+            if line.starts_with(format!("{} ", TAG).as_bytes()) {
+                return Ok(());
             }
+            line.clear();
         }
-
-        client.logout().map_err(|e| format!("登出失败: {}", e))?;
-        Ok(result)
     }
+}
 
-    /// 获取文件夹状态 (UIDVALIDITY, UIDNEXT)
-    /// 返回 (uid_validity, uid_next)
-    pub async fn get_folder_status(&self, folder: &str) -> Result<(u32, u32), String> {
-        // 163邮箱使用特殊处理
-        if is_163_email(&self.account) {
-            return self.get_folder_status_163(folder).await;
-        }
-
-        let mut client = self.connect().await?;
-
-        let select_result = client
-            .select(folder)
-            .or_else(|_| client.select(format!("\"{}\"", folder)));
+/// 是否连接本机的明文 IMAP 服务（如本地邮件桥接程序），993 端口始终使用 TLS
+fn is_local_plain(account: &EmailAccount) -> bool {
+    matches!(account.imap_server.as_str(), "localhost" | "127.0.0.1" | "::1")
+        && account.imap_port != 993
+}
 
-        if let Err(e) = select_result {
-            return Err(format!("选择文件夹 '{}' 失败: {}", folder, e));
-        }
+/// 建立到账户 IMAP 服务器的连接（本机明文，其余 TLS），可选设置读超时
+pub(crate) fn open_imap_stream(
+    account: &EmailAccount,
+    read_timeout: Option<Duration>,
+) -> Result<ImapStream, String> {
+    // 先建立TCP连接
+    let stream = TcpStream::connect((account.imap_server.as_str(), account.imap_port))
+        .map_err(|e| format!("TCP连接失败: {}", e))?;
+    stream
+        .set_read_timeout(read_timeout)
+        .map_err(|e| format!("设置读取超时失败: {}", e))?;
+
+    if is_local_plain(account) {
+        return Ok(ImapStream::Plain(stream));
+    }
 
-        // 从 SELECT 响应中提取 UIDVALIDITY 和 UIDNEXT
-        // 注意：imap crate 的 select 返回 Mailbox，我们需要从中提取
-        // 使用 STATUS 命令获取更可靠
-        let status = client
-            .status(folder, "(UIDVALIDITY UIDNEXT)")
-            .or_else(|_| client.status(&format!("\"{}\"", folder), "(UIDVALIDITY UIDNEXT)"))
-            .map_err(|e| format!("获取文件夹状态失败: {}", e))?;
+    // 创建TLS连接器（生产环境启用证书验证）
+    let tls = if cfg!(debug_assertions) {
+        // 开发环境：允许自签名证书
+        TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .danger_accept_invalid_hostnames(true)
+            .build()
+            .map_err(|e| format!("TLS创建失败: {}", e))?
+    } else {
+        // 生产环境：严格验证证书
+        TlsConnector::builder()
+            .build()
+            .map_err(|e| format!("TLS创建失败: {}", e))?
+    };
+
+    // 升级到TLS
+    let tls_stream = tls.connect(&account.imap_server, stream)
+        .map_err(|e| format!("TLS连接失败: {}", e))?;
+
+    Ok(ImapStream::Tls(Box::new(tls_stream)))
+}
 
-        client.logout().map_err(|e| format!("登出失败: {}", e))?;
+impl ImapService {
+    pub fn new(account: EmailAccount, password: String) -> Self {
+        Self { account, password }
+    }
 
-        // 解析 STATUS 响应
-        // 响应格式: * STATUS folder (UIDVALIDITY 1 UIDNEXT 100)
-        let response_str = format!("{:?}", status);
-        let mut uid_validity = 0u32;
-        let mut uid_next = 0u32;
-
-        // 提取 UIDVALIDITY
-        if let Some(pos) = response_str.find("UIDVALIDITY") {
-            let rest = &response_str[pos + 11..];
-            let nums: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
-            if let Ok(v) = nums.parse::<u32>() {
-                uid_validity = v;
-            }
-        }
+    pub async fn connect(&self) -> Result<ImapSession, String> {
+        let mut stream = open_imap_stream(&self.account, None)?;
 
-        // 提取 UIDNEXT
-        if let Some(pos) = response_str.find("UIDNEXT") {
-            let rest = &response_str[pos + 7..];
-            let nums: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
-            if let Ok(v) = nums.parse::<u32>() {
-                uid_next = v;
-            }
+        // 网易邮箱要求先发送 ID 才允许选择文件夹
+        if needs_imap_id(&self.account) {
+            send_imap_id(&mut stream)?;
         }
 
-        Ok((uid_validity, uid_next))
+        imap::Client::new(stream)
+            .login(&self.account.email, &self.password)
+            .map_err(|(e, _)| Self::login_error(e))
     }
 
-    /// 163邮箱获取文件夹状态
-    async fn get_folder_status_163(&self, folder: &str) -> Result<(u32, u32), String> {
-        use native_tls::TlsConnector;
-        use std::io::{Read, Write};
-
-        let stream = TcpStream::connect((self.account.imap_server.as_str(), self.account.imap_port))
-            .map_err(|e| format!("TCP连接失败: {}", e))?;
-
-        let tls = if cfg!(debug_assertions) {
-            TlsConnector::builder()
-                .danger_accept_invalid_certs(true)
-                .danger_accept_invalid_hostnames(true)
-                .build()
+    fn login_error(e: imap::error::Error) -> String {
+        if e.to_string().contains("Unsafe Login") || e.to_string().contains("kefu@188.com") {
+            format!("163邮箱登录失败：请确保已在邮箱设置中开启IMAP服务并使用正确的授权码。错误: {}", e)
         } else {
-            TlsConnector::builder().build()
-        }.map_err(|e| format!("TLS创建失败: {}", e))?;
-
-        let mut tls_stream = tls.connect(&self.account.imap_server, stream)
-            .map_err(|e| format!("TLS连接失败: {}", e))?;
-
-        // 读取欢迎信息
-        let mut buffer = [0u8; 1024];
-        tls_stream.read(&mut buffer).map_err(|e| format!("读取欢迎信息失败: {}", e))?;
-
-        // 发送ID命令
-        let id_cmd = b"A001 ID (\"name\" \"MailFlow\" \"version\" \"1.0\")\r\n";
-        tls_stream.write_all(id_cmd).map_err(|e| format!("发送ID命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        buffer = [0u8; 1024];
-        tls_stream.read(&mut buffer).map_err(|e| format!("读取ID响应失败: {}", e))?;
-
-        // 发送LOGIN命令
-        let login_cmd = format!("A002 LOGIN \"{}\" \"{}\"\r\n", self.account.email, self.password);
-        tls_stream.write_all(login_cmd.as_bytes()).map_err(|e| format!("发送LOGIN命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        let mut buffer2 = [0u8; 1024];
-        let n = tls_stream.read(&mut buffer2).map_err(|e| format!("读取LOGIN响应失败: {}", e))?;
-        let response = String::from_utf8_lossy(&buffer2[..n]);
-        if response.contains("NO") || response.contains("BAD") {
-            return Err(format!("163邮箱登录失败: {}", response));
+            format!("IMAP登录失败: {}", e)
         }
+    }
 
-        // 发送STATUS命令
-        let status_cmd = format!("A003 STATUS \"{}\" (UIDVALIDITY UIDNEXT)\r\n", folder);
-        tls_stream.write_all(status_cmd.as_bytes()).map_err(|e| format!("发送STATUS命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
+    /// 从连接池取出已登录的会话执行操作，池中没有时新建连接
+    /// 操作成功或连接仍可用时会话归还连接池；复用的会话已被服务器断开时换新连接重试一次，
+    /// 因此只用于可以重复执行的操作（读取、追加标志）
+    async fn with_session<R>(
+        &self,
+        mut op: impl FnMut(&mut PooledSession) -> Result<R, String>,
+    ) -> Result<R, String> {
+        let pool = ImapPool::global();
+        let (mut pooled, reused) = match pool.checkout(&self.account.id) {
+            Some(pooled) => (pooled, true),
+            None => (PooledSession::new(self.connect().await?), false),
+        };
 
-        // 读取响应
-        let mut response_data = String::new();
-        loop {
-            let mut buffer3 = [0u8; 2048];
-            match tls_stream.read(&mut buffer3) {
-                Ok(n) if n > 0 => {
-                    let chunk = String::from_utf8_lossy(&buffer3[..n]);
-                    response_data.push_str(&chunk);
-                    if chunk.contains("A003 OK") || chunk.contains("A003 BAD") || chunk.contains("A003 NO") {
-                        break;
-                    }
+        match op(&mut pooled) {
+            Ok(result) => {
+                pool.checkin(&self.account.id, pooled);
+                Ok(result)
+            }
+            Err(e) if pooled.is_alive() => {
+                pool.checkin(&self.account.id, pooled);
+                Err(e)
+            }
+            Err(e) if !reused => Err(e),
+            Err(_) => {
+                let mut pooled = PooledSession::new(self.connect().await?);
+                let result = op(&mut pooled);
+                if result.is_ok() || pooled.is_alive() {
+                    pool.checkin(&self.account.id, pooled);
                 }
-                _ => break,
+                result
             }
         }
+    }
 
-        // 发送LOGOUT
-        let _ = tls_stream.write_all(b"A004 LOGOUT\r\n");
-        let _ = tls_stream.flush();
-
-        // 解析响应
-        let mut uid_validity = 0u32;
-        let mut uid_next = 0u32;
+    /// 执行不能重复的操作（COPY、EXPUNGE 等）：复用的会话先用 NOOP 确认连接可用，
+    /// 操作开始后失败不再重试，避免命令在服务器上执行两次
+    async fn with_session_once<R>(
+        &self,
+        op: impl FnOnce(&mut PooledSession) -> Result<R, String>,
+    ) -> Result<R, String> {
+        let pool = ImapPool::global();
+        let mut pooled = match pool.checkout(&self.account.id).and_then(|mut p| p.is_alive().then_some(p)) {
+            Some(pooled) => pooled,
+            None => PooledSession::new(self.connect().await?),
+        };
 
-        if let Some(pos) = response_data.find("UIDVALIDITY") {
-            let rest = &response_data[pos + 11..];
-            let nums: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
-            if let Ok(v) = nums.parse::<u32>() {
-                uid_validity = v;
-            }
+        let result = op(&mut pooled);
+        if result.is_ok() || pooled.is_alive() {
+            pool.checkin(&self.account.id, pooled);
         }
+        result
+    }
 
-        if let Some(pos) = response_data.find("UIDNEXT") {
-            let rest = &response_data[pos + 7..];
-            let nums: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
-            if let Ok(v) = nums.parse::<u32>() {
-                uid_next = v;
+    pub async fn list_folders(&self) -> Result<Vec<String>, String> {
+        self.with_session(|pooled| {
+            let folders = pooled.session
+                .list(None, None)
+                .map_err(|e| format!("列出文件夹失败: {}", e))?;
+
+            let mut result = Vec::new();
+            for folder in folders.iter() {
+                let name = folder.name();
+                let name = name.replace("\"", "");
+                if !name.is_empty() && !name.starts_with('.') {
+                    result.push(name);
+                }
             }
-        }
 
-        Ok((uid_validity, uid_next))
+            Ok(result)
+        }).await
     }
 
-    /// 增量获取新邮件（UID > last_uid）
-    pub async fn fetch_new_emails(&self, folder: &str, last_uid: u32, limit: usize) -> Result<Vec<EmailSummary>, String> {
-        // 163邮箱使用特殊处理
-        if is_163_email(&self.account) {
-            return self.fetch_new_emails_163(folder, last_uid, limit).await;
-        }
-
-        let mut client = self.connect().await?;
-
-        let select_result = client
-            .select(folder)
-            .or_else(|_| client.select(format!("\"{}\"", folder)));
-
-        if let Err(e) = select_result {
-            return Err(format!("选择文件夹 '{}' 失败: {}", folder, e));
+    /// 查找特殊用途文件夹：优先使用 LIST 返回的 SPECIAL-USE 属性，其次按常见名称匹配
+    pub async fn find_special_folder(&self, role: SpecialFolder) -> Result<Option<String>, String> {
+        if role == SpecialFolder::Inbox {
+            return Ok(Some("INBOX".to_string()));
         }
 
-        // 搜索 UID > last_uid 的邮件
-        let search_criteria = format!("UID {}:*", last_uid + 1);
-        let ids = client
-            .search(&search_criteria)
-            .map_err(|e| format!("搜索新邮件失败: {}", e))?;
-
-        let mut ids_vec: Vec<u32> = ids.into_iter().collect();
-        ids_vec.sort();
-        ids_vec.reverse(); // 最新的在前
-
-        let ids: Vec<u32> = ids_vec.into_iter().take(limit).collect();
-
-        let mut emails = Vec::new();
-
-        for uid in ids {
-            // 获取邮件摘要
-            let responses = client
-                .fetch(uid.to_string(), "(RFC822.SIZE UID FLAGS ENVELOPE)")
-                .map_err(|e| format!("获取邮件摘要失败: {}", e))?;
-
-            if let Some(response) = responses.iter().next() {
-                if let Some(mut summary) = self.parse_email_summary(response, uid) {
-                    // 获取正文预览
-                    match client.fetch(uid.to_string(), "(RFC822.PEEK[HEADER.FIELDS (Subject From Date)] BODY.PEEK[TEXT]<0.2000>)") {
-                        Ok(body_responses) => {
-                            if let Some(body_response) = body_responses.iter().next() {
-                                if let Some(body_text) = body_response.text() {
-                                    let body_str = String::from_utf8_lossy(body_text);
-                                    let (body, _html) = self.extract_body(&body_str);
-                                    summary.preview = body.chars().take(200).collect::<String>().replace('\n', " ");
-                                    summary.body = body.chars().take(1000).collect();
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("获取邮件正文预览失败 (UID: {}): {}", uid, e);
-                        }
-                    }
-                    emails.push(summary);
-                }
+        self.with_session(|pooled| {
+            let folders = pooled.session
+                .list(Some(""), Some("*"))
+                .map_err(|e| format!("列出文件夹失败: {}", e))?;
+
+            let by_attribute = folders.iter().find(|folder| {
+                folder.attributes().iter().any(|attr| matches!(
+                    attr,
+                    imap::types::NameAttribute::Custom(name) if name.eq_ignore_ascii_case(role.attribute())
+                ))
+            });
+            if let Some(folder) = by_attribute {
+                return Ok(Some(folder.name().replace("\"", "")));
             }
-        }
 
-        client.logout().map_err(|e| format!("登出失败: {}", e))?;
-        Ok(emails)
+            // 名称可能带有上级路径（如 INBOX.Sent）
+            let found = role.common_names().iter().find_map(|common| {
+                folders.iter().find_map(|folder| {
+                    let name = folder.name().replace("\"", "");
+                    let leaf = match folder.delimiter() {
+                        Some(delimiter) => name.rsplit(delimiter).next().unwrap_or(&name),
+                        None => &name,
+                    };
+                    leaf.eq_ignore_ascii_case(common).then(|| name.clone())
+                })
+            });
+            Ok(found)
+        }).await
     }
 
-    /// 163邮箱增量获取新邮件
-    async fn fetch_new_emails_163(&self, folder: &str, last_uid: u32, limit: usize) -> Result<Vec<EmailSummary>, String> {
-        use native_tls::TlsConnector;
-        use std::io::{Read, Write};
-
-        let stream = TcpStream::connect((self.account.imap_server.as_str(), self.account.imap_port))
-            .map_err(|e| format!("TCP连接失败: {}", e))?;
+    /// 获取文件夹状态 (UIDVALIDITY, UIDNEXT)
+    /// 返回 (uid_validity, uid_next)
+    pub async fn get_folder_status(&self, folder: &str) -> Result<(u32, u32), String> {
+        self.with_session(|pooled| Self::folder_status(pooled, folder)).await
+    }
 
-        let tls = if cfg!(debug_assertions) {
-            TlsConnector::builder()
-                .danger_accept_invalid_certs(true)
-                .danger_accept_invalid_hostnames(true)
-                .build()
-        } else {
-            TlsConnector::builder().build()
-        }.map_err(|e| format!("TLS创建失败: {}", e))?;
-
-        let mut tls_stream = tls.connect(&self.account.imap_server, stream)
-            .map_err(|e| format!("TLS连接失败: {}", e))?;
-
-        // 读取欢迎信息
-        let mut buffer = [0u8; 1024];
-        tls_stream.read(&mut buffer).map_err(|e| format!("读取欢迎信息失败: {}", e))?;
-
-        // 发送ID命令
-        let id_cmd = b"A001 ID (\"name\" \"MailFlow\" \"version\" \"1.0\")\r\n";
-        tls_stream.write_all(id_cmd).map_err(|e| format!("发送ID命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        buffer = [0u8; 1024];
-        tls_stream.read(&mut buffer).map_err(|e| format!("读取ID响应失败: {}", e))?;
-
-        // 发送LOGIN命令
-        let login_cmd = format!("A002 LOGIN \"{}\" \"{}\"\r\n", self.account.email, self.password);
-        tls_stream.write_all(login_cmd.as_bytes()).map_err(|e| format!("发送LOGIN命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        let mut buffer2 = [0u8; 1024];
-        let n = tls_stream.read(&mut buffer2).map_err(|e| format!("读取LOGIN响应失败: {}", e))?;
-        let response = String::from_utf8_lossy(&buffer2[..n]);
-        if response.contains("NO") || response.contains("BAD") {
-            return Err(format!("163邮箱登录失败: {}", response));
+    /// 重新 SELECT 文件夹并返回 (uid_validity, uid_next)，会话随后保持选中该文件夹
+    fn folder_status(pooled: &mut PooledSession, folder: &str) -> Result<(u32, u32), String> {
+        // 重新 SELECT 以取得最新的 UIDVALIDITY 和 UIDNEXT
+        let mailbox = pooled.reselect(folder)?;
+        if let (Some(uid_validity), Some(uid_next)) = (mailbox.uid_validity, mailbox.uid_next) {
+            return Ok((uid_validity, uid_next));
         }
 
-        // 选择文件夹
-        let select_cmd = format!("A003 SELECT \"{}\"\r\n", folder);
-        tls_stream.write_all(select_cmd.as_bytes()).map_err(|e| format!("发送SELECT命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        let mut buffer3 = [0u8; 2048];
-        tls_stream.read(&mut buffer3).map_err(|e| format!("读取SELECT响应失败: {}", e))?;
+        // SELECT 响应缺少时使用 STATUS 命令获取
+        // 响应格式: * STATUS folder (UIDVALIDITY 1 UIDNEXT 100)
+        let session = &mut pooled.session;
+        let status = session
+            .status(folder, "(UIDVALIDITY UIDNEXT)")
+            .or_else(|_| session.status(format!("\"{}\"", folder), "(UIDVALIDITY UIDNEXT)"))
+            .map_err(|e| format!("获取文件夹状态失败: {}", e))?;
 
-        // 搜索新邮件 UID > last_uid
-        let search_cmd = format!("A004 UID SEARCH UID {}:*\r\n", last_uid + 1);
-        tls_stream.write_all(search_cmd.as_bytes()).map_err(|e| format!("发送SEARCH命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
+        Ok((
+            status.uid_validity.or(mailbox.uid_validity).unwrap_or(0),
+            status.uid_next.or(mailbox.uid_next).unwrap_or(0),
+        ))
+    }
 
-        // 读取SEARCH响应
-        let mut uid_list: Vec<u32> = Vec::new();
-        let mut response_data = String::new();
-        loop {
-            let mut buffer4 = [0u8; 4096];
-            match tls_stream.read(&mut buffer4) {
-                Ok(n) if n > 0 => {
-                    let chunk = String::from_utf8_lossy(&buffer4[..n]);
-                    response_data.push_str(&chunk);
-                    if chunk.contains("A004 OK") || chunk.contains("A004 BAD") || chunk.contains("A004 NO") {
-                        break;
-                    }
-                }
-                _ => break,
-            }
-        }
+    /// 增量获取新邮件（UID > last_uid）
+    pub async fn fetch_new_emails(&self, folder: &str, last_uid: u32, limit: usize) -> Result<Vec<EmailSummary>, String> {
+        self.with_session(|pooled| {
+            pooled.select(folder)?;
+            let client = &mut pooled.session;
+
+            // 搜索 UID > last_uid 的邮件
+            // "n:*" 在 n 大于最大 UID 时仍会返回最大的 UID，需要再过滤一次
+            let search_criteria = format!("UID {}:*", last_uid + 1);
+            let uids = client
+                .uid_search(&search_criteria)
+                .map_err(|e| format!("搜索新邮件失败: {}", e))?;
+
+            let mut uids_vec: Vec<u32> = uids.into_iter().filter(|uid| *uid > last_uid).collect();
+            uids_vec.sort();
+            uids_vec.reverse(); // 最新的在前
+
+            let uids: Vec<u32> = uids_vec.into_iter().take(limit).collect();
+            self.fetch_summaries(client, uids)
+        }).await
+    }
 
-        // 解析SEARCH响应
-        for line in response_data.lines() {
-            let trimmed = line.trim();
-            if trimmed.starts_with("* SEARCH") {
-                let uids: Vec<u32> = trimmed
-                    .split_whitespace()
-                    .skip(2)
-                    .filter_map(|s| s.parse().ok())
-                    .collect();
-                uid_list.extend(uids);
-            }
-        }
+    pub async fn fetch_emails(
+        &self,
+        folder: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<EmailSummary>, String> {
+        self.fetch_page(folder, limit, offset, true).await
+    }
 
-        // 过滤掉 <= last_uid 的 UID（因为 UID:* 可能包含已存在的）
-        uid_list.retain(|&uid| uid > last_uid);
-        uid_list.sort_unstable_by(|a, b| b.cmp(a)); // 降序
-        uid_list.truncate(limit);
+    /// 与 `fetch_emails` 相同，但文件夹无法打开时返回错误而不是退回收件箱；
+    /// 用于会修改邮件的批量操作，避免误处理收件箱
+    pub async fn fetch_folder_emails(
+        &self,
+        folder: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<EmailSummary>, String> {
+        self.fetch_page(folder, limit, offset, false).await
+    }
 
-        // 获取每封邮件
-        let mut emails = Vec::new();
-        for uid in uid_list {
-            match self.fetch_email_163_rfc822_with_stream(&mut tls_stream, folder, uid).await {
-                Ok(email) => emails.push(email),
-                Err(_) => {}
+    async fn fetch_page(
+        &self,
+        folder: &str,
+        limit: usize,
+        offset: usize,
+        fallback_to_inbox: bool,
+    ) -> Result<Vec<EmailSummary>, String> {
+        self.with_session(|pooled| {
+            // 文件夹不存在时退回收件箱
+            if let Err(e) = pooled.select(folder) {
+                if !fallback_to_inbox {
+                    return Err(e);
+                }
+                pooled.select("INBOX").map_err(|_| e)?;
             }
-        }
+            let client = &mut pooled.session;
 
-        // 发送LOGOUT
-        let _ = tls_stream.write_all(b"A005 LOGOUT\r\n");
-        let _ = tls_stream.flush();
+            // 搜索所有邮件的 UID
+            let uids = client
+                .uid_search("ALL")
+                .map_err(|e| format!("搜索邮件失败: {}", e))?;
 
-        Ok(emails)
+            let mut uids_vec: Vec<u32> = uids.into_iter().collect();
+            uids_vec.sort();
+            uids_vec.reverse();
+
+            let uids: Vec<u32> = uids_vec.into_iter().skip(offset).take(limit).collect();
+            self.fetch_summaries(client, uids)
+        }).await
     }
 
-    /// 使用现有连接获取163邮件
-    async fn fetch_email_163_rfc822_with_stream<S: Read + Write>(
+    /// 翻页获取比 before_uid 更早的邮件（最新的在前）
+    /// 返回文件夹当前的 UIDVALIDITY 和邮件，调用方据此判断 before_uid 是否仍然有效
+    pub async fn fetch_older_emails(
         &self,
-        stream: &mut S,
-        _folder: &str,
-        uid: u32,
-    ) -> Result<EmailSummary, String> {
-        // 使用新的 tag 避免冲突
-        let fetch_cmd = format!("A006 UID FETCH {} (RFC822)\r\n", uid);
-        stream.write_all(fetch_cmd.as_bytes()).map_err(|e| format!("发送FETCH命令失败: {}", e))?;
-        stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        // 读取响应
-        let mut response_content = String::new();
-        let mut buffer = [0u8; 4096];
-
-        loop {
-            match stream.read(&mut buffer) {
-                Ok(n) if n > 0 => {
-                    let chunk = &buffer[..n];
-                    let text = String::from_utf8_lossy(chunk);
-                    response_content.push_str(&text);
-
-                    if text.contains("A006 OK") || text.contains("A006 BAD") || text.contains("A006 NO") {
-                        break;
-                    }
-                }
-                _ => break,
-            }
-        }
+        folder: &str,
+        before_uid: u32,
+        limit: usize,
+    ) -> Result<(u32, Vec<EmailSummary>), String> {
+        self.with_session(|pooled| {
+            let (uid_validity, _) = Self::folder_status(pooled, folder)?;
+            let client = &mut pooled.session;
+
+            // 按 UID 而不是偏移定位，翻页期间到达的新邮件不会使页面错位
+            let uids = client
+                .uid_search("ALL")
+                .map_err(|e| format!("搜索邮件失败: {}", e))?;
+
+            let mut uids_vec: Vec<u32> = uids.into_iter().filter(|uid| *uid < before_uid).collect();
+            uids_vec.sort();
+            uids_vec.reverse();
+
+            let uids: Vec<u32> = uids_vec.into_iter().take(limit).collect();
+            Ok((uid_validity, self.fetch_summaries(client, uids)?))
+        }).await
+    }
 
-        let email_content = self.extract_rfc822_from_response(&response_content);
-        self.parse_email_from_rfc822(&email_content, uid)
-            .ok_or_else(|| format!("无法解析邮件 UID: {}", uid))
+    /// 在服务器上按条件搜索文件夹，返回最新的 limit 封邮件
+    pub async fn search_emails(
+        &self,
+        folder: &str,
+        criteria: &SearchCriteria,
+        limit: usize,
+    ) -> Result<Vec<EmailSummary>, String> {
+        let query = SearchQuery::from_criteria(criteria)?;
+        self.query_emails(folder, &query, limit).await
     }
 
-    pub async fn fetch_emails(
+    /// 按查询语句在服务器端搜索文件夹，结果按 UID 从新到旧排列
+    ///
+    /// has:attachment 无法在服务器端搜索：分别代入有、无附件搜索两次，
+    /// 再按 BODYSTRUCTURE 判断每封邮件是否有附件，从对应的结果中选取
+    pub async fn query_emails(
         &self,
         folder: &str,
+        query: &SearchQuery,
         limit: usize,
-        offset: usize,
     ) -> Result<Vec<EmailSummary>, String> {
-        // 163邮箱使用特殊连接方式
-        if is_163_email(&self.account) {
-            return self.fetch_emails_163(folder, limit, offset).await;
-        }
+        self.with_session(|pooled| {
+            pooled.select(folder)?;
+
+            let mut uids = if query.has_attachment_term() {
+                let with: HashSet<u32> = self.search_uids(pooled, folder, &query.with_attachment(true))?.into_iter().collect();
+                let without: HashSet<u32> = self.search_uids(pooled, folder, &query.with_attachment(false))?.into_iter().collect();
+                let candidates: Vec<u32> = with.union(&without).copied().collect();
+                let attachments = Self::fetch_has_attachment(&mut pooled.session, &candidates)?;
+                candidates
+                    .into_iter()
+                    .filter(|uid| match attachments.get(uid) {
+                        Some(true) => with.contains(uid),
+                        Some(false) => without.contains(uid),
+                        None => false,
+                    })
+                    .collect()
+            } else {
+                self.search_uids(pooled, folder, query)?
+            };
 
-        let mut client = self.connect().await?;
+            uids.sort_unstable_by(|a, b| b.cmp(a));
+            uids.truncate(limit);
 
-        // 尝试选择文件夹，支持带引号和不带引号的格式
-        let select_result = client
-            .select(folder)
-            .or_else(|_| client.select(format!("\"{}\"", folder)))
-            .or_else(|_| client.select("INBOX"));
+            self.fetch_summaries(&mut pooled.session, uids)
+        }).await
+    }
 
-        if let Err(e) = select_result {
-            return Err(format!("选择文件夹 '{}' 失败: {}", folder, e));
+    /// 在已选择的文件夹中执行 UID SEARCH
+    fn search_uids(&self, pooled: &mut PooledSession, folder: &str, query: &SearchQuery) -> Result<Vec<u32>, String> {
+        let literal_plus = pooled.has_capability("LITERAL+");
+        let query = query.to_imap(literal_plus)?;
+        if literal_plus || query.is_ascii() {
+            return Ok(pooled.session
+                .uid_search(&query)
+                .map_err(|e| format!("搜索邮件失败: {}", e))?
+                .into_iter()
+                .collect());
         }
 
-        // 搜索所有邮件
-        let ids = client
-            .search("ALL")
-            .map_err(|e| format!("搜索邮件失败: {}", e))?;
+        // 同步字面量要等服务器的继续请求，imap crate 不支持
+        self.with_raw(|conn| {
+            conn.run(&format!("SELECT {}", quote_mailbox(folder)))?;
+            let responses = conn.run(&format!("UID SEARCH {}", query))
+                .map_err(|e| format!("搜索邮件失败: {}", e))?;
+            Ok(search_uids(&responses))
+        })
+    }
 
-        let mut ids_vec: Vec<u32> = ids.into_iter().collect();
-        ids_vec.sort();
-        ids_vec.reverse();
+    /// 按 BODYSTRUCTURE 判断一批邮件是否有附件
+    fn fetch_has_attachment(client: &mut ImapSession, uids: &[u32]) -> Result<HashMap<u32, bool>, String> {
+        if uids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let set = uids.iter().map(u32::to_string).collect::<Vec<_>>().join(",");
+        let responses = client
+            .uid_fetch(set, "(UID BODYSTRUCTURE)")
+            .map_err(|e| format!("获取邮件结构失败: {}", e))?;
 
-        let ids: Vec<u32> = ids_vec.into_iter().skip(offset).take(limit).collect();
+        Ok(responses
+            .iter()
+            .filter_map(|fetch| Some((fetch.uid?, fetch.bodystructure().is_some_and(structure_has_attachment))))
+            .collect())
+    }
 
+    /// 逐封获取邮件摘要和正文预览
+    fn fetch_summaries(&self, client: &mut ImapSession, uids: Vec<u32>) -> Result<Vec<EmailSummary>, String> {
         let mut emails = Vec::new();
 
-        for uid in ids {
-            // 首先获取邮件摘要（ENVELOPE）
+        for uid in uids {
+            // 首先获取邮件摘要（ENVELOPE），BODYSTRUCTURE 用于判断是否有附件
             let responses = client
-                .fetch(uid.to_string(), "(RFC822.SIZE UID FLAGS ENVELOPE)")
+                .uid_fetch(uid.to_string(), "(RFC822.SIZE UID FLAGS ENVELOPE BODYSTRUCTURE)")
                 .map_err(|e| format!("获取邮件摘要失败: {}", e))?;
 
             if let Some(response) = responses.iter().next() {
                 if let Some(mut summary) = self.parse_email_summary(response, uid) {
-                    // 然后获取邮件正文预览（前2000字节）
-                    match client.fetch(uid.to_string(), "(RFC822.PEEK[HEADER.FIELDS (Subject From Date)] BODY.PEEK[TEXT]<0.2000>)") {
+                    // 然后获取邮件头和正文开头（前2000字节）生成预览，邮件头用于识别 MIME 结构
+                    match client.uid_fetch(uid.to_string(), "(BODY.PEEK[HEADER] BODY.PEEK[TEXT]<0.2000>)") {
                         Ok(body_responses) => {
                             if let Some(body_response) = body_responses.iter().next() {
                                 // 尝试获取正文内容 - text() 返回 Option<&[u8]>
                                 if let Some(body_text) = body_response.text() {
-                                    let body_str = String::from_utf8_lossy(body_text);
-                                    let (body, _html) = self.extract_body(&body_str);
+                                    let body = self.preview_text(body_response.header().unwrap_or_default(), body_text);
                                     summary.preview = body.chars().take(200).collect::<String>().replace('\n', " ");
                                     summary.body = body.chars().take(1000).collect();
                                 }
@@ -501,1356 +500,1425 @@ impl ImapService {
             }
         }
 
-        client.logout().map_err(|e| format!("登出失败: {}", e))?;
         Ok(emails)
     }
 
-    // 使用原生 TCP/TLS 连接获取163邮箱邮件（不依赖外部 openssl 程序）
-    // 163邮箱需要ID命令才能登录，这里使用自定义 IMAP 实现
-    #[allow(unused_variables)]
-    async fn fetch_emails_163(
-        &self,
-        folder: &str,
-        limit: usize,
-        _offset: usize,
-    ) -> Result<Vec<EmailSummary>, String> {
-        use native_tls::TlsConnector;
-        use std::io::{Read, Write};
-
-        // 建立TCP连接
-        let stream = TcpStream::connect((self.account.imap_server.as_str(), self.account.imap_port))
-            .map_err(|e| format!("TCP连接失败: {}", e))?;
-
-        // 创建TLS连接器（开发环境允许自签名证书）
-        let tls = if cfg!(debug_assertions) {
-            TlsConnector::builder()
-                .danger_accept_invalid_certs(true)
-                .danger_accept_invalid_hostnames(true)
-                .build()
-        } else {
-            TlsConnector::builder().build()
-        }.map_err(|e| format!("TLS创建失败: {}", e))?;
-
-        // 升级到TLS
-        let mut tls_stream = tls.connect(&self.account.imap_server, stream)
-            .map_err(|e| format!("TLS连接失败: {}", e))?;
-
-        // 读取欢迎信息
-        let mut buffer = [0u8; 1024];
-        tls_stream.read(&mut buffer).map_err(|e| format!("读取欢迎信息失败: {}", e))?;
-
-        // 发送ID命令（163邮箱必需）
-        let id_cmd = b"A001 ID (\"name\" \"MailFlow\" \"version\" \"1.0\")\r\n";
-        tls_stream.write_all(id_cmd).map_err(|e| format!("发送ID命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        buffer = [0u8; 1024];
-        tls_stream.read(&mut buffer).map_err(|e| format!("读取ID响应失败: {}", e))?;
-
-        // 发送LOGIN命令
-        let login_cmd = format!("A002 LOGIN \"{}\" \"{}\"\r\n", self.account.email, self.password);
-        tls_stream.write_all(login_cmd.as_bytes()).map_err(|e| format!("发送LOGIN命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        let mut buffer2 = [0u8; 1024];
-        let n = tls_stream.read(&mut buffer2).map_err(|e| format!("读取LOGIN响应失败: {}", e))?;
-        let response = String::from_utf8_lossy(&buffer2[..n]);
-        if response.contains("NO") || response.contains("BAD") {
-            return Err(format!("163邮箱登录失败: {}", response));
-        }
-
-        // 选择文件夹
-        let select_cmd = format!("A003 SELECT \"{}\"\r\n", folder);
-        tls_stream.write_all(select_cmd.as_bytes()).map_err(|e| format!("发送SELECT命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        // 读取响应并解析邮件数量
-        let mut total_count = 0;
-        let mut buffer3 = [0u8; 2048];
-        let n = tls_stream.read(&mut buffer3).map_err(|e| format!("读取SELECT响应失败: {}", e))?;
-        let response = String::from_utf8_lossy(&buffer3[..n]);
-        for line in response.lines() {
-            if line.contains("EXISTS") {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                for part in parts {
-                    if let Ok(count) = part.parse::<usize>() {
-                        total_count = count;
-                        break;
-                    }
-                }
-            }
-        }
-
-        if total_count == 0 {
-            // 发送LOGOUT
-            let _ = tls_stream.write_all(b"A004 LOGOUT\r\n");
-            return Ok(Vec::new());
-        }
-
-        // 计算要获取的邮件范围（最新的）
-        let start = if total_count > limit { total_count - limit + 1 } else { 1 };
-        let end = total_count;
-
-        // 使用 UID SEARCH 获取所有邮件的 UID 列表
-        let mut emails = Vec::new();
-        let search_cmd = b"A004 UID SEARCH ALL\r\n";
-        tls_stream.write_all(search_cmd).map_err(|e| format!("发送SEARCH命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        // 读取SEARCH响应
-        let mut uid_list: Vec<u32> = Vec::new();
-        let mut response_data = String::new();
-        loop {
-            let mut buffer4 = [0u8; 4096];
-            match tls_stream.read(&mut buffer4) {
-                Ok(n) if n > 0 => {
-                    let chunk = String::from_utf8_lossy(&buffer4[..n]);
-                    response_data.push_str(&chunk);
-                    if chunk.contains("A004 OK") || chunk.contains("A004 BAD") || chunk.contains("A004 NO") {
-                        break;
-                    }
-                }
-                _ => break,
-            }
-        }
-
-        // 解析SEARCH响应: * SEARCH 1 2 3 4 5
-        for line in response_data.lines() {
-            let trimmed = line.trim();
-            if trimmed.starts_with("* SEARCH") {
-                // 提取所有 UID 数字
-                let uids: Vec<u32> = trimmed
-                    .split_whitespace()
-                    .skip(2) // 跳过 "*" 和 "SEARCH"
-                    .filter_map(|s| s.parse().ok())
-                    .collect();
-                uid_list.extend(uids);
-            }
-        }
+    pub async fn fetch_email_detail(&self, folder: &str, uid: u32) -> Result<Email, String> {
+        self.fetch_detail(folder, uid, "RFC822").await
+    }
 
-        // 按UID降序排列（最新的在前），并限制数量
-        uid_list.sort_unstable_by(|a, b| b.cmp(a));
-        uid_list.truncate(limit);
+    /// 与 `fetch_email_detail` 相同，但使用 BODY.PEEK[]，不会把邮件标记为已读；
+    /// 用于过滤规则等后台读取
+    pub async fn fetch_email_detail_peek(&self, folder: &str, uid: u32) -> Result<Email, String> {
+        self.fetch_detail(folder, uid, "BODY.PEEK[]").await
+    }
 
-        // 获取每封邮件的完整内容
-        for uid in uid_list {
-            match self.fetch_email_163_rfc822(&mut tls_stream, folder, uid).await {
-                Ok(email) => emails.push(email),
-                Err(_e) => {
-                    // 获取失败时跳过
-                }
-            }
-        }
+    async fn fetch_detail(&self, folder: &str, uid: u32, items: &str) -> Result<Email, String> {
+        self.with_session(|pooled| {
+            pooled.select(folder)?;
 
-        // 发送LOGOUT
-        let _ = tls_stream.write_all(b"A005 LOGOUT\r\n");
-        let _ = tls_stream.flush();
+            let responses = pooled.session
+                .uid_fetch(uid.to_string(), items)
+                .map_err(|e| format!("获取邮件详情失败: {}", e))?;
 
-        Ok(emails)
+            responses.iter()
+                .next()
+                .and_then(|response| response.body())
+                .map(|body| self.parse_email_full(body, uid, folder))
+                .ok_or_else(|| "未找到邮件".to_string())
+        }).await
     }
 
-    // 辅助方法：从163邮箱获取单封邮件的RFC822内容
-    async fn fetch_email_163_rfc822<S: Read + Write>(
-        &self,
-        stream: &mut S,
-        _folder: &str,
-        uid: u32,
-    ) -> Result<EmailSummary, String> {
-
-        // 发送 UID FETCH RFC822 命令
-        let fetch_cmd = format!("A005 UID FETCH {} (RFC822)\r\n", uid);
-        stream.write_all(fetch_cmd.as_bytes()).map_err(|e| format!("发送FETCH命令失败: {}", e))?;
-        stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        // 读取响应
-        let mut response_content = String::new();
-        let mut buffer = [0u8; 4096];
+    /// 下载附件，返回附件信息和解码后的内容
+    pub async fn fetch_attachment(&self, folder: &str, uid: u32, part_id: &str) -> Result<(Attachment, Vec<u8>), String> {
+        self.with_session(|pooled| {
+            pooled.select(folder)?;
 
-        loop {
-            match stream.read(&mut buffer) {
-                Ok(n) if n > 0 => {
-                    let chunk = &buffer[..n];
-                    let text = String::from_utf8_lossy(chunk);
-                    response_content.push_str(&text);
-
-                    // 检查是否完成
-                    if text.contains("A005 OK") || text.contains("A005 BAD") || text.contains("A005 NO") {
-                        break;
-                    }
-                }
-                _ => break,
-            }
-        }
+            let responses = pooled.session
+                .uid_fetch(uid.to_string(), "BODY.PEEK[]")
+                .map_err(|e| format!("获取邮件失败: {}", e))?;
+            let raw = responses.iter()
+                .next()
+                .and_then(|response| response.body())
+                .ok_or("未找到邮件")?;
 
-        // 从IMAP响应中提取实际的RFC822邮件内容
-        let email_content = self.extract_rfc822_from_response(&response_content);
+            let message = MimePart::parse(raw);
+            let part = message.find(part_id)
+                .filter(|part| part.children.is_empty())
+                .ok_or_else(|| format!("未找到附件: {}", part_id))?;
 
-        // 解析邮件内容
-        self.parse_email_from_rfc822(&email_content, uid)
-            .ok_or_else(|| format!("无法解析邮件 UID: {}", uid))
+            let data = part.decoded_body();
+            Ok((part.to_attachment(data.len()), data))
+        }).await
     }
 
-    /// 从IMAP FETCH响应中提取RFC822邮件内容
-    fn extract_rfc822_from_response(&self, response: &str) -> String {
-        let mut in_email = false;
-        let mut email_lines: Vec<&str> = Vec::new();
+    /// 下载 HTML 正文引用的全部内嵌资源，返回 (Content-ID, 解码后的内容)
+    pub async fn fetch_inline_resources(&self, folder: &str, uid: u32) -> Result<Vec<(String, Vec<u8>)>, String> {
+        self.with_session(|pooled| {
+            pooled.select(folder)?;
 
-        for line in response.lines() {
-            let trimmed = line.trim();
-
-            // 检测到FETCH响应开始行，例如: * 1 FETCH (RFC822 {1234}
-            if trimmed.starts_with("*") && trimmed.contains("FETCH") && trimmed.contains("RFC822") {
-                in_email = true;
-                continue;
-            }
-
-            // 检测结束标记：单独的 ) 行
-            if in_email && trimmed == ")" {
-                break;
-            }
-
-            // 检测IMAP响应结束标记
-            if trimmed.starts_with("A005") && (trimmed.contains("OK") || trimmed.contains("BAD") || trimmed.contains("NO")) {
-                break;
-            }
+            let responses = pooled.session
+                .uid_fetch(uid.to_string(), "BODY.PEEK[]")
+                .map_err(|e| format!("获取邮件失败: {}", e))?;
+            let raw = responses.iter()
+                .next()
+                .and_then(|response| response.body())
+                .ok_or("未找到邮件")?;
+
+            let message = MimePart::parse(raw);
+            Ok(message.inline_parts()
+                .into_iter()
+                .filter_map(|part| Some((part.content_id.clone()?, part.decoded_body())))
+                .collect())
+        }).await
+    }
 
-            // 收集邮件内容行
-            if in_email {
-                email_lines.push(line);
-            }
-        }
+    fn parse_email_summary(&self, response: &imap::types::Fetch, uid: u32) -> Option<EmailSummary> {
+        let flags = response.flags();
 
-        email_lines.join("\n")
-    }
+        let envelope = response.envelope()?;
 
-    /// 从RFC822格式的邮件内容解析EmailSummary
-    fn parse_email_from_rfc822(&self, content: &str, uid: u32) -> Option<EmailSummary> {
-        let subject = self.extract_header(content, "Subject")
-            .map(|s| self.decode_rfc2047(&s))
+        // ENVELOPE 中的主题和发件人名称保留了 RFC 2047 编码，需要解码
+        let subject = envelope.subject
+            .and_then(|s| std::str::from_utf8(s).ok())
+            .map(decode_rfc2047)
             .unwrap_or_else(|| "(无主题)".to_string());
 
-        let from = self.extract_header(content, "From")
-            .map(|s| {
-                let decoded = self.decode_rfc2047(&s);
-                self.extract_display_name(&decoded)
-            })
+        let [from, sender, reply_to, to, cc, bcc] = [
+            &envelope.from,
+            &envelope.sender,
+            &envelope.reply_to,
+            &envelope.to,
+            &envelope.cc,
+            &envelope.bcc,
+        ].map(|addrs| {
+            addrs.iter()
+                .flatten()
+                .filter_map(|addr| envelope_address(addr.name, addr.mailbox, addr.host))
+                .collect::<Vec<_>>()
+        });
+        let addresses = EmailAddresses { from, sender, reply_to, to, cc, bcc };
+
+        let from = addresses.from
+            .first()
+            .map(|addr| addr.display_name().to_string())
             .unwrap_or_else(|| "未知发件人".to_string());
 
-        let date_str = self.extract_header(content, "Date").unwrap_or_default();
-        let date = chrono::DateTime::parse_from_rfc2822(&date_str)
-            .ok()
-            .map(|dt| dt.with_timezone(&chrono::Utc).to_rfc3339())
-            .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
-
-        // 提取正文内容用于预览
-        let (body, _html) = self.extract_body(content);
-        let preview = body.chars().take(200).collect::<String>().replace('\n', " ");
+        let date = envelope.date
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|s| {
+                chrono::DateTime::parse_from_rfc2822(s).ok()
+                    .map(|dt| dt.with_timezone(&chrono::Utc))
+            })
+            .unwrap_or_else(|| chrono::Utc::now());
 
-        // 检查是否有附件
-        let has_attachment = content.to_lowercase().contains("content-disposition: attachment");
+        let is_read = flags.contains(&imap::types::Flag::Seen);
+        let is_starred = flags.contains(&imap::types::Flag::Flagged);
+        let has_attachment = response.bodystructure().is_some_and(structure_has_attachment);
 
         Some(EmailSummary {
             id: format!("{}_{}", self.account.id, uid),
             uid,
             subject,
             from,
-            date,
-            is_read: false, // 163邮箱需要单独处理已读状态
-            is_starred: false,
+            date: date.to_rfc3339(),
+            is_read,
+            is_starred,
             has_attachment,
             category: None,
-            preview,
-            body: body.chars().take(1000).collect(), // 限制body长度
+            preview: String::new(),
+            body: String::new(),
+            size: response.size.map(u64::from).unwrap_or(0),
+            tags: Vec::new(),
+            source: None,
+            addresses,
         })
     }
 
-    /// 解码 RFC 2047 编码的字符串（如 =?UTF-8?B?...?= 或 =?UTF-8?Q?...?=）
-    fn decode_rfc2047(&self, text: &str) -> String {
-        // 正则表达式风格的手动解析
-        let mut result = String::new();
-        let mut chars = text.chars().peekable();
+    /// 由邮件头和截断的正文生成预览文本
+    fn preview_text(&self, header: &[u8], text: &[u8]) -> String {
+        // 没有邮件头时以空行开头，正文按纯文本处理
+        let mut raw = if header.is_empty() { b"\r\n".to_vec() } else { header.to_vec() };
+        raw.extend_from_slice(text);
+
+        let (plain, html) = MimePart::parse(&raw).body_text();
+        plain
+            .map(|plain| plain.trim().to_string())
+            .or_else(|| html.map(|html| self.html_to_text(&html)))
+            .unwrap_or_default()
+    }
 
-        while let Some(ch) = chars.next() {
-            if ch == '=' && chars.peek() == Some(&'?') {
-                // 尝试解析 RFC 2047 编码块
-                chars.next(); // 跳过 '?'
-
-                // 读取字符集
-                let mut charset = String::new();
-                while let Some(&c) = chars.peek() {
-                    if c == '?' {
-                        break;
-                    }
-                    charset.push(c);
-                    chars.next();
-                }
+    fn parse_email_full(&self, body: &[u8], uid: u32, folder: &str) -> Email {
+        let message = MimePart::parse(body);
+
+        // 邮件头只解析一次：MimePart 已展开折叠行，这里再解码 RFC 2047 编码供显示和查找
+        let headers: HeaderMap = message.headers
+            .iter()
+            .map(|(name, value)| EmailHeader {
+                name: name.clone(),
+                value: decode_rfc2047(value),
+            })
+            .collect();
 
-                if chars.peek() != Some(&'?') {
-                    result.push('=');
-                    result.push_str(&charset);
-                    continue;
-                }
-                chars.next(); // 跳过 '?'
-
-                // 读取编码方式 (B 或 Q)
-                let encoding = chars.next();
-                if chars.peek() != Some(&'?') {
-                    result.push('=');
-                    result.push('?');
-                    result.push_str(&charset);
-                    result.push('?');
-                    if let Some(e) = encoding {
-                        result.push(e);
-                    }
-                    continue;
-                }
-                chars.next(); // 跳过 '?'
-
-                // 读取编码内容直到 ?=
-                let mut encoded = String::new();
-                while let Some(&c) = chars.peek() {
-                    if c == '?' {
-                        chars.next();
-                        if chars.peek() == Some(&'=') {
-                            chars.next();
-                            break;
-                        } else {
-                            encoded.push('?');
-                        }
-                    } else {
-                        encoded.push(c);
-                        chars.next();
-                    }
-                }
+        let subject = headers.get("Subject")
+            .map(str::to_string)
+            .unwrap_or_else(|| "(无主题)".to_string());
 
-                // 解码
-                let decoded = match encoding {
-                    Some('B') | Some('b') => {
-                        // Base64 解码
-                        base64::decode(encoded.replace(" ", ""))
-                            .ok()
-                            .and_then(|bytes| {
-                                if charset.to_lowercase().contains("utf-8") {
-                                    String::from_utf8(bytes).ok()
-                                } else {
-                                    // 对于其他编码，尝试 UTF-8
-                                    String::from_utf8(bytes).ok()
-                                }
-                            })
-                    }
-                    Some('Q') | Some('q') => {
-                        // Quoted-Printable 解码
-                        Some(self.decode_rfc2047_q_encoding(&encoded))
-                    }
-                    _ => None,
-                };
-
-                if let Some(decoded_str) = decoded {
-                    result.push_str(&decoded_str);
-                } else {
-                    // 解码失败，保留原始编码
-                    result.push_str(&format!("=?{}?{}?{}?=" , charset,
-                        encoding.map(|c| c.to_string()).unwrap_or_default(), encoded));
-                }
-            } else {
-                result.push(ch);
-            }
-        }
+        // 地址头按原始值解析，避免解码后名称中的逗号被当作分隔符；同名的多个头合并
+        let address_list = |name: &str| -> Vec<Address> {
+            message.headers
+                .iter()
+                .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+                .flat_map(|(_, value)| parse_address_list(value))
+                .collect()
+        };
+        let addresses = EmailAddresses {
+            from: address_list("From"),
+            sender: address_list("Sender"),
+            reply_to: address_list("Reply-To"),
+            to: address_list("To"),
+            cc: address_list("Cc"),
+            bcc: address_list("Bcc"),
+        };
 
-        result
+        let from = addresses.from
+            .first()
+            .map(Address::to_string)
+            .unwrap_or_else(|| "未知发件人".to_string());
+        let to = addresses.to.iter().map(Address::to_string).collect();
+        let cc = addresses.cc.iter().map(Address::to_string).collect();
+
+        let date = headers.get("Date")
+            .and_then(|d| {
+                chrono::DateTime::parse_from_rfc2822(d).ok()
+                    .map(|dt| dt.with_timezone(&chrono::Utc))
+            })
+            .unwrap_or_else(|| chrono::Utc::now());
+
+        let (plain, html) = message.body_text();
+        let html_body = html
+            .map(|html| html.trim().to_string())
+            .filter(|html| !html.is_empty());
+        // 只有 HTML 版本时由 HTML 生成纯文本
+        let plain_body = match plain {
+            Some(plain) => plain.trim().to_string(),
+            None => html_body.as_deref().map(|html| self.html_to_text(html)).unwrap_or_default(),
+        };
+        let attachments = message.attachments();
+        let inline_resources = message.inline_resources();
+
+        Email {
+            id: format!("{}_{}", self.account.id, uid),
+            uid,
+            subject,
+            from,
+            to,
+            cc,
+            addresses,
+            date,
+            body: plain_body,
+            html_body,
+            folder: folder.to_string(),
+            flags: Vec::new(),
+            is_read: false,
+            is_starred: false,
+            category: None,
+            has_attachment: !attachments.is_empty(),
+            size: body.len() as u64,
+            headers,
+            attachments,
+            inline_resources,
+        }
     }
 
-    /// 解码 RFC 2047 Q 编码（类似 Quoted-Printable）
-    fn decode_rfc2047_q_encoding(&self, encoded: &str) -> String {
+    /// 将 HTML 转换为纯文本（移除标签，保留内容）
+    fn html_to_text(&self, html: &str) -> String {
         let mut result = String::new();
-        let mut chars = encoded.chars().peekable();
+        let mut in_tag = false;
+        let mut in_script = false;
+        let mut in_style = false;
+        let mut tag_name = String::new();
+        let mut chars = html.chars().peekable();
 
         while let Some(ch) = chars.next() {
-            if ch == '=' {
-                let hex: String = chars.by_ref().take(2).collect();
-                if let Ok(byte) = u8::from_str_radix(&hex, 16) {
-                    result.push(byte as char);
-                } else {
-                    result.push('=');
-                    result.push_str(&hex);
-                }
-            } else if ch == '_' {
-                result.push(' ');
-            } else {
-                result.push(ch);
+            if ch == '<' {
+                in_tag = true;
+                tag_name.clear();
+                continue;
             }
-        }
 
-        result
-    }
+            if in_tag {
+                if ch == '>' {
+                    in_tag = false;
+                    let tag_lower = tag_name.to_lowercase();
 
-    /// 从 From 头中提取显示名称
-    /// 输入: "Name" <email@example.com> 或 email@example.com
-    /// 输出: Name 或 email (如果无显示名称则返回@前的部分)
-    fn extract_display_name(&self, from: &str) -> String {
-        let trimmed = from.trim();
+                    // 检测 script 和 style 标签
+                    if tag_lower.starts_with("script") || tag_lower.starts_with("/script") {
+                        in_script = !tag_lower.starts_with("/");
+                    }
+                    if tag_lower.starts_with("style") || tag_lower.starts_with("/style") {
+                        in_style = !tag_lower.starts_with("/");
+                    }
 
-        // 尝试提取 "Name" <email> 中的 Name
-        if trimmed.starts_with('"') {
-            // 格式: "Name" <email>
-            if let Some(end_quote) = trimmed[1..].find('"') {
-                return trimmed[1..=end_quote].to_string();
+                    // 块级元素添加换行
+                    if tag_lower.starts_with("/div") ||
+                       tag_lower.starts_with("/p") ||
+                       tag_lower.starts_with("/h") ||
+                       tag_lower.starts_with("br") {
+                        if !result.ends_with('\n') {
+                            result.push(' ');
+                        }
+                    }
+                } else if ch.is_ascii_alphabetic() || ch == '/' {
+                    tag_name.push(ch);
+                }
+                continue;
             }
-        }
 
-        // 尝试提取 Name <email> 中的 Name
-        if let Some(lt_pos) = trimmed.find('<') {
-            let name_part = trimmed[..lt_pos].trim();
-            if !name_part.is_empty() {
-                return name_part.to_string();
-            }
-            // 如果没有名称部分，提取邮箱@前的部分
-            if let Some(gt_pos) = trimmed.find('>') {
-                let email = &trimmed[lt_pos + 1..gt_pos];
-                if let Some(at_pos) = email.find('@') {
-                    return email[..at_pos].to_string();
-                }
-                return email.to_string();
+            // 跳过 script 和 style 内容
+            if in_script || in_style {
+                continue;
             }
-        }
 
-        // 纯邮箱格式: email@example.com
-        if let Some(at_pos) = trimmed.find('@') {
-            return trimmed[..at_pos].to_string();
+            // 保留普通文本
+            if !in_tag {
+                result.push(ch);
+            }
         }
 
-        // 返回原值
-        trimmed.to_string()
+        // 清理多余空白
+        result.split_whitespace().collect::<Vec<_>>().join(" ")
     }
 
-    pub async fn fetch_email_detail(&self, folder: &str, uid: u32) -> Result<Email, String> {
-        // 163邮箱使用特殊处理
-        if is_163_email(&self.account) {
-            return self.fetch_email_detail_163(folder, uid).await;
+    /// 同步已缓存邮件（known_uids）的标志和删除情况
+    /// 支持 CONDSTORE/QRESYNC（RFC 7162）且记录过 modseq 时只取 modseq 之后的变化；
+    /// 否则重新取得缓存范围内全部邮件的标志，并把服务器上已不存在的 UID 视为已删除
+    pub async fn fetch_flag_changes(
+        &self,
+        folder: &str,
+        uid_validity: u32,
+        modseq: u64,
+        known_uids: &[u32],
+    ) -> Result<FlagChanges, String> {
+        if known_uids.is_empty() {
+            return Ok(FlagChanges::default());
         }
 
-        let mut client = self.connect().await?;
+        let condstore = self.with_session(|pooled| {
+            Ok(pooled.has_capability("CONDSTORE") || pooled.has_capability("QRESYNC"))
+        }).await?;
+
+        if condstore {
+            // imap crate 无法解析 MODSEQ 和 VANISHED，改用直接收发命令的连接
+            self.fetch_changes_since(folder, uid_validity, modseq, known_uids)
+        } else {
+            self.resync_flags(folder, known_uids).await
+        }
+    }
 
-        client
-            .select(format!("\"{}\"", folder))
-            .map_err(|e| format!("选择文件夹失败: {}", e))?;
+    fn fetch_changes_since(
+        &self,
+        folder: &str,
+        uid_validity: u32,
+        modseq: u64,
+        known_uids: &[u32],
+    ) -> Result<FlagChanges, String> {
+        self.with_raw(|conn| Self::changes_since(conn, folder, uid_validity, modseq, known_uids))
+    }
 
-        let responses = client
-            .fetch(uid.to_string(), "(RFC822)")
-            .map_err(|e| format!("获取邮件详情失败: {}", e))?;
+    /// 使用连接池中直接收发命令的连接，没有时新建；出错后连接状态未知，不再归还
+    fn with_raw<R>(&self, op: impl FnOnce(&mut RawImapConnection) -> Result<R, String>) -> Result<R, String> {
+        let pool = ImapPool::global();
+        let mut conn = match pool.checkout_raw(&self.account.id) {
+            Some(conn) => conn,
+            None => RawImapConnection::open(
+                &self.account,
+                &self.password,
+                Duration::from_secs(RAW_READ_TIMEOUT_SECONDS),
+            )?,
+        };
 
-        if let Some(response) = responses.iter().next() {
-            if let Some(body) = response.body() {
-                client.logout().map_err(|e| format!("登出失败: {}", e))?;
-                return Ok(self.parse_email_full(body, uid, folder));
+        match op(&mut conn) {
+            Ok(result) => {
+                pool.checkin_raw(&self.account.id, conn);
+                Ok(result)
+            }
+            Err(e) => {
+                conn.logout();
+                Err(e)
             }
         }
-
-        client.logout().map_err(|e| format!("登出失败: {}", e))?;
-        Err("未找到邮件".to_string())
     }
 
-    /// 为163邮箱获取邮件详情
-    async fn fetch_email_detail_163(&self, folder: &str, uid: u32) -> Result<Email, String> {
-        use native_tls::TlsConnector;
-        use std::io::{Read, Write};
+    fn changes_since(
+        conn: &mut RawImapConnection,
+        folder: &str,
+        uid_validity: u32,
+        modseq: u64,
+        known_uids: &[u32],
+    ) -> Result<FlagChanges, String> {
+        let range = uid_range(known_uids);
+        let qresync = modseq > 0 && conn.capabilities()?.iter().any(|c| c == "QRESYNC");
+
+        let select = if qresync {
+            conn.run("ENABLE QRESYNC")?;
+            format!("SELECT {} (QRESYNC ({} {} {}))", quote_mailbox(folder), uid_validity, modseq, range)
+        } else {
+            format!("SELECT {} (CONDSTORE)", quote_mailbox(folder))
+        };
+        let selected = conn.run(&select)
+            .map_err(|e| format!("选择文件夹 '{}' 失败: {}", folder, e))?;
 
-        // 建立TCP连接
-        let stream = TcpStream::connect((self.account.imap_server.as_str(), self.account.imap_port))
-            .map_err(|e| format!("TCP连接失败: {}", e))?;
+        let mut changes = FlagChanges {
+            highest_modseq: highest_modseq(&selected),
+            ..FlagChanges::default()
+        };
 
-        // 创建TLS连接器
-        let tls = if cfg!(debug_assertions) {
-            TlsConnector::builder()
-                .danger_accept_invalid_certs(true)
-                .danger_accept_invalid_hostnames(true)
-                .build()
+        if qresync && changes.highest_modseq > 0 {
+            // SELECT 响应中已包含 modseq 之后的标志变化和 VANISHED (EARLIER)
+            collect_changes(&selected, &mut changes)?;
         } else {
-            TlsConnector::builder().build()
-        }.map_err(|e| format!("TLS创建失败: {}", e))?;
-
-        // 升级到TLS
-        let mut tls_stream = tls.connect(&self.account.imap_server, stream)
-            .map_err(|e| format!("TLS连接失败: {}", e))?;
-
-        // 读取欢迎信息
-        let mut buffer = [0u8; 1024];
-        tls_stream.read(&mut buffer).map_err(|e| format!("读取欢迎信息失败: {}", e))?;
-
-        // 发送ID命令（163邮箱必需）
-        let id_cmd = b"A001 ID (\"name\" \"MailFlow\" \"version\" \"1.0\")\r\n";
-        tls_stream.write_all(id_cmd).map_err(|e| format!("发送ID命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        buffer = [0u8; 1024];
-        tls_stream.read(&mut buffer).map_err(|e| format!("读取ID响应失败: {}", e))?;
-
-        // 发送LOGIN命令
-        let login_cmd = format!("A002 LOGIN \"{}\" \"{}\"\r\n", self.account.email, self.password);
-        tls_stream.write_all(login_cmd.as_bytes()).map_err(|e| format!("发送LOGIN命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        let mut buffer2 = [0u8; 1024];
-        let n = tls_stream.read(&mut buffer2).map_err(|e| format!("读取LOGIN响应失败: {}", e))?;
-        let response = String::from_utf8_lossy(&buffer2[..n]);
-        if response.contains("NO") || response.contains("BAD") {
-            return Err(format!("163邮箱登录失败: {}", response));
+            // 文件夹不支持 modseq（NOMODSEQ）时取全部标志
+            let fetch = if modseq > 0 && changes.highest_modseq > 0 {
+                format!("UID FETCH {} (FLAGS) (CHANGEDSINCE {})", range, modseq)
+            } else {
+                format!("UID FETCH {} (FLAGS)", range)
+            };
+            let fetched = conn.run(&fetch)
+                .map_err(|e| format!("获取邮件标志失败: {}", e))?;
+            collect_changes(&fetched, &mut changes)?;
+
+            let found = conn.run(&format!("UID SEARCH UID {}", range))
+                .map_err(|e| format!("核对已删除邮件失败: {}", e))?;
+            let existing: HashSet<u32> = found
+                .iter()
+                .filter(|r| r.kind().as_deref() == Some("SEARCH"))
+                .flat_map(|r| r.values.iter().skip(1).filter_map(ImapValue::as_number))
+                .map(|uid| uid as u32)
+                .collect();
+            changes.vanished.extend(known_uids.iter().filter(|uid| !existing.contains(*uid)));
         }
 
-        // 选择文件夹
-        let select_cmd = format!("A003 SELECT \"{}\"\r\n", folder);
-        tls_stream.write_all(select_cmd.as_bytes()).map_err(|e| format!("发送SELECT命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
+        let known: HashSet<&u32> = known_uids.iter().collect();
+        changes.vanished.retain(|uid| known.contains(uid));
+        changes.flags.retain(|uid, _| known.contains(uid));
 
-        let mut buffer3 = [0u8; 2048];
-        tls_stream.read(&mut buffer3).map_err(|e| format!("读取SELECT响应失败: {}", e))?;
+        Ok(changes)
+    }
 
-        // 发送 UID FETCH RFC822 命令
-        let fetch_cmd = format!("A004 UID FETCH {} (RFC822)\r\n", uid);
-        tls_stream.write_all(fetch_cmd.as_bytes()).map_err(|e| format!("发送FETCH命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
+    /// 不支持 CONDSTORE 时的回退：重新取得全部已缓存邮件的标志
+    async fn resync_flags(&self, folder: &str, known_uids: &[u32]) -> Result<FlagChanges, String> {
+        let range = uid_range(known_uids);
 
-        // 读取响应
-        let mut response_content = String::new();
-        loop {
-            let mut buffer4 = [0u8; 4096];
-            match tls_stream.read(&mut buffer4) {
-                Ok(n) if n > 0 => {
-                    let chunk = String::from_utf8_lossy(&buffer4[..n]);
-                    response_content.push_str(&chunk);
-                    if chunk.contains("A004 OK") || chunk.contains("A004 BAD") || chunk.contains("A004 NO") {
-                        break;
-                    }
+        self.with_session(|pooled| {
+            pooled.select(folder)?;
+
+            let fetches = pooled.session
+                .uid_fetch(&range, "(UID FLAGS)")
+                .map_err(|e| format!("获取邮件标志失败: {}", e))?;
+
+            let mut changes = FlagChanges::default();
+            for fetch in fetches.iter() {
+                if let Some(uid) = fetch.uid {
+                    let flags = fetch.flags().iter().map(|flag| flag.to_string()).collect();
+                    changes.flags.insert(uid, flags);
                 }
-                _ => break,
             }
-        }
-
-        // 发送LOGOUT
-        let _ = tls_stream.write_all(b"A005 LOGOUT\r\n");
-        let _ = tls_stream.flush();
 
-        // 提取邮件内容
-        let email_content = self.extract_rfc822_from_response(&response_content);
-        if email_content.is_empty() {
-            return Err("未找到邮件内容".to_string());
-        }
+            // UID FETCH 不会返回已删除的邮件
+            changes.vanished = known_uids
+                .iter()
+                .copied()
+                .filter(|uid| !changes.flags.contains_key(uid))
+                .collect();
+            changes.flags.retain(|uid, _| known_uids.contains(uid));
 
-        Ok(self.parse_email_full_str(&email_content, uid, folder))
+            Ok(changes)
+        }).await
     }
 
-    fn parse_email_summary(&self, response: &imap::types::Fetch, uid: u32) -> Option<EmailSummary> {
-        let flags = response.flags();
-
-        let envelope = response.envelope()?;
-
-        let subject = envelope.subject
-            .and_then(|s| std::str::from_utf8(s).ok())
-            .map(|s| s.to_string())
-            .unwrap_or_else(|| "(无主题)".to_string());
-
-        let from = envelope.from
-            .as_ref()
-            .and_then(|addrs| addrs.first())
-            .and_then(|addr| {
-                addr.name.as_ref()
-                    .or_else(|| addr.mailbox.as_ref())
-            })
-            .and_then(|s| std::str::from_utf8(s).ok())
-            .map(|s| s.to_string())
-            .unwrap_or_else(|| "未知发件人".to_string());
-
-        let date = envelope.date
-            .and_then(|d| std::str::from_utf8(d).ok())
-            .and_then(|s| {
-                chrono::DateTime::parse_from_rfc2822(s).ok()
-                    .map(|dt| dt.with_timezone(&chrono::Utc))
-            })
-            .unwrap_or_else(|| chrono::Utc::now());
+    pub async fn mark_as_read(&self, folder: &str, uid: u32) -> Result<(), String> {
+        self.with_session(|pooled| {
+            pooled.select(folder)?;
 
-        let is_read = flags.contains(&imap::types::Flag::Seen);
-        let is_starred = flags.contains(&imap::types::Flag::Flagged);
-        let has_attachment = false;
+            pooled.session
+                .uid_store(uid.to_string(), "+FLAGS (\\Seen)")
+                .map_err(|e| format!("标记已读失败: {}", e))?;
 
-        Some(EmailSummary {
-            id: format!("{}_{}", self.account.id, uid),
-            uid,
-            subject,
-            from,
-            date: date.to_rfc3339(),
-            is_read,
-            is_starred,
-            has_attachment,
-            category: None,
-            preview: String::new(),
-            body: String::new(),
-        })
+            Ok(())
+        }).await
     }
 
-    fn parse_email_full(&self, body: &[u8], uid: u32, folder: &str) -> Email {
-        let body_str = String::from_utf8_lossy(body).to_string();
-        self.parse_email_full_str(&body_str, uid, folder)
+    /// 标记邮件为星标
+    pub async fn mark_as_starred(&self, folder: &str, uid: u32) -> Result<(), String> {
+        self.store_flags(folder, uid, "\\Flagged").await
     }
 
-    fn parse_email_full_str(&self, body_str: &str, uid: u32, folder: &str) -> Email {
-        let subject = self.extract_header(body_str, "Subject")
-            .map(|s| self.decode_rfc2047(&s))
-            .unwrap_or_else(|| "(无主题)".to_string());
-
-        let from = self.extract_header(body_str, "From")
-            .map(|s| self.decode_rfc2047(&s))
-            .unwrap_or_else(|| "未知发件人".to_string());
-
-        let to = self.extract_header(body_str, "To")
-            .map(|t| vec![self.decode_rfc2047(&t)])
-            .unwrap_or_default();
+    /// 为邮件添加 IMAP 关键字（用于过滤规则的标签）
+    pub async fn add_keyword(&self, folder: &str, uid: u32, keyword: &str) -> Result<(), String> {
+        // 关键字必须是 IMAP atom，不能包含空格、括号、引号等特殊字符
+        let is_atom = !keyword.is_empty() && keyword.chars().all(|c| {
+            c.is_ascii_graphic() && !matches!(c, '(' | ')' | '{' | '%' | '*' | '"' | '\\' | ']')
+        });
+        if !is_atom {
+            return Err(format!("标签 '{}' 不能作为IMAP关键字", keyword));
+        }
 
-        let date = self.extract_header(body_str, "Date")
-            .and_then(|d| {
-                chrono::DateTime::parse_from_rfc2822(&d).ok()
-                    .map(|dt| dt.with_timezone(&chrono::Utc))
-            })
-            .unwrap_or_else(|| chrono::Utc::now());
+        self.store_flags(folder, uid, keyword).await
+    }
 
-        let (plain_body, html_body) = self.extract_body(body_str);
+    /// 为邮件追加标志（+FLAGS）
+    async fn store_flags(&self, folder: &str, uid: u32, flags: &str) -> Result<(), String> {
+        self.with_session(|pooled| {
+            pooled.select(folder)?;
 
-        // 检查是否有附件
-        let has_attachment = body_str.to_lowercase().contains("content-disposition: attachment");
+            pooled.session
+                .uid_store(uid.to_string(), format!("+FLAGS ({})", flags))
+                .map_err(|e| format!("设置邮件标志失败: {}", e))?;
 
-        Email {
-            id: format!("{}_{}", self.account.id, uid),
-            uid,
-            subject,
-            from,
-            to,
-            date,
-            body: plain_body,
-            html_body: if html_body.is_empty() { None } else { Some(html_body) },
-            folder: folder.to_string(),
-            flags: Vec::new(),
-            is_read: false,
-            is_starred: false,
-            category: None,
-            has_attachment,
-            size: body_str.len() as u64,
-        }
-    }
-
-    fn extract_header(&self, email: &str, header: &str) -> Option<String> {
-        email.lines()
-            .find(|line| line.starts_with(&format!("{}:", header)))
-            .and_then(|line| {
-                line.split(':')
-                    .skip(1)
-                    .next()
-                    .map(|s| s.trim().to_string())
-            })
+            Ok(())
+        }).await
     }
 
-    /// 提取邮件正文内容
-    /// 改进版：支持 MIME 多部分邮件解析和字符集检测
-    /// 返回 (plain_text, html_text) 元组
-    fn extract_body(&self, email: &str) -> (String, String) {
-        // 查找头部和正文的分隔（第一个空行）
-        let parts: Vec<&str> = email.splitn(2, "\n\n").collect();
-        if parts.len() < 2 {
-            return (String::new(), String::new());
-        }
+    pub async fn delete_email(&self, folder: &str, uid: u32) -> Result<(), String> {
+        self.with_session_once(|pooled| {
+            pooled.select(folder)?;
 
-        let headers = parts[0];
-        let body = parts[1];
+            pooled.session
+                .uid_store(uid.to_string(), "+FLAGS (\\Deleted)")
+                .map_err(|e| format!("删除邮件失败: {}", e))?;
 
-        // 获取完整的 Content-Type（包含 charset）
-        let content_type_full = self.extract_header(headers, "Content-Type")
-            .unwrap_or_default();
-        let content_type_lower = content_type_full.to_lowercase();
+            Self::expunge_uid(pooled, uid)
+        }).await
+    }
 
-        if content_type_lower.contains("multipart/") {
-            // 使用更健壮的 boundary 提取
-            if let Some(boundary) = self.extract_boundary(&content_type_full) {
-                return self.extract_multipart_body(body, &boundary);
-            }
-            // 后备方案：简单的 boundary 提取
-            if let Some(boundary) = content_type_lower.split("boundary=").nth(1) {
-                let boundary = boundary.trim().trim_matches('"').trim_matches('\'');
-                return self.extract_multipart_body(body, boundary);
+    pub async fn move_email(&self, folder: &str, uid: u32, dest_folder: &str) -> Result<(), String> {
+        self.with_session_once(|pooled| {
+            pooled.select(folder)?;
+
+            // 服务器支持 MOVE（RFC 6851）时一步完成
+            if pooled.has_capability("MOVE") {
+                return pooled.session
+                    .run_command_and_check_ok(format!("UID MOVE {} {}", uid, quote_mailbox(dest_folder)))
+                    .map_err(|e| format!("移动邮件失败: {}", e));
             }
-        }
 
-        // 提取字符集
-        let charset = self.extract_charset(&content_type_full);
+            pooled.session
+                .uid_copy(uid.to_string(), quote_mailbox(dest_folder))
+                .map_err(|e| format!("复制邮件失败: {}", e))?;
 
-        // 检查内容传输编码
-        let transfer_encoding = self.extract_header(headers, "Content-Transfer-Encoding")
-            .unwrap_or_default();
+            pooled.session
+                .uid_store(uid.to_string(), "+FLAGS (\\Deleted)")
+                .map_err(|e| format!("标记删除失败: {}", e))?;
 
-        let decoded_body = if transfer_encoding.to_lowercase().contains("base64") {
-            self.decode_base64_with_charset(body, &charset)
-        } else if transfer_encoding.to_lowercase().contains("quoted-printable") {
-            self.decode_quoted_printable_with_charset(body, &charset)
-        } else {
-            // 对于普通文本，尝试使用检测到的字符集
-            self.try_decode_with_charset(body.as_bytes(), &charset)
-        };
+            Self::expunge_uid(pooled, uid)
+        }).await
+    }
 
-        // 非 multipart 邮件：检查是否是 HTML
-        if content_type_lower.contains("text/html") {
-            let html = decoded_body.trim().to_string();
-            let plain = self.html_to_text(&html);
-            (plain, html)
+    /// 清除已标记删除的邮件
+    /// 支持 UIDPLUS（RFC 4315）时只清除指定 UID，避免误删其他客户端标记删除的邮件
+    fn expunge_uid(pooled: &mut PooledSession, uid: u32) -> Result<(), String> {
+        if pooled.has_capability("UIDPLUS") {
+            pooled.session.uid_expunge(uid.to_string())
         } else {
-            (decoded_body.trim().to_string(), String::new())
+            pooled.session.expunge()
         }
+        .map(|_| ())
+        .map_err(|e| format!("清理失败: {}", e))
     }
 
-    /// 更健壮的 boundary 提取
-    fn extract_boundary(&self, content_type: &str) -> Option<String> {
-        let content_type_lower = content_type.to_lowercase();
-        if let Some(boundary_pos) = content_type_lower.find("boundary=") {
-            let boundary_str = &content_type[boundary_pos + 9..];
-            // 处理引号包围、分号结尾、跨行等情况
-            let mut boundary = String::new();
-            let mut in_quotes = false;
-            let mut started = false;
-
-            for ch in boundary_str.chars() {
-                match ch {
-                    '"' => {
-                        if started && !in_quotes { break; }
-                        in_quotes = !in_quotes;
-                        started = true;
-                    }
-                    ';' | ' ' | '\t' | '\n' | '\r' if !in_quotes => {
-                        if started { break; }
-                    }
-                    _ => {
-                        boundary.push(ch);
-                        started = true;
-                    }
-                }
-            }
+}
 
-            let boundary = boundary.trim().to_string();
-            if !boundary.is_empty() { return Some(boundary); }
+/// BODYSTRUCTURE 中是否有附件，判断规则与 `MimePart::attachments` 一致：
+/// 非文本的叶子部分和转发的邮件是附件，文本部分标记为 attachment 或带文件名时是附件，
+/// multipart/related 中正文引用的内嵌资源不算附件
+fn structure_has_attachment(structure: &BodyStructure<'_>) -> bool {
+    match structure {
+        BodyStructure::Multipart { common, bodies, .. } => {
+            let related_root = common.ty.subtype.eq_ignore_ascii_case("related").then(|| {
+                let start = structure_param(&common.ty.params, "start");
+                start
+                    .and_then(|start| bodies.iter().position(|body| structure_content_id(body) == Some(start)))
+                    .unwrap_or(0)
+            });
+
+            bodies.iter().enumerate().any(|(index, body)| {
+                let inline_resource = related_root.is_some_and(|root| root != index)
+                    && structure_content_id(body).is_some()
+                    && !structure_disposition_is(body, "attachment");
+                !inline_resource && structure_has_attachment(body)
+            })
         }
-        None
+        BodyStructure::Text { common, .. } => match &common.disposition {
+            Some(disposition) => disposition.ty.eq_ignore_ascii_case("attachment"),
+            None => structure_param(&common.ty.params, "name").is_some(),
+        },
+        BodyStructure::Basic { .. } | BodyStructure::Message { .. } => true,
     }
+}
 
-    /// 合并被折叠的头部行（RFC 822）
-    fn unfold_headers(&self, headers: &str) -> String {
-        let mut result = String::new();
-        for line in headers.lines() {
-            if line.starts_with(' ') || line.starts_with('\t') {
-                // 折叠行：移除前导空白并追加到上一行
-                result.push_str(line.trim_start());
-            } else {
-                if !result.is_empty() { result.push('\n'); }
-                result.push_str(line);
-            }
+/// 参数值，RFC 2231 分段或编码的参数（如 `name*0*`）按参数名前缀匹配
+fn structure_param<'a>(params: &BodyParams<'a>, name: &str) -> Option<&'a str> {
+    params.iter().flatten().find_map(|(key, value)| {
+        let base = key.split('*').next().unwrap_or_default();
+        base.eq_ignore_ascii_case(name).then_some(*value)
+    })
+}
+
+/// 叶子部分去掉尖括号的 Content-ID
+fn structure_content_id<'a>(structure: &BodyStructure<'a>) -> Option<&'a str> {
+    match structure {
+        BodyStructure::Basic { other, .. }
+        | BodyStructure::Text { other, .. }
+        | BodyStructure::Message { other, .. } => {
+            other.id.map(|id| id.trim().trim_start_matches('<').trim_end_matches('>'))
         }
-        result
+        BodyStructure::Multipart { .. } => None,
     }
+}
 
-    /// 从 MIME 多部分邮件中提取纯文本和 HTML 内容
-    /// 返回 (plain_text, html_text) 元组
-    fn extract_multipart_body(&self, body: &str, boundary: &str) -> (String, String) {
-        let delimiter = format!("--{}", boundary);
-        let end_marker = format!("--{}--", boundary);
-
-        let mut text_plain_content = String::new();
-        let mut text_html_content = String::new();
-        let mut current_headers = String::new();
-        let mut current_content = String::new();
-        let mut in_part = false;
-        let mut in_part_content = false;
-        let mut current_content_type = String::new();
-        let mut current_transfer_encoding = String::new();
-
-        for line in body.lines() {
-            let trimmed = line.trim();
-
-            // 检查是否是结束标记
-            if trimmed == end_marker {
-                // 保存最后一个部分
-                if in_part {
-                    let decoded = self.decode_part_content(&current_content, &current_transfer_encoding, &current_content_type);
-                    if current_content_type.to_lowercase().contains("text/plain") && text_plain_content.is_empty() {
-                        text_plain_content = decoded;
-                    } else if current_content_type.to_lowercase().contains("text/html") && text_html_content.is_empty() {
-                        text_html_content = decoded;
-                    } else if current_content_type.to_lowercase().contains("multipart/") {
-                        // 处理嵌套 multipart
-                        if let Some(nested_boundary) = self.extract_boundary(&current_content_type) {
-                            let (nested_plain, nested_html) =
-                                self.extract_multipart_body(&decoded, &nested_boundary);
-                            if text_plain_content.is_empty() { text_plain_content = nested_plain; }
-                            if text_html_content.is_empty() { text_html_content = nested_html; }
-                        }
-                    }
-                }
-                break;
-            }
+fn structure_disposition_is(structure: &BodyStructure<'_>, ty: &str) -> bool {
+    let common = match structure {
+        BodyStructure::Basic { common, .. }
+        | BodyStructure::Text { common, .. }
+        | BodyStructure::Message { common, .. }
+        | BodyStructure::Multipart { common, .. } => common,
+    };
+    common.disposition.as_ref().is_some_and(|d| d.ty.eq_ignore_ascii_case(ty))
+}
 
-            // 检查是否是部分分隔符
-            if trimmed == delimiter {
-                // 保存之前部分的内容
-                if in_part {
-                    let decoded = self.decode_part_content(&current_content, &current_transfer_encoding, &current_content_type);
-                    if current_content_type.to_lowercase().contains("text/plain") && text_plain_content.is_empty() {
-                        text_plain_content = decoded;
-                    } else if current_content_type.to_lowercase().contains("text/html") && text_html_content.is_empty() {
-                        text_html_content = decoded;
-                    } else if current_content_type.to_lowercase().contains("multipart/") {
-                        // 处理嵌套 multipart
-                        if let Some(nested_boundary) = self.extract_boundary(&current_content_type) {
-                            let (nested_plain, nested_html) =
-                                self.extract_multipart_body(&decoded, &nested_boundary);
-                            if text_plain_content.is_empty() { text_plain_content = nested_plain; }
-                            if text_html_content.is_empty() { text_html_content = nested_html; }
-                        }
-                    }
-                }
-                // 开始新部分
-                in_part = true;
-                in_part_content = false;
-                current_headers.clear();
-                current_content.clear();
-                current_content_type.clear();
-                current_transfer_encoding.clear();
-                continue;
-            }
+/// ENVELOPE 中的一个地址；host 为 NIL 的项是组的开始或结束标记，不是地址
+fn envelope_address(name: Option<&[u8]>, mailbox: Option<&[u8]>, host: Option<&[u8]>) -> Option<Address> {
+    let host = String::from_utf8_lossy(host?);
+    let mailbox = mailbox.map(String::from_utf8_lossy).unwrap_or_default();
+    let name = name
+        .map(|name| decode_rfc2047(&String::from_utf8_lossy(name)))
+        .filter(|name| !name.trim().is_empty());
+
+    Some(Address {
+        name,
+        email: format!("{}@{}", mailbox, host),
+    })
+}
 
-            if !in_part {
-                continue;
-            }
+/// 覆盖全部 UID 的范围（如 `10:42`）
+fn uid_range(uids: &[u32]) -> String {
+    let min = uids.iter().min().copied().unwrap_or(1);
+    let max = uids.iter().max().copied().unwrap_or(1);
+    format!("{}:{}", min, max)
+}
 
-            // 收集头部
-            if !in_part_content {
-                if trimmed.is_empty() {
-                    // 头部结束，解析头部信息（先处理头部折叠）
-                    let unfolded_headers = self.unfold_headers(&current_headers);
-                    let headers_lower = unfolded_headers.to_lowercase();
-                    // 提取 Content-Type
-                    if let Some(ct_start) = headers_lower.find("content-type:") {
-                        let ct_line = &unfolded_headers[ct_start..];
-                        if let Some(ct_end) = ct_line.find('\n') {
-                            current_content_type = ct_line[..ct_end].to_string();
-                        } else {
-                            current_content_type = ct_line.to_string();
-                        }
-                    }
-                    // 提取 Content-Transfer-Encoding
-                    if let Some(cte_start) = headers_lower.find("content-transfer-encoding:") {
-                        let cte_line = &unfolded_headers[cte_start..];
-                        if let Some(cte_end) = cte_line.find('\n') {
-                            current_transfer_encoding = cte_line[..cte_end].to_string();
-                        } else {
-                            current_transfer_encoding = cte_line.to_string();
-                        }
-                    }
-                    in_part_content = true;
-                } else {
-                    current_headers.push_str(line);
-                    current_headers.push('\n');
-                }
-            } else {
-                // 收集正文内容
-                current_content.push_str(line);
-                current_content.push('\n');
+/// SELECT 响应中的 HIGHESTMODSEQ，文件夹不支持 modseq 时为 0
+fn highest_modseq(responses: &[ImapResponse]) -> u64 {
+    responses
+        .iter()
+        .filter(|r| r.status().is_some())
+        .find_map(|r| match r.code.as_slice() {
+            [code, value] if code.as_atom().is_some_and(|c| c.eq_ignore_ascii_case("HIGHESTMODSEQ")) => {
+                value.as_number()
             }
-        }
+            _ => None,
+        })
+        .unwrap_or(0)
+}
 
-        // 返回 (plain_text, html_text) 元组
-        let plain = if !text_plain_content.is_empty() {
-            text_plain_content.trim().to_string()
-        } else if !text_html_content.is_empty() {
-            self.html_to_text(&text_html_content)
-        } else {
-            String::new()
-        };
+/// SEARCH 响应中的 UID
+fn search_uids(responses: &[ImapResponse]) -> Vec<u32> {
+    responses
+        .iter()
+        .filter(|r| r.kind().as_deref() == Some("SEARCH"))
+        .flat_map(|r| r.values.iter().skip(1).filter_map(ImapValue::as_number))
+        .map(|uid| uid as u32)
+        .collect()
+}
 
-        let html = text_html_content.trim().to_string();
-        (plain, html)
+/// 收集 FETCH 响应中的标志和 VANISHED 响应中的 UID
+fn collect_changes(responses: &[ImapResponse], changes: &mut FlagChanges) -> Result<(), String> {
+    for response in responses {
+        if response.kind().as_deref() == Some("VANISHED") {
+            if let Some(set) = response.values.last().and_then(ImapValue::as_atom) {
+                changes.vanished.extend(parse_uid_set(set)?);
+            }
+        } else if let Some(fetch) = FetchData::from_response(response) {
+            if let Some(uid) = fetch.uid() {
+                changes.flags.insert(uid, fetch.flags());
+            }
+        }
     }
+    Ok(())
+}
 
-    /// 解码邮件部分内容
-    fn decode_part_content(&self, content: &str, transfer_encoding: &str, content_type: &str) -> String {
-        let encoding_lower = transfer_encoding.to_lowercase();
-        let charset = self.extract_charset(content_type);
-
-        let decoded = if encoding_lower.contains("base64") {
-            self.decode_base64_with_charset(content, &charset)
-        } else if encoding_lower.contains("quoted-printable") {
-            self.decode_quoted_printable_with_charset(content, &charset)
-        } else {
-            self.try_decode_with_charset(content.as_bytes(), &charset)
-        };
-        decoded.trim().to_string()
+/// 测试用的 IMAP 替身服务器，其他服务（如规则引擎）的测试也用它执行邮件操作
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone)]
+    pub(crate) struct MockMessage {
+        pub(crate) uid: u32,
+        pub(crate) flags: Vec<String>,
+        raw: String,
+        /// BODYSTRUCTURE 中是否带一个 PDF 附件
+        attachment: bool,
     }
 
-    /// 从 Content-Type 中提取字符集
-    fn extract_charset(&self, content_type: &str) -> String {
-        let lower = content_type.to_lowercase();
-        if let Some(charset_pos) = lower.find("charset=") {
-            let charset_str = &content_type[charset_pos + 8..];
-            let charset = charset_str
-                .split(&[';', ' ', '"', '\''][..])
-                .next()
-                .unwrap_or("utf-8")
-                .trim()
-                .to_lowercase();
-            return charset;
+    impl MockMessage {
+        pub(crate) fn new(uid: u32) -> Self {
+            let raw = format!(
+                "From: Alice <alice@example.com>\r\nSubject: Message {}\r\nDate: Mon, 01 Jan 2024 10:00:00 +0000\r\n\r\nBody {}\r\n",
+                uid, uid
+            );
+            Self { uid, flags: Vec::new(), raw, attachment: false }
         }
-        "utf-8".to_string()
-    }
-
-    /// 尝试使用指定字符集解码字节
-    fn try_decode_with_charset(&self, bytes: &[u8], charset: &str) -> String {
-        let charset_lower = charset.to_lowercase();
 
-        // 首先尝试 UTF-8
-        if charset_lower.contains("utf-8") || charset_lower.contains("utf8") {
-            if let Ok(s) = String::from_utf8(bytes.to_vec()) {
-                return s;
+        fn bodystructure(&self) -> String {
+            let text = format!("(\"TEXT\" \"PLAIN\" (\"CHARSET\" \"UTF-8\") NIL NIL \"7BIT\" {} 1)", self.body().len());
+            if self.attachment {
+                format!(
+                    "({}(\"APPLICATION\" \"PDF\" (\"NAME\" \"a.pdf\") NIL NIL \"BASE64\" 8 NIL (\"ATTACHMENT\" (\"FILENAME\" \"a.pdf\")) NIL) \"MIXED\")",
+                    text
+                )
+            } else {
+                text
             }
         }
 
-        // 尝试 GBK/GB2312
-        if charset_lower.contains("gbk") || charset_lower.contains("gb2312") || charset_lower.contains("gb18030") {
-            let (decoded, _encoding, _had_errors) = encoding_rs::GBK.decode(bytes);
-            return decoded.to_string();
+        fn header(&self) -> &str {
+            &self.raw[..self.raw.find("\r\n\r\n").unwrap() + 4]
         }
 
-        // 尝试 Big5
-        if charset_lower.contains("big5") {
-            let (decoded, _encoding, _had_errors) = encoding_rs::BIG5.decode(bytes);
-            return decoded.to_string();
+        fn body(&self) -> &str {
+            &self.raw[self.raw.find("\r\n\r\n").unwrap() + 4..]
         }
+    }
 
-        // 尝试 ISO-8859-1
-        if charset_lower.contains("iso-8859-1") || charset_lower.contains("latin1") {
-            let (decoded, _encoding, _had_errors) = encoding_rs::WINDOWS_1252.decode(bytes);
-            return decoded.to_string();
-        }
+    #[derive(Default)]
+    pub(crate) struct MockState {
+        pub(crate) folders: HashMap<String, Vec<MockMessage>>,
+        pub(crate) uid_next: u32,
+        /// SELECT 返回的 UIDVALIDITY，修改它模拟文件夹在服务器上被重建
+        pub(crate) uid_validity: u32,
+        /// 模拟网易邮箱：未发送 ID 时拒绝 SELECT
+        require_id: bool,
+        /// 执行完该命令后不回复直接断开连接（只生效一次），模拟命令已执行但连接中断
+        disconnect_after: Option<&'static str>,
+        /// 声明 CONDSTORE，SELECT 时返回 HIGHESTMODSEQ
+        condstore: bool,
+        /// 成功登录的次数，即建立的连接数
+        pub(crate) logins: usize,
+        /// 收到的 SEARCH 命令数
+        pub(crate) searches: usize,
+        /// SEARCH 延迟回复，模拟较慢的服务器
+        pub(crate) search_delay: Option<std::time::Duration>,
+        /// 收到的同步字面量内容
+        literals: Vec<String>,
+        /// 接下来拒绝的登录次数，模拟服务器暂时不可用
+        pub(crate) fail_logins: usize,
+        /// 收到的 NOOP 和 LOGOUT 命令数
+        pub(crate) noops: usize,
+        pub(crate) logouts: usize,
+    }
 
-        // 默认：先尝试 UTF-8，失败则尝试 GBK
-        if let Ok(s) = String::from_utf8(bytes.to_vec()) {
-            return s;
-        }
+    pub(crate) type Mailboxes = Arc<Mutex<MockState>>;
 
-        // 尝试 GBK 作为后备
-        let (decoded, _, _) = encoding_rs::GBK.decode(bytes);
-        decoded.to_string()
-    }
+    /// 本地的 IMAP 替身服务器：UID 与序号不一致（UID 为 10、20、30…），
+    /// 用序号代替 UID 的命令会命中错误的邮件或找不到邮件
+    pub(crate) fn spawn_server(state: Mailboxes) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
 
-    /// 解码 Base64 编码的内容（支持字符集）
-    fn decode_base64_with_charset(&self, content: &str, charset: &str) -> String {
-        let cleaned: String = content.chars()
-            .filter(|c| !c.is_whitespace())
-            .collect();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                let state = Arc::clone(&state);
+                std::thread::spawn(move || serve(stream, state));
+            }
+        });
 
-        match base64::decode(&cleaned) {
-            Ok(bytes) => self.try_decode_with_charset(&bytes, charset),
-            Err(_) => content.to_string()
-        }
+        port
     }
 
-    /// 解码 Quoted-Printable 编码的内容（支持字符集）
-    fn decode_quoted_printable_with_charset(&self, content: &str, charset: &str) -> String {
-        let mut bytes: Vec<u8> = Vec::new();
-        let mut chars = content.chars().peekable();
+    /// 解析序列集合（如 "2,4:*"），返回邮件下标
+    fn resolve(set: &str, messages: &[MockMessage], by_uid: bool) -> Vec<usize> {
+        let key = |i: usize| if by_uid { messages[i].uid } else { i as u32 + 1 };
+        let max = if messages.is_empty() { 0 } else { key(messages.len() - 1) };
+        let parse = |v: &str| if v == "*" { max } else { v.parse().unwrap() };
+
+        let mut found = Vec::new();
+        for part in set.split(',') {
+            let (a, b) = match part.split_once(':') {
+                Some((a, b)) => (parse(a), parse(b)),
+                None => (parse(part), parse(part)),
+            };
+            let (low, high) = (a.min(b), a.max(b));
+            for i in 0..messages.len() {
+                if (low..=high).contains(&key(i)) && !found.contains(&i) {
+                    found.push(i);
+                }
+            }
+        }
+        found
+    }
 
-        while let Some(ch) = chars.next() {
-            if ch == '=' {
-                if let Some(&next) = chars.peek() {
-                    if next == '\n' || next == '\r' {
-                        // 软换行，跳过
-                        chars.next();
-                        if chars.peek() == Some(&'\n') {
-                            chars.next();
-                        }
-                        continue;
+    /// 按 SEARCH 条件筛选邮件，支持 ALL、NOT、OR、括号、UID 和 SUBJECT
+    fn search(args: &str, messages: &[MockMessage]) -> Vec<usize> {
+        let mut tokens = Vec::new();
+        let mut chars = args.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                ' ' => {}
+                '(' | ')' => tokens.push(c.to_string()),
+                '"' => tokens.push(chars.by_ref().take_while(|c| *c != '"').collect()),
+                _ => {
+                    let mut token = c.to_string();
+                    while let Some(c) = chars.next_if(|c| !matches!(c, ' ' | '(' | ')')) {
+                        token.push(c);
                     }
+                    tokens.push(token);
+                }
+            }
+        }
+        if tokens.first().is_some_and(|t| t == "CHARSET") {
+            tokens.drain(..2);
+        }
 
-                    // 解码 hex
-                    let hex: String = chars.by_ref().take(2).collect();
-                    if let Ok(byte) = u8::from_str_radix(&hex, 16) {
-                        bytes.push(byte);
+        fn key(tokens: &[String], pos: &mut usize, messages: &[MockMessage], i: usize) -> bool {
+            let token = tokens[*pos].to_ascii_uppercase();
+            *pos += 1;
+            match token.as_str() {
+                "ALL" => true,
+                "NOT" => !key(tokens, pos, messages, i),
+                "OR" => {
+                    let a = key(tokens, pos, messages, i);
+                    key(tokens, pos, messages, i) || a
+                }
+                "(" => {
+                    let mut all = true;
+                    while tokens[*pos] != ")" {
+                        all &= key(tokens, pos, messages, i);
+                    }
+                    *pos += 1;
+                    all
+                }
+                "UID" | "SUBJECT" => {
+                    let value = &tokens[*pos];
+                    *pos += 1;
+                    if token == "UID" {
+                        resolve(value, messages, true).contains(&i)
                     } else {
-                        bytes.push(b'=');
-                        for c in hex.chars() {
-                            bytes.push(c as u8);
-                        }
+                        messages[i].raw.contains(value.as_str())
                     }
-                } else {
-                    bytes.push(b'=');
                 }
-            } else {
-                bytes.push(ch as u8);
+                other => panic!("unsupported search key {}", other),
             }
         }
 
-        self.try_decode_with_charset(&bytes, charset)
+        (0..messages.len())
+            .filter(|i| {
+                let mut pos = 0;
+                let mut all = true;
+                while pos < tokens.len() {
+                    all &= key(&tokens, &mut pos, messages, *i);
+                }
+                all
+            })
+            .collect()
     }
 
-    /// 将 HTML 转换为纯文本（移除标签，保留内容）
-    fn html_to_text(&self, html: &str) -> String {
-        let mut result = String::new();
-        let mut in_tag = false;
-        let mut in_script = false;
-        let mut in_style = false;
-        let mut tag_name = String::new();
-        let mut chars = html.chars().peekable();
+    fn serve(stream: TcpStream, state: Mailboxes) {
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+        writer.write_all(b"* OK IMAP4rev1 stand-in ready\r\n").unwrap();
+        let mut selected: Option<String> = None;
+        let mut identified = false;
 
-        while let Some(ch) = chars.next() {
-            if ch == '<' {
-                in_tag = true;
-                tag_name.clear();
-                continue;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                return;
             }
+            // 把命令中的字面量接回命令行，同步字面量先回复继续请求
+            while let Some(open) = line.trim_end().strip_suffix('}').and_then(|l| l.rfind('{')) {
+                let marker = line.trim_end()[open + 1..].trim_end_matches('}').to_string();
+                let sync = !marker.ends_with('+');
+                let Ok(length) = marker.trim_end_matches('+').parse::<usize>() else { break };
+                if sync {
+                    writer.write_all(b"+ Ready\r\n").unwrap();
+                }
+                let mut data = vec![0; length];
+                reader.read_exact(&mut data).unwrap();
+                let data = String::from_utf8(data).unwrap();
+                if sync {
+                    state.lock().unwrap().literals.push(data.clone());
+                }
+                line.truncate(open);
+                line.push_str(&data);
+                reader.read_line(&mut line).unwrap();
+            }
+            let line = line.trim_end();
+            let (tag, rest) = line.split_once(' ').unwrap();
+            let (mut command, mut args) = rest.split_once(' ').unwrap_or((rest, ""));
+            let by_uid = command.eq_ignore_ascii_case("UID");
+            if by_uid {
+                (command, args) = args.split_once(' ').unwrap_or((args, ""));
+            }
+            let command = command.to_ascii_uppercase();
 
-            if in_tag {
-                if ch == '>' {
-                    in_tag = false;
-                    let tag_lower = tag_name.to_lowercase();
-
-                    // 检测 script 和 style 标签
-                    if tag_lower.starts_with("script") || tag_lower.starts_with("/script") {
-                        in_script = !tag_lower.starts_with("/");
+            if command == "SEARCH" {
+                let delay = state.lock().unwrap().search_delay;
+                if let Some(delay) = delay {
+                    std::thread::sleep(delay);
+                }
+            }
+            let mut state = state.lock().unwrap();
+            let mut out = String::new();
+            let status = match command.as_str() {
+                "CAPABILITY" => {
+                    let condstore = if state.condstore { " CONDSTORE" } else { "" };
+                    out.push_str(&format!("* CAPABILITY IMAP4rev1 UIDPLUS{}\r\n", condstore));
+                    "OK CAPABILITY completed"
+                }
+                "LOGIN" if state.fail_logins > 0 => {
+                    state.fail_logins -= 1;
+                    "NO LOGIN temporarily unavailable"
+                }
+                "LOGIN" => {
+                    if args.ends_with("\"secret\"") {
+                        state.logins += 1;
+                        "OK LOGIN completed"
+                    } else {
+                        "NO LOGIN failed"
                     }
-                    if tag_lower.starts_with("style") || tag_lower.starts_with("/style") {
-                        in_style = !tag_lower.starts_with("/");
+                }
+                "NOOP" => {
+                    state.noops += 1;
+                    "OK NOOP completed"
+                }
+                "ID" => {
+                    identified = true;
+                    out.push_str("* ID (\"name\" \"stand-in\")\r\n");
+                    "OK ID completed"
+                }
+                "LOGOUT" => {
+                    state.logouts += 1;
+                    let _ = writer.write_all(format!("* BYE\r\n{} OK LOGOUT completed\r\n", tag).as_bytes());
+                    return;
+                }
+                "SELECT" if state.require_id && !identified => {
+                    "NO SELECT Unsafe Login. Please contact kefu@188.com for help"
+                }
+                "SELECT" => {
+                    // 可能带有 (CONDSTORE) 等参数
+                    let name = args.split(" (").next().unwrap().trim_matches('"').to_string();
+                    match state.folders.get(&name) {
+                        Some(messages) => {
+                            out.push_str(&format!("* {} EXISTS\r\n* 0 RECENT\r\n", messages.len()));
+                            out.push_str("* FLAGS (\\Seen \\Deleted \\Flagged)\r\n");
+                            out.push_str(&format!("* OK [UIDVALIDITY {}] UIDs valid\r\n", state.uid_validity));
+                            out.push_str(&format!("* OK [UIDNEXT {}] Predicted next UID\r\n", state.uid_next));
+                            if state.condstore {
+                                out.push_str("* OK [HIGHESTMODSEQ 5] Highest\r\n");
+                            }
+                            selected = Some(name);
+                            "OK [READ-WRITE] SELECT completed"
+                        }
+                        None => "NO Mailbox does not exist",
                     }
-
-                    // 块级元素添加换行
-                    if tag_lower.starts_with("/div") ||
-                       tag_lower.starts_with("/p") ||
-                       tag_lower.starts_with("/h") ||
-                       tag_lower.starts_with("br") {
-                        if !result.ends_with('\n') {
-                            result.push(' ');
+                }
+                _ => {
+                    let folder = selected.clone().unwrap();
+                    match command.as_str() {
+                        "SEARCH" => {
+                            state.searches += 1;
+                            let messages = &state.folders[&folder];
+                            let indexes = search(args, messages);
+                            out.push_str("* SEARCH");
+                            for i in indexes {
+                                let id = if by_uid { messages[i].uid } else { i as u32 + 1 };
+                                out.push_str(&format!(" {}", id));
+                            }
+                            out.push_str("\r\n");
+                            "OK SEARCH completed"
+                        }
+                        "FETCH" => {
+                            let messages = state.folders.get_mut(&folder).unwrap();
+                            let (set, items) = args.split_once(' ').unwrap();
+                            for i in resolve(set, messages, by_uid) {
+                                // 与真实服务器一样，非 PEEK 的正文读取会设置 \Seen
+                                if items == "RFC822" && !messages[i].flags.iter().any(|f| f == "\\Seen") {
+                                    messages[i].flags.push("\\Seen".to_string());
+                                }
+                                let m = &messages[i];
+                                let attrs = if items.contains("ENVELOPE") {
+                                    format!(
+                                        "RFC822.SIZE {} FLAGS ({}) ENVELOPE (\"Mon, 01 Jan 2024 10:00:00 +0000\" \"Message {}\" ((\"Alice\" NIL \"alice\" \"example.com\")) ((\"Alice\" NIL \"alice\" \"example.com\")) ((\"Alice\" NIL \"alice\" \"example.com\")) NIL NIL NIL NIL \"<{}@example.com>\") BODYSTRUCTURE {}",
+                                        m.raw.len(), m.flags.join(" "), m.uid, m.uid, m.bodystructure()
+                                    )
+                                } else if items.contains("BODYSTRUCTURE") {
+                                    format!("BODYSTRUCTURE {}", m.bodystructure())
+                                } else if items == "(UID FLAGS)" || items.starts_with("(FLAGS)") {
+                                    format!("FLAGS ({})", m.flags.join(" "))
+                                } else if items.contains("BODY.PEEK[TEXT]") {
+                                    format!(
+                                        "BODY[HEADER] {{{}}}\r\n{} BODY[TEXT]<0> {{{}}}\r\n{}",
+                                        m.header().len(), m.header(), m.body().len(), m.body()
+                                    )
+                                } else if items == "BODY.PEEK[]" {
+                                    format!("BODY[] {{{}}}\r\n{}", m.raw.len(), m.raw)
+                                } else {
+                                    format!("RFC822 {{{}}}\r\n{}", m.raw.len(), m.raw)
+                                };
+                                out.push_str(&format!("* {} FETCH (UID {} {})\r\n", i + 1, m.uid, attrs));
+                            }
+                            "OK FETCH completed"
+                        }
+                        "STORE" => {
+                            let (set, change) = args.split_once(' ').unwrap();
+                            let flags: Vec<String> = change
+                                .trim_start_matches("+FLAGS ")
+                                .trim_matches(|c| c == '(' || c == ')')
+                                .split_whitespace()
+                                .map(str::to_string)
+                                .collect();
+                            let messages = state.folders.get_mut(&folder).unwrap();
+                            for i in resolve(set, messages, by_uid) {
+                                for flag in &flags {
+                                    if !messages[i].flags.contains(flag) {
+                                        messages[i].flags.push(flag.clone());
+                                    }
+                                }
+                                out.push_str(&format!(
+                                    "* {} FETCH (UID {} FLAGS ({}))\r\n",
+                                    i + 1, messages[i].uid, messages[i].flags.join(" ")
+                                ));
+                            }
+                            "OK STORE completed"
+                        }
+                        "COPY" => {
+                            let (set, dest) = args.split_once(' ').unwrap();
+                            let dest = dest.trim_matches('"').to_string();
+                            if !state.folders.contains_key(&dest) {
+                                "NO [TRYCREATE] Mailbox does not exist"
+                            } else {
+                                let copies: Vec<MockMessage> = resolve(set, &state.folders[&folder], by_uid)
+                                    .into_iter()
+                                    .map(|i| state.folders[&folder][i].clone())
+                                    .collect();
+                                for mut copy in copies {
+                                    copy.uid = state.uid_next;
+                                    state.uid_next += 1;
+                                    state.folders.get_mut(&dest).unwrap().push(copy);
+                                }
+                                "OK COPY completed"
+                            }
                         }
+                        "EXPUNGE" => {
+                            let messages = state.folders.get_mut(&folder).unwrap();
+                            let allowed = if by_uid { resolve(args, messages, true) } else { (0..messages.len()).collect() };
+                            let allowed: Vec<u32> = allowed.into_iter().map(|i| messages[i].uid).collect();
+                            let mut i = 0;
+                            while i < messages.len() {
+                                if messages[i].flags.iter().any(|f| f == "\\Deleted") && allowed.contains(&messages[i].uid) {
+                                    messages.remove(i);
+                                    out.push_str(&format!("* {} EXPUNGE\r\n", i + 1));
+                                } else {
+                                    i += 1;
+                                }
+                            }
+                            "OK EXPUNGE completed"
+                        }
+                        _ => "BAD Unknown command",
                     }
-                } else if ch.is_ascii_alphabetic() || ch == '/' {
-                    tag_name.push(ch);
                 }
-                continue;
-            }
+            };
 
-            // 跳过 script 和 style 内容
-            if in_script || in_style {
-                continue;
+            if state.disconnect_after == Some(command.as_str()) {
+                state.disconnect_after = None;
+                return;
             }
 
-            // 保留普通文本
-            if !in_tag {
-                result.push(ch);
-            }
+            out.push_str(&format!("{} {}\r\n", tag, status));
+            writer.write_all(out.as_bytes()).unwrap();
         }
-
-        // 清理多余空白
-        result.split_whitespace().collect::<Vec<_>>().join(" ")
     }
 
-    pub async fn mark_as_read(&self, folder: &str, uid: u32) -> Result<(), String> {
-        // 163邮箱使用特殊处理
-        if is_163_email(&self.account) {
-            return self.mark_as_read_163(folder, uid).await;
-        }
-
-        let mut client = self.connect().await?;
-
-        client
-            .select(format!("\"{}\"", folder))
-            .map_err(|e| format!("选择文件夹失败: {}", e))?;
-
-        client
-            .store(uid.to_string(), "+FLAGS (\\Seen)")
-            .map_err(|e| format!("标记已读失败: {}", e))?;
-
-        client.logout().map_err(|e| format!("登出失败: {}", e))?;
-        Ok(())
+    /// INBOX 中有指定 UID 的邮件，另有一个空的 Archive 文件夹
+    pub(crate) fn mailboxes(uids: &[u32]) -> Mailboxes {
+        let mut state = MockState { uid_validity: 7, ..MockState::default() };
+        state.folders.insert("INBOX".to_string(), uids.iter().map(|uid| MockMessage::new(*uid)).collect());
+        state.folders.insert("Archive".to_string(), Vec::new());
+        state.uid_next = uids.iter().max().copied().unwrap_or(0) + 1;
+        Arc::new(Mutex::new(state))
     }
 
-    /// 为163邮箱标记邮件已读
-    async fn mark_as_read_163(&self, folder: &str, uid: u32) -> Result<(), String> {
-        use native_tls::TlsConnector;
-        use std::io::{Read, Write};
+    pub(crate) fn service(port: u16) -> ImapService {
+        service_for("user@example.com", port)
+    }
 
-        // 建立TCP连接
-        let stream = TcpStream::connect((self.account.imap_server.as_str(), self.account.imap_port))
-            .map_err(|e| format!("TCP连接失败: {}", e))?;
+    fn service_for(email: &str, port: u16) -> ImapService {
+        ImapService::new(account(email, port), "secret".to_string())
+    }
 
-        // 创建TLS连接器
-        let tls = if cfg!(debug_assertions) {
-            TlsConnector::builder()
-                .danger_accept_invalid_certs(true)
-                .danger_accept_invalid_hostnames(true)
-                .build()
-        } else {
-            TlsConnector::builder().build()
-        }.map_err(|e| format!("TLS创建失败: {}", e))?;
-
-        // 升级到TLS
-        let mut tls_stream = tls.connect(&self.account.imap_server, stream)
-            .map_err(|e| format!("TLS连接失败: {}", e))?;
-
-        // 读取欢迎信息
-        let mut buffer = [0u8; 1024];
-        tls_stream.read(&mut buffer).map_err(|e| format!("读取欢迎信息失败: {}", e))?;
-
-        // 发送ID命令（163邮箱必需）
-        let id_cmd = b"A001 ID (\"name\" \"MailFlow\" \"version\" \"1.0\")\r\n";
-        tls_stream.write_all(id_cmd).map_err(|e| format!("发送ID命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        buffer = [0u8; 1024];
-        tls_stream.read(&mut buffer).map_err(|e| format!("读取ID响应失败: {}", e))?;
-
-        // 发送LOGIN命令
-        let login_cmd = format!("A002 LOGIN \"{}\" \"{}\"\r\n", self.account.email, self.password);
-        tls_stream.write_all(login_cmd.as_bytes()).map_err(|e| format!("发送LOGIN命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        let mut buffer2 = [0u8; 1024];
-        let n = tls_stream.read(&mut buffer2).map_err(|e| format!("读取LOGIN响应失败: {}", e))?;
-        let response = String::from_utf8_lossy(&buffer2[..n]);
-        if response.contains("NO") || response.contains("BAD") {
-            return Err(format!("163邮箱登录失败: {}", response));
+    /// 连接替身服务器的账户，密码为 "secret"
+    pub(crate) fn account(email: &str, port: u16) -> EmailAccount {
+        EmailAccount {
+            // 每个测试使用独立的账户，避免共用连接池中的会话
+            id: uuid::Uuid::new_v4().to_string(),
+            email: email.to_string(),
+            imap_server: "127.0.0.1".to_string(),
+            imap_port: port,
+            smtp_server: "127.0.0.1".to_string(),
+            smtp_port: 0,
+            name: "Test".to_string(),
+            is_default: false,
+            sieve_server: None,
+            sieve_port: None,
         }
+    }
 
-        // 选择文件夹
-        let select_cmd = format!("A003 SELECT \"{}\"\r\n", folder);
-        tls_stream.write_all(select_cmd.as_bytes()).map_err(|e| format!("发送SELECT命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        let mut buffer3 = [0u8; 2048];
-        tls_stream.read(&mut buffer3).map_err(|e| format!("读取SELECT响应失败: {}", e))?;
-
-        // 标记已读
-        let store_cmd = format!("A004 UID STORE {} +FLAGS (\\Seen)\r\n", uid);
-        tls_stream.write_all(store_cmd.as_bytes()).map_err(|e| format!("发送STORE命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        let mut buffer4 = [0u8; 1024];
-        tls_stream.read(&mut buffer4).map_err(|e| format!("读取STORE响应失败: {}", e))?;
-
-        // 发送LOGOUT
-        let _ = tls_stream.write_all(b"A005 LOGOUT\r\n");
-        let _ = tls_stream.flush();
-
-        Ok(())
+    pub(crate) fn inbox_uids(state: &Mailboxes) -> Vec<u32> {
+        state.lock().unwrap().folders["INBOX"].iter().map(|m| m.uid).collect()
     }
 
-    pub async fn delete_email(&self, folder: &str, uid: u32) -> Result<(), String> {
-        // 163邮箱使用特殊处理
-        if is_163_email(&self.account) {
-            return self.delete_email_163(folder, uid).await;
+    #[tokio::test]
+    async fn test_operations_use_uids_after_expunge() {
+        let state = mailboxes(&[10, 20, 30, 40]);
+        let service = service(spawn_server(Arc::clone(&state)));
+
+        state.lock().unwrap().folders.get_mut("INBOX").unwrap()[2].attachment = true;
+
+        let emails = service.fetch_emails("INBOX", 10, 0).await.unwrap();
+        let uids: Vec<u32> = emails.iter().map(|e| e.uid).collect();
+        assert_eq!(uids, vec![40, 30, 20, 10]);
+        let with_attachment: Vec<u32> = emails.iter().filter(|e| e.has_attachment).map(|e| e.uid).collect();
+        assert_eq!(with_attachment, vec![30]);
+        assert_eq!(emails[1].subject, "Message 30");
+        assert_eq!(emails[1].from, "Alice");
+        assert_eq!(emails[1].addresses.reply_to[0].email, "alice@example.com");
+        assert!(emails[1].addresses.to.is_empty());
+
+        // 删除后其余邮件的序号前移，UID 不变
+        service.delete_email("INBOX", 10).await.unwrap();
+        assert_eq!(inbox_uids(&state), vec![20, 30, 40]);
+
+        service.mark_as_read("INBOX", 30).await.unwrap();
+        {
+            let state = state.lock().unwrap();
+            let seen: Vec<u32> = state.folders["INBOX"]
+                .iter()
+                .filter(|m| m.flags.iter().any(|f| f == "\\Seen"))
+                .map(|m| m.uid)
+                .collect();
+            assert_eq!(seen, vec![30]);
         }
 
-        let mut client = self.connect().await?;
-
-        client
-            .select(format!("\"{}\"", folder))
-            .map_err(|e| format!("选择文件夹失败: {}", e))?;
+        let detail = service.fetch_email_detail("INBOX", 40).await.unwrap();
+        assert_eq!(detail.uid, 40);
+        assert_eq!(detail.subject, "Message 40");
 
-        client
-            .store(uid.to_string(), "+FLAGS (\\Deleted)")
-            .map_err(|e| format!("删除邮件失败: {}", e))?;
+        let new_emails = service.fetch_new_emails("INBOX", 30, 10).await.unwrap();
+        let uids: Vec<u32> = new_emails.iter().map(|e| e.uid).collect();
+        assert_eq!(uids, vec![40]);
 
-        client.expunge().map_err(|e| format!("清理失败: {}", e))?;
+        // 没有比 last_uid 更新的邮件时 "41:*" 仍会匹配 UID 40，应被过滤掉
+        assert!(service.fetch_new_emails("INBOX", 40, 10).await.unwrap().is_empty());
 
-        client.logout().map_err(|e| format!("登出失败: {}", e))?;
-        Ok(())
+        service.move_email("INBOX", 20, "Archive").await.unwrap();
+        assert_eq!(inbox_uids(&state), vec![30, 40]);
+        let state = state.lock().unwrap();
+        assert_eq!(state.folders["Archive"].len(), 1);
+        assert!(state.folders["Archive"][0].raw.contains("Subject: Message 20"));
     }
 
-    /// 为163邮箱删除邮件
-    async fn delete_email_163(&self, folder: &str, uid: u32) -> Result<(), String> {
-        use native_tls::TlsConnector;
-        use std::io::{Read, Write};
+    #[tokio::test]
+    async fn test_expunge_keeps_messages_deleted_by_other_clients() {
+        let state = mailboxes(&[5, 6, 7]);
+        state.lock().unwrap().folders.get_mut("INBOX").unwrap()[0]
+            .flags
+            .push("\\Deleted".to_string());
+        let service = service(spawn_server(Arc::clone(&state)));
 
-        // 建立TCP连接
-        let stream = TcpStream::connect((self.account.imap_server.as_str(), self.account.imap_port))
-            .map_err(|e| format!("TCP连接失败: {}", e))?;
+        service.delete_email("INBOX", 7).await.unwrap();
 
-        // 创建TLS连接器
-        let tls = if cfg!(debug_assertions) {
-            TlsConnector::builder()
-                .danger_accept_invalid_certs(true)
-                .danger_accept_invalid_hostnames(true)
-                .build()
-        } else {
-            TlsConnector::builder().build()
-        }.map_err(|e| format!("TLS创建失败: {}", e))?;
-
-        // 升级到TLS
-        let mut tls_stream = tls.connect(&self.account.imap_server, stream)
-            .map_err(|e| format!("TLS连接失败: {}", e))?;
-
-        // 读取欢迎信息
-        let mut buffer = [0u8; 1024];
-        tls_stream.read(&mut buffer).map_err(|e| format!("读取欢迎信息失败: {}", e))?;
-
-        // 发送ID命令
-        let id_cmd = b"A001 ID (\"name\" \"MailFlow\" \"version\" \"1.0\")\r\n";
-        tls_stream.write_all(id_cmd).map_err(|e| format!("发送ID命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        buffer = [0u8; 1024];
-        tls_stream.read(&mut buffer).map_err(|e| format!("读取ID响应失败: {}", e))?;
-
-        // 发送LOGIN命令
-        let login_cmd = format!("A002 LOGIN \"{}\" \"{}\"\r\n", self.account.email, self.password);
-        tls_stream.write_all(login_cmd.as_bytes()).map_err(|e| format!("发送LOGIN命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        let mut buffer2 = [0u8; 1024];
-        let n = tls_stream.read(&mut buffer2).map_err(|e| format!("读取LOGIN响应失败: {}", e))?;
-        let response = String::from_utf8_lossy(&buffer2[..n]);
-        if response.contains("NO") || response.contains("BAD") {
-            return Err(format!("163邮箱登录失败: {}", response));
-        }
+        // 服务器支持 UIDPLUS，只清除 UID 7；另一客户端标记删除的 UID 5 保留
+        assert_eq!(inbox_uids(&state), vec![5, 6]);
+    }
 
-        // 选择文件夹
-        let select_cmd = format!("A003 SELECT \"{}\"\r\n", folder);
-        tls_stream.write_all(select_cmd.as_bytes()).map_err(|e| format!("发送SELECT命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
+    #[tokio::test]
+    async fn test_move_and_delete_are_not_retried() {
+        let state = mailboxes(&[10, 20, 30]);
+        let service = service(spawn_server(Arc::clone(&state)));
+        // 先执行一次操作，让连接池中有可复用的会话
+        service.fetch_emails("INBOX", 10, 0).await.unwrap();
+
+        // COPY 已在服务器执行但连接中断：不能换新连接再复制一次
+        state.lock().unwrap().disconnect_after = Some("COPY");
+        assert!(service.move_email("INBOX", 10, "Archive").await.is_err());
+        assert_eq!(state.lock().unwrap().folders["Archive"].len(), 1);
+        assert_eq!(inbox_uids(&state), vec![10, 20, 30]);
+
+        // 池中的会话已断开时，操作开始前换用新连接
+        service.fetch_emails("INBOX", 10, 0).await.unwrap();
+        state.lock().unwrap().disconnect_after = Some("NOOP");
+        service.delete_email("INBOX", 20).await.unwrap();
+        assert_eq!(inbox_uids(&state), vec![10, 30]);
+    }
 
-        let mut buffer3 = [0u8; 2048];
-        tls_stream.read(&mut buffer3).map_err(|e| format!("读取SELECT响应失败: {}", e))?;
+    #[tokio::test]
+    async fn test_move_to_non_ascii_folder() {
+        let state = mailboxes(&[10, 20]);
+        state.lock().unwrap().folders.insert("&X1JoYw-".to_string(), Vec::new());
+        let service = service(spawn_server(Arc::clone(&state)));
+
+        // 手动填写的中文名称和 LIST 返回的编码名称指向同一个文件夹
+        service.move_email("INBOX", 10, "归档").await.unwrap();
+        service.move_email("INBOX", 20, "&X1JoYw-").await.unwrap();
+        assert!(inbox_uids(&state).is_empty());
+        assert_eq!(state.lock().unwrap().folders["&X1JoYw-"].len(), 2);
+    }
 
-        // 标记删除
-        let store_cmd = format!("A004 UID STORE {} +FLAGS (\\Deleted)\r\n", uid);
-        tls_stream.write_all(store_cmd.as_bytes()).map_err(|e| format!("发送STORE命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
+    #[tokio::test]
+    async fn test_netease_account_sends_id_before_select() {
+        let state = mailboxes(&[1, 2]);
+        state.lock().unwrap().require_id = true;
+        let port = spawn_server(Arc::clone(&state));
 
-        let mut buffer4 = [0u8; 1024];
-        tls_stream.read(&mut buffer4).map_err(|e| format!("读取STORE响应失败: {}", e))?;
+        let emails = service_for("user@163.com", port).fetch_emails("INBOX", 10, 0).await.unwrap();
+        assert_eq!(emails.len(), 2);
 
-        // EXPUNGE
-        let expunge_cmd = b"A005 EXPUNGE\r\n";
-        tls_stream.write_all(expunge_cmd).map_err(|e| format!("发送EXPUNGE命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
+        // 其他服务商不发送 ID
+        let result = service(port).get_folder_status("INBOX").await;
+        assert!(result.err().unwrap().contains("Unsafe Login"));
+    }
 
-        let mut buffer5 = [0u8; 1024];
-        tls_stream.read(&mut buffer5).map_err(|e| format!("读取EXPUNGE响应失败: {}", e))?;
+    #[tokio::test]
+    async fn test_flag_resync_without_condstore() {
+        let state = mailboxes(&[10, 20, 30]);
+        let service = service(spawn_server(Arc::clone(&state)));
+
+        // 另一客户端标记了 UID 20 已读、UID 30 星标，并删除了 UID 10
+        {
+            let mut state = state.lock().unwrap();
+            let inbox = state.folders.get_mut("INBOX").unwrap();
+            inbox[1].flags.push("\\Seen".to_string());
+            inbox[2].flags.push("\\Flagged".to_string());
+            inbox.remove(0);
+        }
 
-        // 发送LOGOUT
-        let _ = tls_stream.write_all(b"A006 LOGOUT\r\n");
-        let _ = tls_stream.flush();
+        let changes = service.fetch_flag_changes("INBOX", 7, 0, &[10, 20, 30]).await.unwrap();
+        assert_eq!(changes.highest_modseq, 0);
+        assert_eq!(changes.vanished, vec![10]);
+        assert_eq!(changes.flags[&20], vec!["\\Seen".to_string()]);
+        assert_eq!(changes.flags[&30], vec!["\\Flagged".to_string()]);
 
-        Ok(())
+        assert!(service.fetch_flag_changes("INBOX", 7, 0, &[]).await.unwrap().flags.is_empty());
     }
 
-    pub async fn move_email(&self, folder: &str, uid: u32, dest_folder: &str) -> Result<(), String> {
-        // 163邮箱使用特殊处理
-        if is_163_email(&self.account) {
-            return self.move_email_163(folder, uid, dest_folder).await;
+    #[tokio::test]
+    async fn test_condstore_flag_sync_reuses_connection() {
+        let state = mailboxes(&[10, 20, 30]);
+        state.lock().unwrap().condstore = true;
+        let service = service(spawn_server(Arc::clone(&state)));
+
+        {
+            let mut state = state.lock().unwrap();
+            let inbox = state.folders.get_mut("INBOX").unwrap();
+            inbox[1].flags.push("\\Seen".to_string());
+            inbox.remove(0);
         }
 
-        let mut client = self.connect().await?;
+        let changes = service.fetch_flag_changes("INBOX", 7, 0, &[10, 20, 30]).await.unwrap();
+        assert_eq!(changes.highest_modseq, 5);
+        assert_eq!(changes.vanished, vec![10]);
+        assert_eq!(changes.flags[&20], vec!["\\Seen".to_string()]);
+
+        // 之后的同步复用连接池中的会话和直接收发命令的连接，不再重新登录
+        let logins = state.lock().unwrap().logins;
+        assert_eq!(logins, 2);
+        for _ in 0..3 {
+            let changes = service.fetch_flag_changes("INBOX", 7, 5, &[20, 30]).await.unwrap();
+            assert!(changes.vanished.is_empty());
+        }
+        assert_eq!(state.lock().unwrap().logins, logins);
+    }
 
-        client
-            .select(format!("\"{}\"", folder))
-            .map_err(|e| format!("选择文件夹失败: {}", e))?;
+    #[tokio::test]
+    async fn test_older_pages_walk_back_to_oldest() {
+        let state = mailboxes(&[3, 8, 15, 21, 40]);
+        let service = service(spawn_server(Arc::clone(&state)));
 
-        client
-            .copy(uid.to_string(), format!("\"{}\"", dest_folder))
-            .map_err(|e| format!("复制邮件失败: {}", e))?;
+        let first: Vec<u32> = service.fetch_emails("INBOX", 2, 0).await.unwrap().iter().map(|e| e.uid).collect();
+        assert_eq!(first, vec![40, 21]);
 
-        client
-            .store(uid.to_string(), "+FLAGS (\\Deleted)")
-            .map_err(|e| format!("标记删除失败: {}", e))?;
+        // 翻页期间到达的新邮件不影响更早的页面
+        state.lock().unwrap().folders.get_mut("INBOX").unwrap().push(MockMessage::new(50));
 
-        client.expunge().map_err(|e| format!("清理失败: {}", e))?;
+        let (uid_validity, second) = service.fetch_older_emails("INBOX", 21, 2).await.unwrap();
+        assert_eq!(uid_validity, 7);
+        assert_eq!(second.iter().map(|e| e.uid).collect::<Vec<_>>(), vec![15, 8]);
 
-        client.logout().map_err(|e| format!("登出失败: {}", e))?;
-        Ok(())
+        let (_, last) = service.fetch_older_emails("INBOX", 8, 2).await.unwrap();
+        assert_eq!(last.iter().map(|e| e.uid).collect::<Vec<_>>(), vec![3]);
+        assert!(service.fetch_older_emails("INBOX", 3, 2).await.unwrap().1.is_empty());
     }
 
-    /// 为163邮箱移动邮件
-    async fn move_email_163(&self, folder: &str, uid: u32, dest_folder: &str) -> Result<(), String> {
-        use native_tls::TlsConnector;
-        use std::io::{Read, Write};
-
-        // 建立TCP连接
-        let stream = TcpStream::connect((self.account.imap_server.as_str(), self.account.imap_port))
-            .map_err(|e| format!("TCP连接失败: {}", e))?;
+    #[tokio::test]
+    async fn test_non_ascii_search_uses_synchronizing_literal() {
+        let state = mailboxes(&[10, 20, 30]);
+        let service = service(spawn_server(Arc::clone(&state)));
+        let raw = "From: Alice <alice@example.com>\r\nSubject: 周报\r\nDate: Mon, 01 Jan 2024 10:00:00 +0000\r\n\r\nBody 20\r\n";
+        state.lock().unwrap().folders.get_mut("INBOX").unwrap()[1].raw = raw.to_string();
+
+        // 服务器不支持 LITERAL+，等继续请求后再发送字面量
+        let query = SearchQuery::parse("subject:周报").unwrap();
+        let emails = service.query_emails("INBOX", &query, 10).await.unwrap();
+        assert_eq!(emails.iter().map(|e| e.uid).collect::<Vec<_>>(), vec![20]);
+        assert_eq!(state.lock().unwrap().literals, vec!["周报".to_string()]);
+
+        let query = SearchQuery::parse("subject:\"Message 30\"").unwrap();
+        let emails = service.query_emails("INBOX", &query, 10).await.unwrap();
+        assert_eq!(emails.iter().map(|e| e.uid).collect::<Vec<_>>(), vec![30]);
+        assert_eq!(state.lock().unwrap().literals.len(), 1);
+    }
 
-        // 创建TLS连接器
-        let tls = if cfg!(debug_assertions) {
-            TlsConnector::builder()
-                .danger_accept_invalid_certs(true)
-                .danger_accept_invalid_hostnames(true)
-                .build()
-        } else {
-            TlsConnector::builder().build()
-        }.map_err(|e| format!("TLS创建失败: {}", e))?;
-
-        // 升级到TLS
-        let mut tls_stream = tls.connect(&self.account.imap_server, stream)
-            .map_err(|e| format!("TLS连接失败: {}", e))?;
-
-        // 读取欢迎信息
-        let mut buffer = [0u8; 1024];
-        tls_stream.read(&mut buffer).map_err(|e| format!("读取欢迎信息失败: {}", e))?;
-
-        // 发送ID命令
-        let id_cmd = b"A001 ID (\"name\" \"MailFlow\" \"version\" \"1.0\")\r\n";
-        tls_stream.write_all(id_cmd).map_err(|e| format!("发送ID命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        buffer = [0u8; 1024];
-        tls_stream.read(&mut buffer).map_err(|e| format!("读取ID响应失败: {}", e))?;
-
-        // 发送LOGIN命令
-        let login_cmd = format!("A002 LOGIN \"{}\" \"{}\"\r\n", self.account.email, self.password);
-        tls_stream.write_all(login_cmd.as_bytes()).map_err(|e| format!("发送LOGIN命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        let mut buffer2 = [0u8; 1024];
-        let n = tls_stream.read(&mut buffer2).map_err(|e| format!("读取LOGIN响应失败: {}", e))?;
-        let response = String::from_utf8_lossy(&buffer2[..n]);
-        if response.contains("NO") || response.contains("BAD") {
-            return Err(format!("163邮箱登录失败: {}", response));
+    #[tokio::test]
+    async fn test_attachment_search_uses_bodystructure() {
+        let state = mailboxes(&[10, 20, 30, 40]);
+        let service = service(spawn_server(Arc::clone(&state)));
+        {
+            let mut state = state.lock().unwrap();
+            let inbox = state.folders.get_mut("INBOX").unwrap();
+            inbox[1].attachment = true;
+            inbox[3].attachment = true;
         }
 
-        // 选择文件夹
-        let select_cmd = format!("A003 SELECT \"{}\"\r\n", folder);
-        tls_stream.write_all(select_cmd.as_bytes()).map_err(|e| format!("发送SELECT命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
-
-        let mut buffer3 = [0u8; 2048];
-        tls_stream.read(&mut buffer3).map_err(|e| format!("读取SELECT响应失败: {}", e))?;
-
-        // 复制邮件
-        let copy_cmd = format!("A004 UID COPY {} \"{}\"\r\n", uid, dest_folder);
-        tls_stream.write_all(copy_cmd.as_bytes()).map_err(|e| format!("发送COPY命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
+        let search = |q: &str| {
+            let query = SearchQuery::parse(q).unwrap();
+            let service = &service;
+            async move {
+                service.query_emails("INBOX", &query, 10).await.unwrap().iter().map(|e| e.uid).collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(search("has:attachment").await, vec![40, 20]);
+        assert_eq!(search("-has:attachment").await, vec![30, 10]);
+        assert_eq!(search("has:attachment OR subject:\"Message 30\"").await, vec![40, 30, 20]);
+        assert_eq!(search("has:attachment subject:\"Message 40\"").await, vec![40]);
+    }
 
-        let mut buffer4 = [0u8; 1024];
-        tls_stream.read(&mut buffer4).map_err(|e| format!("读取COPY响应失败: {}", e))?;
+    #[tokio::test]
+    async fn test_missing_folder_fallback() {
+        let state = mailboxes(&[1, 2]);
+        let service = service(spawn_server(Arc::clone(&state)));
 
-        // 标记删除
-        let store_cmd = format!("A005 UID STORE {} +FLAGS (\\Deleted)\r\n", uid);
-        tls_stream.write_all(store_cmd.as_bytes()).map_err(|e| format!("发送STORE命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
+        let fallback = service.fetch_emails("Missing", 10, 0).await.unwrap();
+        assert_eq!(fallback.iter().map(|e| e.uid).collect::<Vec<_>>(), vec![2, 1]);
+        assert!(service.fetch_folder_emails("Missing", 10, 0).await.is_err());
+        assert_eq!(service.fetch_folder_emails("Archive", 10, 0).await.unwrap().len(), 0);
+    }
 
-        let mut buffer5 = [0u8; 1024];
-        tls_stream.read(&mut buffer5).map_err(|e| format!("读取STORE响应失败: {}", e))?;
+    #[tokio::test]
+    async fn test_select_non_ascii_folder() {
+        let state = mailboxes(&[]);
+        // 服务器上的文件夹名称是修改版 UTF-7 编码的“已发送”
+        state.lock().unwrap().folders.insert("&XfJT0ZAB-".to_string(), vec![MockMessage::new(5)]);
+        let service = service(spawn_server(Arc::clone(&state)));
 
-        // EXPUNGE
-        let expunge_cmd = b"A006 EXPUNGE\r\n";
-        tls_stream.write_all(expunge_cmd).map_err(|e| format!("发送EXPUNGE命令失败: {}", e))?;
-        tls_stream.flush().map_err(|e| format!("刷新失败: {}", e))?;
+        let sent = service.fetch_folder_emails("已发送", 10, 0).await.unwrap();
+        assert_eq!(sent.iter().map(|e| e.uid).collect::<Vec<_>>(), vec![5]);
+    }
 
-        let mut buffer6 = [0u8; 1024];
-        tls_stream.read(&mut buffer6).map_err(|e| format!("读取EXPUNGE响应失败: {}", e))?;
+    #[test]
+    fn test_parse_detail_from_mime_tree() {
+        let raw = std::fs::read(format!(
+            "{}/tests/fixtures/mime/outlook_related_mixed.eml",
+            env!("CARGO_MANIFEST_DIR")
+        )).unwrap();
+        let email = service(0).parse_email_full(&raw, 7, "INBOX");
+
+        assert!(email.body.starts_with("Hi Fang,"));
+        assert_eq!(email.from, "John Smith <john.smith@contoso.com>");
+        assert_eq!(email.to, vec!["Wang Fang <wang.fang@example.cn>"]);
+        assert_eq!(email.addresses.from[0].display_name(), "John Smith");
+        assert!(email.html_body.unwrap().contains("<p>Best regards,<br>John</p>"));
+        assert!(email.has_attachment);
+        assert!(email.attachments.iter().any(|a| a.id == "2" && a.filename == "Q1 Budget.xlsx"));
+        assert_eq!(email.inline_resources[0].content_id, "image001.png@01DA1234.56789AB0");
+
+        // 只有 HTML 版本时由 HTML 生成纯文本
+        let html_only = b"Content-Type: text/html; charset=utf-8\r\n\r\n<p>\xe4\xbd\xa0\xe5\xa5\xbd</p>\r\n";
+        let email = service(0).parse_email_full(html_only, 8, "INBOX");
+        assert_eq!(email.body, "你好");
+        assert!(!email.has_attachment);
+    }
 
-        // 发送LOGOUT
-        let _ = tls_stream.write_all(b"A007 LOGOUT\r\n");
-        let _ = tls_stream.flush();
+    #[test]
+    fn test_parse_headers_folding_case_and_duplicates() {
+        let raw = concat!(
+            "Received: from a.example.com by mx.example.com\r\n",
+            "received: from b.example.com\r\n",
+            "\tby a.example.com\r\n",
+            "SUBJECT: Re: =?UTF-8?B?5Lya6K6u?=\r\n",
+            " =?UTF-8?B?57qq6KaB?= (v2)\r\n",
+            "from: Li Na <li.na@example.com>\r\n",
+            "To: a@example.com\r\n",
+            "To: \"Wang, Fang\" <wang@example.cn>\r\n",
+            "Date: Tue, 13 Jan 2026 18:00:00 +0800\r\n",
+            "\r\n",
+            "body\r\n",
+        );
+        let email = service(0).parse_email_full(raw.as_bytes(), 1, "INBOX");
+
+        // 主题中的冒号和折叠行都保留
+        assert_eq!(email.subject, "Re: 会议纪要 (v2)");
+        assert_eq!(email.header("Subject"), Some("Re: 会议纪要 (v2)"));
+        assert_eq!(email.from, "Li Na <li.na@example.com>");
+        assert_eq!(email.to, vec!["a@example.com", "Wang, Fang <wang@example.cn>"]);
+        assert_eq!(email.date.to_rfc3339(), "2026-01-13T10:00:00+00:00");
+
+        let received: Vec<&str> = email.headers.get_all("RECEIVED").collect();
+        assert_eq!(received, vec![
+            "from a.example.com by mx.example.com",
+            "from b.example.com\tby a.example.com",
+        ]);
+        assert_eq!(email.headers.len(), 7);
+
+        // 序列化格式与之前的 EmailHeader 数组相同
+        let json = serde_json::to_value(&email.headers).unwrap();
+        assert_eq!(json[0]["name"], "Received");
+        assert_eq!(json[2]["value"], "Re: 会议纪要 (v2)");
+    }
 
-        Ok(())
+    #[test]
+    fn test_bodystructure_attachment_detection() {
+        let has_attachment = |structure: &str| {
+            let line = format!("* 1 FETCH (BODYSTRUCTURE {})\r\n", structure);
+            match imap_proto::parse_response(line.as_bytes()).unwrap().1 {
+                imap_proto::Response::Fetch(_, attrs) => attrs.iter().any(|attr| match attr {
+                    imap_proto::AttributeValue::BodyStructure(structure) => structure_has_attachment(structure),
+                    _ => false,
+                }),
+                _ => panic!("not a FETCH response"),
+            }
+        };
+        let plain = "(\"TEXT\" \"PLAIN\" (\"CHARSET\" \"UTF-8\") NIL NIL \"7BIT\" 10 1)";
+        let html = "(\"TEXT\" \"HTML\" (\"CHARSET\" \"UTF-8\") NIL NIL \"7BIT\" 20 1)";
+        let image = "(\"IMAGE\" \"PNG\" (\"NAME\" \"logo.png\") \"<logo@mail>\" NIL \"BASE64\" 70 NIL (\"INLINE\" NIL) NIL)";
+
+        assert!(!has_attachment(plain));
+        // 只有正文的 alternative 和 mixed
+        assert!(!has_attachment(&format!("({}{} \"ALTERNATIVE\")", plain, html)));
+        assert!(!has_attachment(&format!("(({}{} \"ALTERNATIVE\") \"MIXED\")", plain, html)));
+        // 正文引用的内嵌图片不算附件
+        assert!(!has_attachment(&format!("({}{} \"RELATED\")", html, image)));
+        // 带文件名的文本部分和 mixed 中的图片是附件
+        assert!(has_attachment(&format!(
+            "({}(\"TEXT\" \"CSV\" (\"NAME\" \"a.csv\") NIL NIL \"7BIT\" 4 1) \"MIXED\")",
+            plain
+        )));
+        assert!(has_attachment(&format!("({}{} \"MIXED\")", plain, image)));
     }
 }
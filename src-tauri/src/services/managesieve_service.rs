@@ -0,0 +1,525 @@
+use crate::models::EmailAccount;
+use native_tls::{TlsConnector, TlsStream};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// ManageSieve 默认端口（RFC 5804）
+pub const MANAGESIEVE_PORT: u16 = 4190;
+
+const MANAGESIEVE_TIMEOUT: u64 = 30;
+
+/// 服务器上的一个 Sieve 脚本
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SieveScriptInfo {
+    pub name: String,
+    pub active: bool,
+}
+
+/// ManageSieve 连接，明文连接在服务器支持时升级为 TLS
+enum Connection {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Connection::Plain(s) => s.read(buf),
+            Connection::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Connection::Plain(s) => s.write(buf),
+            Connection::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Connection::Plain(s) => s.flush(),
+            Connection::Tls(s) => s.flush(),
+        }
+    }
+}
+
+/// 响应中的一个元素
+#[derive(Debug, Clone, PartialEq)]
+enum Item {
+    Atom(String),
+    Str(String),
+}
+
+impl Item {
+    fn text(&self) -> &str {
+        match self {
+            Item::Atom(s) | Item::Str(s) => s,
+        }
+    }
+}
+
+/// 一次命令的完整响应：数据行和最后的 OK/NO/BYE
+struct Response {
+    lines: Vec<Vec<Item>>,
+}
+
+/// 已登录的 ManageSieve 会话
+pub struct ManageSieveSession {
+    reader: BufReader<Connection>,
+    capabilities: Vec<(String, String)>,
+}
+
+impl ManageSieveSession {
+    /// 服务器声明的能力，例如 ("SIEVE", "fileinto imap4flags")
+    pub fn capabilities(&self) -> &[(String, String)] {
+        &self.capabilities
+    }
+
+    fn send(&mut self, command: &[u8]) -> Result<(), String> {
+        let stream = self.reader.get_mut();
+        stream.write_all(command).map_err(|e| format!("发送ManageSieve命令失败: {}", e))?;
+        stream.flush().map_err(|e| format!("刷新失败: {}", e))
+    }
+
+    /// 读取直到 OK/NO/BYE，NO 和 BYE 转换为错误
+    fn read_response(&mut self) -> Result<Response, String> {
+        let mut lines = Vec::new();
+        loop {
+            let items = read_items(&mut self.reader)?;
+            let status = match items.first() {
+                Some(Item::Atom(atom)) => atom.to_ascii_uppercase(),
+                _ => String::new(),
+            };
+
+            match status.as_str() {
+                "OK" => return Ok(Response { lines }),
+                "NO" | "BYE" => {
+                    let message: Vec<&str> = items[1..].iter().map(|i| i.text()).collect();
+                    return Err(format!("ManageSieve服务器返回 {}: {}", status, message.join(" ")));
+                }
+                _ => lines.push(items),
+            }
+        }
+    }
+
+    fn command(&mut self, command: &str) -> Result<Response, String> {
+        self.send(format!("{}\r\n", command).as_bytes())?;
+        self.read_response()
+    }
+
+    pub fn list_scripts(&mut self) -> Result<Vec<SieveScriptInfo>, String> {
+        let response = self.command("LISTSCRIPTS")?;
+        Ok(response
+            .lines
+            .iter()
+            .filter_map(|line| {
+                let name = match line.first()? {
+                    Item::Str(name) => name.clone(),
+                    Item::Atom(_) => return None,
+                };
+                let active = line
+                    .get(1)
+                    .is_some_and(|i| i.text().eq_ignore_ascii_case("ACTIVE"));
+                Some(SieveScriptInfo { name, active })
+            })
+            .collect())
+    }
+
+    pub fn get_script(&mut self, name: &str) -> Result<String, String> {
+        let response = self.command(&format!("GETSCRIPT {}", quote(name)))?;
+        response
+            .lines
+            .first()
+            .and_then(|line| line.first())
+            .map(|item| item.text().to_string())
+            .ok_or_else(|| format!("脚本 '{}' 内容为空", name))
+    }
+
+    /// 上传（或覆盖）脚本，脚本以非同步字面量发送
+    pub fn put_script(&mut self, name: &str, script: &str) -> Result<(), String> {
+        let mut command = format!("PUTSCRIPT {} {{{}+}}\r\n", quote(name), script.len()).into_bytes();
+        command.extend_from_slice(script.as_bytes());
+        command.extend_from_slice(b"\r\n");
+        self.send(&command)?;
+        self.read_response().map(|_| ())
+    }
+
+    /// 激活脚本；传入空字符串则停用所有脚本
+    pub fn set_active(&mut self, name: &str) -> Result<(), String> {
+        self.command(&format!("SETACTIVE {}", quote(name))).map(|_| ())
+    }
+
+    pub fn delete_script(&mut self, name: &str) -> Result<(), String> {
+        self.command(&format!("DELETESCRIPT {}", quote(name))).map(|_| ())
+    }
+
+    pub fn logout(mut self) -> Result<(), String> {
+        self.command("LOGOUT").map(|_| ())
+    }
+}
+
+/// ManageSieve 客户端，用于在服务器端管理 Sieve 脚本
+pub struct ManageSieveService {
+    host: String,
+    port: u16,
+    username: String,
+    password: String,
+}
+
+impl ManageSieveService {
+    pub fn new(host: String, port: u16, username: String, password: String) -> Self {
+        Self { host, port, username, password }
+    }
+
+    /// 使用账户配置的 ManageSieve 服务器，未配置时与 IMAP 服务器相同
+    pub fn from_account(account: &EmailAccount, password: String) -> Self {
+        Self::new(
+            account.sieve_server.clone().unwrap_or_else(|| account.imap_server.clone()),
+            account.sieve_port.unwrap_or(MANAGESIEVE_PORT),
+            account.email.clone(),
+            password,
+        )
+    }
+
+    fn is_loopback(&self) -> bool {
+        matches!(self.host.as_str(), "localhost" | "127.0.0.1" | "::1")
+    }
+
+    /// 连接、按需 STARTTLS 并使用 SASL PLAIN 登录
+    ///
+    /// 服务器不支持 STARTTLS 时只允许连接本机，避免明文发送密码。
+    pub async fn connect(&self) -> Result<ManageSieveSession, String> {
+        let stream = TcpStream::connect((self.host.as_str(), self.port))
+            .map_err(|e| format!("TCP连接失败: {}", e))?;
+        stream
+            .set_read_timeout(Some(Duration::from_secs(MANAGESIEVE_TIMEOUT)))
+            .map_err(|e| format!("设置超时失败: {}", e))?;
+
+        let mut session = ManageSieveSession {
+            reader: BufReader::new(Connection::Plain(stream)),
+            capabilities: Vec::new(),
+        };
+        session.capabilities = parse_capabilities(&session.read_response()?);
+
+        if session.capabilities.iter().any(|(name, _)| name == "STARTTLS") {
+            session.command("STARTTLS")?;
+
+            let tls = if cfg!(debug_assertions) {
+                TlsConnector::builder()
+                    .danger_accept_invalid_certs(true)
+                    .danger_accept_invalid_hostnames(true)
+                    .build()
+            } else {
+                TlsConnector::builder().build()
+            }.map_err(|e| format!("TLS创建失败: {}", e))?;
+
+            let tcp = match session.reader.into_inner() {
+                Connection::Plain(tcp) => tcp,
+                Connection::Tls(_) => return Err("连接已经是TLS".to_string()),
+            };
+            let tls_stream = tls.connect(&self.host, tcp)
+                .map_err(|e| format!("TLS连接失败: {}", e))?;
+
+            session = ManageSieveSession {
+                reader: BufReader::new(Connection::Tls(Box::new(tls_stream))),
+                capabilities: Vec::new(),
+            };
+            // TLS 握手后服务器会重新发送能力列表
+            session.capabilities = parse_capabilities(&session.read_response()?);
+        } else if !self.is_loopback() {
+            return Err("ManageSieve服务器不支持STARTTLS，已拒绝明文登录".to_string());
+        }
+
+        let supports_plain = session
+            .capabilities
+            .iter()
+            .any(|(name, value)| name == "SASL" && value.split_whitespace().any(|m| m.eq_ignore_ascii_case("PLAIN")));
+        if !supports_plain {
+            return Err("ManageSieve服务器不支持PLAIN认证".to_string());
+        }
+
+        let credentials = base64::encode(format!("\0{}\0{}", self.username, self.password));
+        session
+            .command(&format!("AUTHENTICATE \"PLAIN\" {}", quote(&credentials)))
+            .map_err(|e| format!("ManageSieve登录失败: {}", e))?;
+
+        Ok(session)
+    }
+
+    pub async fn list_scripts(&self) -> Result<Vec<SieveScriptInfo>, String> {
+        let mut session = self.connect().await?;
+        let scripts = session.list_scripts()?;
+        session.logout()?;
+        Ok(scripts)
+    }
+
+    /// 上传脚本，可选择同时激活
+    pub async fn upload_script(&self, name: &str, script: &str, activate: bool) -> Result<(), String> {
+        let mut session = self.connect().await?;
+        session.put_script(name, script)?;
+        if activate {
+            session.set_active(name)?;
+        }
+        session.logout()
+    }
+
+    pub async fn activate_script(&self, name: &str) -> Result<(), String> {
+        let mut session = self.connect().await?;
+        session.set_active(name)?;
+        session.logout()
+    }
+
+    pub async fn delete_script(&self, name: &str) -> Result<(), String> {
+        let mut session = self.connect().await?;
+        session.delete_script(name)?;
+        session.logout()
+    }
+}
+
+/// 生成 ManageSieve 引号字符串
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// 从问候或 STARTTLS 后的响应中提取能力列表
+fn parse_capabilities(response: &Response) -> Vec<(String, String)> {
+    response
+        .lines
+        .iter()
+        .filter_map(|line| {
+            let name = line.first()?.text().to_ascii_uppercase();
+            let value = line.get(1).map(|i| i.text().to_string()).unwrap_or_default();
+            Some((name, value))
+        })
+        .collect()
+}
+
+/// 读取一行响应并拆分为原子和字符串，字面量 {n} / {n+} 按字节长度读取
+fn read_items<R: BufRead>(reader: &mut R) -> Result<Vec<Item>, String> {
+    let mut items = Vec::new();
+
+    loop {
+        let mut line = Vec::new();
+        let n = reader
+            .read_until(b'\n', &mut line)
+            .map_err(|e| format!("读取ManageSieve响应失败: {}", e))?;
+        if n == 0 {
+            return Err("ManageSieve服务器关闭了连接".to_string());
+        }
+        while matches!(line.last(), Some(b'\n') | Some(b'\r')) {
+            line.pop();
+        }
+
+        let mut i = 0;
+        let mut literal_len = None;
+        while i < line.len() {
+            match line[i] {
+                b' ' => i += 1,
+                b'"' => {
+                    let mut value = Vec::new();
+                    i += 1;
+                    while i < line.len() && line[i] != b'"' {
+                        if line[i] == b'\\' && i + 1 < line.len() {
+                            i += 1;
+                        }
+                        value.push(line[i]);
+                        i += 1;
+                    }
+                    i += 1;
+                    items.push(Item::Str(String::from_utf8_lossy(&value).to_string()));
+                }
+                b'{' => {
+                    let end = line[i..].iter().position(|&b| b == b'}').map(|p| i + p);
+                    match end {
+                        Some(end) if end == line.len() - 1 => {
+                            let digits = String::from_utf8_lossy(&line[i + 1..end]).trim_end_matches('+').to_string();
+                            literal_len = Some(digits.parse::<usize>().map_err(|_| "ManageSieve字面量长度无效".to_string())?);
+                            i = line.len();
+                        }
+                        _ => {
+                            items.push(Item::Atom(String::from_utf8_lossy(&line[i..]).to_string()));
+                            i = line.len();
+                        }
+                    }
+                }
+                b'(' => {
+                    // 响应码作为一个原子保留，例如 (WARNINGS)
+                    let mut depth = 0;
+                    let mut in_quotes = false;
+                    let start = i;
+                    while i < line.len() {
+                        match line[i] {
+                            b'"' => in_quotes = !in_quotes,
+                            b'(' if !in_quotes => depth += 1,
+                            b')' if !in_quotes => {
+                                depth -= 1;
+                                if depth == 0 {
+                                    i += 1;
+                                    break;
+                                }
+                            }
+                            _ => {}
+                        }
+                        i += 1;
+                    }
+                    items.push(Item::Atom(String::from_utf8_lossy(&line[start..i]).to_string()));
+                }
+                _ => {
+                    let start = i;
+                    while i < line.len() && line[i] != b' ' {
+                        i += 1;
+                    }
+                    items.push(Item::Atom(String::from_utf8_lossy(&line[start..i]).to_string()));
+                }
+            }
+        }
+
+        match literal_len {
+            Some(len) => {
+                let mut data = vec![0u8; len];
+                reader
+                    .read_exact(&mut data)
+                    .map_err(|e| format!("读取ManageSieve字面量失败: {}", e))?;
+                items.push(Item::Str(String::from_utf8_lossy(&data).to_string()));
+                // 字面量之后本行可能还有内容，继续读取
+            }
+            None => return Ok(items),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    type Scripts = Arc<Mutex<BTreeMap<String, (String, bool)>>>;
+
+    /// 本地的 ManageSieve 替身服务器，只支持测试用到的命令，不提供 STARTTLS
+    fn spawn_server(scripts: Scripts) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                let scripts = Arc::clone(&scripts);
+                std::thread::spawn(move || serve(stream, scripts));
+            }
+        });
+
+        port
+    }
+
+    fn serve(stream: TcpStream, scripts: Scripts) {
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+        writer
+            .write_all(b"\"IMPLEMENTATION\" \"Stand-in\"\r\n\"SASL\" \"PLAIN\"\r\n\"SIEVE\" \"fileinto imap4flags\"\r\nOK \"Ready\"\r\n")
+            .unwrap();
+
+        loop {
+            let items = match read_items(&mut reader) {
+                Ok(items) => items,
+                Err(_) => return,
+            };
+            let command = items[0].text().to_ascii_uppercase();
+            let args: Vec<String> = items[1..].iter().map(|i| i.text().to_string()).collect();
+            let mut scripts = scripts.lock().unwrap();
+
+            let reply = match command.as_str() {
+                "AUTHENTICATE" => {
+                    let decoded = base64::decode(&args[1]).unwrap();
+                    if decoded == b"\0user@example.com\0secret" {
+                        "OK\r\n".to_string()
+                    } else {
+                        "NO \"Authentication failed\"\r\n".to_string()
+                    }
+                }
+                "LISTSCRIPTS" => {
+                    let mut out = String::new();
+                    for (name, (_, active)) in scripts.iter() {
+                        out.push_str(&quote(name));
+                        if *active {
+                            out.push_str(" ACTIVE");
+                        }
+                        out.push_str("\r\n");
+                    }
+                    out + "OK\r\n"
+                }
+                "PUTSCRIPT" => {
+                    let active = scripts.get(&args[0]).is_some_and(|(_, a)| *a);
+                    scripts.insert(args[0].clone(), (args[1].clone(), active));
+                    "OK\r\n".to_string()
+                }
+                "GETSCRIPT" => match scripts.get(&args[0]) {
+                    Some((content, _)) => format!("{{{}}}\r\n{}\r\nOK\r\n", content.len(), content),
+                    None => "NO (NONEXISTENT) \"No such script\"\r\n".to_string(),
+                },
+                "SETACTIVE" => {
+                    for (name, (_, active)) in scripts.iter_mut() {
+                        *active = *name == args[0];
+                    }
+                    "OK\r\n".to_string()
+                }
+                "DELETESCRIPT" => match scripts.remove(&args[0]) {
+                    Some(_) => "OK\r\n".to_string(),
+                    None => "NO (NONEXISTENT) \"No such script\"\r\n".to_string(),
+                },
+                "LOGOUT" => {
+                    let _ = writer.write_all(b"OK \"Bye\"\r\n");
+                    return;
+                }
+                _ => "NO \"Unknown command\"\r\n".to_string(),
+            };
+            writer.write_all(reply.as_bytes()).unwrap();
+        }
+    }
+
+    fn service(port: u16, password: &str) -> ManageSieveService {
+        ManageSieveService::new("127.0.0.1".to_string(), port, "user@example.com".to_string(), password.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_upload_activate_list_and_delete() {
+        let scripts: Scripts = Arc::new(Mutex::new(BTreeMap::new()));
+        let port = spawn_server(Arc::clone(&scripts));
+        let service = service(port, "secret");
+
+        let script = "require \"fileinto\";\r\n# 规则: \"周报\"\r\nif header :contains \"subject\" \"周报\" {\r\n    fileinto \"Reports\";\r\n}\r\n";
+        service.upload_script("mailflow", script, true).await.unwrap();
+        service.upload_script("legacy", "keep;", false).await.unwrap();
+
+        let listed = service.list_scripts().await.unwrap();
+        assert_eq!(listed, vec![
+            SieveScriptInfo { name: "legacy".to_string(), active: false },
+            SieveScriptInfo { name: "mailflow".to_string(), active: true },
+        ]);
+
+        // 字面量按字节长度读取，中文和内嵌引号都能原样取回
+        let mut session = service.connect().await.unwrap();
+        assert_eq!(session.get_script("mailflow").unwrap(), script);
+        session.logout().unwrap();
+
+        service.activate_script("legacy").await.unwrap();
+        service.delete_script("mailflow").await.unwrap();
+        let listed = service.list_scripts().await.unwrap();
+        assert_eq!(listed, vec![SieveScriptInfo { name: "legacy".to_string(), active: true }]);
+
+        assert!(service.delete_script("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_authentication_failure() {
+        let port = spawn_server(Arc::new(Mutex::new(BTreeMap::new())));
+        let result = service(port, "wrong").connect().await;
+        assert!(result.err().unwrap().contains("ManageSieve登录失败"));
+    }
+}
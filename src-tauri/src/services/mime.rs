@@ -0,0 +1,965 @@
+use crate::models::{Attachment, InlineResource};
+use std::collections::HashMap;
+
+/// 最大嵌套层数，超过时按普通部分处理，避免恶意邮件导致栈溢出
+const MAX_DEPTH: usize = 32;
+
+/// MIME 部分树中的一个节点（RFC 2045/2046）
+#[derive(Debug, Clone)]
+pub struct MimePart {
+    /// IMAP 部分编号（如 `1`、`2.1`），与 `BODY[<编号>]` 一致；multipart 根节点为空
+    pub part_id: String,
+    /// 部分头（已展开折叠行，保留原有顺序）
+    pub headers: Vec<(String, String)>,
+    /// 小写的 类型/子类型，如 `text/plain`
+    pub content_type: String,
+    /// Content-Type 参数，参数名为小写，RFC 2231 分段参数已合并解码
+    pub params: HashMap<String, String>,
+    /// 小写的 Content-Disposition 类型（attachment / inline）
+    pub disposition: Option<String>,
+    pub disposition_params: HashMap<String, String>,
+    /// 小写的 Content-Transfer-Encoding，未指定时为空
+    pub encoding: String,
+    /// 去掉尖括号的 Content-ID
+    pub content_id: Option<String>,
+    /// 未解码的正文，multipart 部分为空
+    pub body: Vec<u8>,
+    pub children: Vec<MimePart>,
+    /// message/rfc822 部分中封装的邮件，其部分编号接在外层编号之后（如 `2.1`）
+    pub message: Option<Box<MimePart>>,
+}
+
+impl MimePart {
+    /// 解析完整的原始邮件
+    pub fn parse(raw: &[u8]) -> Self {
+        Self::parse_part(raw, String::new(), "text/plain", 0)
+    }
+
+    fn parse_part(raw: &[u8], part_id: String, default_type: &str, depth: usize) -> Self {
+        let (header_block, body) = split_header(raw);
+        let headers = parse_headers(header_block);
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+        };
+
+        let (content_type, params) = header("Content-Type")
+            .map(parse_header_value)
+            .filter(|(content_type, _)| content_type.contains('/'))
+            .unwrap_or_else(|| (default_type.to_string(), HashMap::new()));
+        let (disposition, disposition_params) = match header("Content-Disposition").map(parse_header_value) {
+            Some((disposition, params)) if !disposition.is_empty() => (Some(disposition), params),
+            _ => (None, HashMap::new()),
+        };
+        let encoding = header("Content-Transfer-Encoding")
+            .map(|e| e.trim().to_lowercase())
+            .unwrap_or_default();
+        let content_id = header("Content-ID")
+            .map(|id| id.trim().trim_start_matches('<').trim_end_matches('>').trim().to_string())
+            .filter(|id| !id.is_empty());
+
+        let mut part = MimePart {
+            part_id,
+            headers,
+            content_type,
+            params,
+            disposition,
+            disposition_params,
+            encoding,
+            content_id,
+            body: Vec::new(),
+            children: Vec::new(),
+            message: None,
+        };
+        let nested = depth < MAX_DEPTH;
+
+        let boundary = part.params.get("boundary").filter(|b| !b.is_empty()).cloned();
+        if let (true, Some(boundary)) = (nested && part.content_type.starts_with("multipart/"), boundary) {
+            // multipart/digest 中的部分默认为 message/rfc822
+            let child_type = if part.content_type == "multipart/digest" { "message/rfc822" } else { "text/plain" };
+            for (index, child) in split_multipart(body, &boundary).into_iter().enumerate() {
+                let child_id = if part.part_id.is_empty() {
+                    (index + 1).to_string()
+                } else {
+                    format!("{}.{}", part.part_id, index + 1)
+                };
+                part.children.push(Self::parse_part(child, child_id, child_type, depth + 1));
+            }
+            return part;
+        }
+
+        // 非 multipart 邮件的正文在 IMAP 中编号为 1
+        if part.part_id.is_empty() {
+            part.part_id = "1".to_string();
+        }
+        part.body = body.to_vec();
+
+        if nested && part.content_type == "message/rfc822" {
+            let mut message = Self::parse_part(&part.decoded_body(), String::new(), "text/plain", depth + 1);
+            message.prefix_ids(&part.part_id);
+            part.message = Some(Box::new(message));
+        }
+        part
+    }
+
+    fn prefix_ids(&mut self, prefix: &str) {
+        self.part_id = if self.part_id.is_empty() {
+            prefix.to_string()
+        } else {
+            format!("{}.{}", prefix, self.part_id)
+        };
+        for child in self.children.iter_mut().chain(self.message.as_deref_mut()) {
+            child.prefix_ids(prefix);
+        }
+    }
+
+    /// 按名称查找部分头（不区分大小写），返回第一个匹配值
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// 按部分编号查找，包括转发邮件中的部分
+    pub fn find(&self, part_id: &str) -> Option<&MimePart> {
+        if self.part_id == part_id {
+            return Some(self);
+        }
+        self.children
+            .iter()
+            .chain(self.message.as_deref())
+            .find_map(|child| child.find(part_id))
+    }
+
+    /// 深度优先列出全部叶子部分（不进入转发的邮件）
+    pub fn leaves(&self) -> Vec<&MimePart> {
+        if self.children.is_empty() {
+            return vec![self];
+        }
+        self.children.iter().flat_map(|child| child.leaves()).collect()
+    }
+
+    /// 按 Content-Transfer-Encoding 解码后的正文
+    pub fn decoded_body(&self) -> Vec<u8> {
+        match self.encoding.as_str() {
+            "base64" => {
+                let cleaned: Vec<u8> = self.body
+                    .iter()
+                    .copied()
+                    .filter(|b| !b.is_ascii_whitespace())
+                    .collect();
+                // 只取得了开头部分时解码其中完整的 4 字节组
+                base64::decode(&cleaned)
+                    .or_else(|_| base64::decode(&cleaned[..cleaned.len() / 4 * 4]))
+                    .unwrap_or_else(|_| self.body.clone())
+            }
+            "quoted-printable" => decode_quoted_printable(&self.body),
+            _ => self.body.clone(),
+        }
+    }
+
+    /// 按 charset 参数解码的文本内容
+    pub fn text(&self) -> String {
+        let charset = self.params.get("charset").map(String::as_str).unwrap_or("utf-8");
+        decode_charset(&self.decoded_body(), charset)
+    }
+
+    /// 附件文件名：优先 Content-Disposition 的 filename，其次 Content-Type 的 name，
+    /// 转发的邮件以其主题命名；支持 RFC 2231 参数和 RFC 2047 编码，去掉路径部分
+    pub fn filename(&self) -> Option<String> {
+        let name = match self.disposition_params.get("filename").or_else(|| self.params.get("name")) {
+            Some(name) => decode_rfc2047(name),
+            None => format!("{}.eml", decode_rfc2047(self.message.as_ref()?.header("Subject")?)),
+        };
+        let name = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+        if name.is_empty() { None } else { Some(name.to_string()) }
+    }
+
+    /// 是否作为附件显示：不属于正文的文本部分，以及其他所有叶子部分
+    pub fn is_attachment(&self) -> bool {
+        if !self.children.is_empty() || self.content_type.starts_with("multipart/") {
+            return false;
+        }
+        !self.content_type.starts_with("text/") || !self.is_inline_text()
+    }
+
+    /// 文本部分是否属于正文：标记为 inline，或既未标记也没有文件名
+    fn is_inline_text(&self) -> bool {
+        match self.disposition.as_deref() {
+            Some("attachment") => false,
+            Some(_) => true,
+            None => self.filename().is_none(),
+        }
+    }
+
+    /// 正文的纯文本和 HTML 版本
+    ///
+    /// multipart/alternative 取最后（最接近原文）的版本，multipart/related 只看根部分，
+    /// 其他 multipart 取第一个正文部分；附件和转发的邮件不计入正文
+    pub fn body_text(&self) -> (Option<String>, Option<String>) {
+        let mut plain = None;
+        let mut html = None;
+        self.collect_body(&mut plain, &mut html);
+        (plain, html)
+    }
+
+    fn collect_body(&self, plain: &mut Option<String>, html: &mut Option<String>) {
+        match self.content_type.as_str() {
+            "multipart/alternative" => {
+                for child in self.children.iter().rev() {
+                    child.collect_body(plain, html);
+                }
+            }
+            "multipart/related" => {
+                if let Some(root) = self.related_root() {
+                    root.collect_body(plain, html);
+                }
+            }
+            t if t.starts_with("multipart/") => {
+                for child in &self.children {
+                    child.collect_body(plain, html);
+                }
+            }
+            "text/plain" | "text/html" if self.is_inline_text() => {
+                let slot = if self.content_type == "text/plain" { plain } else { html };
+                if slot.is_none() {
+                    *slot = Some(self.text());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// multipart/related 的根部分：start 参数指定的部分，默认为第一个部分
+    fn related_root(&self) -> Option<&MimePart> {
+        let start = self.params
+            .get("start")
+            .map(|start| start.trim().trim_start_matches('<').trim_end_matches('>'));
+        start
+            .and_then(|start| self.children.iter().find(|c| c.content_id.as_deref() == Some(start)))
+            .or_else(|| self.children.first())
+    }
+
+    /// 附件信息，size 为解码后的字节数
+    pub fn to_attachment(&self, size: usize) -> Attachment {
+        Attachment {
+            id: self.part_id.clone(),
+            filename: self.filename().unwrap_or_else(|| format!("附件{}", self.part_id)),
+            size: size as u64,
+            content_type: self.content_type.clone(),
+        }
+    }
+
+    /// 邮件中的全部附件，HTML 正文引用的内嵌资源除外
+    pub fn attachments(&self) -> Vec<Attachment> {
+        let inline: Vec<&str> = self.inline_parts().iter().map(|part| part.part_id.as_str()).collect();
+        self.leaves()
+            .into_iter()
+            .filter(|part| part.is_attachment() && !inline.contains(&part.part_id.as_str()))
+            .map(|part| part.to_attachment(part.decoded_body().len()))
+            .collect()
+    }
+
+    /// multipart/related 中根部分以外、带 Content-ID 的叶子部分（不进入转发的邮件）
+    pub fn inline_parts(&self) -> Vec<&MimePart> {
+        let mut parts = Vec::new();
+        self.collect_inline(&mut parts);
+        parts
+    }
+
+    fn collect_inline<'a>(&'a self, parts: &mut Vec<&'a MimePart>) {
+        if self.content_type == "multipart/related" {
+            let root = self.related_root().map(|root| root.part_id.as_str());
+            parts.extend(self.children.iter().filter(|child| {
+                Some(child.part_id.as_str()) != root
+                    && child.children.is_empty()
+                    && child.content_id.is_some()
+                    && child.disposition.as_deref() != Some("attachment")
+            }));
+        }
+        for child in &self.children {
+            child.collect_inline(parts);
+        }
+    }
+
+    /// 内嵌资源信息，size 为解码后的字节数
+    pub fn inline_resources(&self) -> Vec<InlineResource> {
+        self.inline_parts()
+            .into_iter()
+            .filter_map(|part| {
+                Some(InlineResource {
+                    content_id: part.content_id.clone()?,
+                    part_id: part.part_id.clone(),
+                    content_type: part.content_type.clone(),
+                    size: part.decoded_body().len() as u64,
+                })
+            })
+            .collect()
+    }
+}
+
+/// 将 HTML 中的 `cid:` 引用（RFC 2392）替换为 resolve 返回的地址，无法解析的引用保持原样
+///
+/// resolve 收到的是 URL 解码后的 Content-ID
+pub fn replace_cid_urls(html: &str, mut resolve: impl FnMut(&str) -> Option<String>) -> String {
+    // 只转换 ASCII 大小写，字节位置与原文一致
+    let lower = html.to_ascii_lowercase();
+    let mut result = String::with_capacity(html.len());
+    let mut pos = 0;
+
+    while let Some(offset) = lower[pos..].find("cid:") {
+        let start = pos + offset;
+        let id_start = start + "cid:".len();
+        let id_end = html[id_start..]
+            .find(|c: char| matches!(c, '"' | '\'' | ')' | '>') || c.is_whitespace())
+            .map_or(html.len(), |end| id_start + end);
+
+        // 只替换位于属性值或 url() 开头的引用，避免改动正文中的文字
+        let at_url_start = html[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| matches!(c, '"' | '\'' | '(' | '='));
+        let id = String::from_utf8_lossy(&percent_decode(&html[id_start..id_end])).to_string();
+
+        result.push_str(&html[pos..start]);
+        match resolve(&id).filter(|_| at_url_start && !id.is_empty()) {
+            Some(url) => result.push_str(&url),
+            None => result.push_str(&html[start..id_end]),
+        }
+        pos = id_end;
+    }
+
+    result.push_str(&html[pos..]);
+    result
+}
+
+/// 以 base64 编码的 data URI
+///
+/// 类型来自邮件，会原样插入 HTML 属性，只接受 `类型/子类型` 形式（RFC 6838 的字符集），
+/// 否则使用 application/octet-stream
+pub fn data_uri(content_type: &str, data: &[u8]) -> String {
+    let is_name = |name: &str| {
+        !name.is_empty()
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+    };
+    let content_type = match content_type.split_once('/') {
+        Some((kind, subtype)) if is_name(kind) && is_name(subtype) => content_type,
+        _ => "application/octet-stream",
+    };
+    format!("data:{};base64,{}", content_type, base64::encode(data))
+}
+
+/// 以第一个空行分隔头部和正文，兼容 CRLF 和 LF
+fn split_header(raw: &[u8]) -> (&[u8], &[u8]) {
+    let mut pos = 0;
+    while pos < raw.len() {
+        let end = match raw[pos..].iter().position(|b| *b == b'\n') {
+            Some(offset) => pos + offset + 1,
+            None => return (raw, &[]),
+        };
+        let line = &raw[pos..end];
+        if line == b"\n" || line == b"\r\n" {
+            return (&raw[..pos], &raw[end..]);
+        }
+        pos = end;
+    }
+    (raw, &[])
+}
+
+/// 解析头部块，折叠行并入上一个头部
+fn parse_headers(block: &[u8]) -> Vec<(String, String)> {
+    // 头部应为 ASCII，但常见客户端直接写入 UTF-8 或 GBK 字节
+    let text = decode_charset(block, "utf-8");
+    let mut headers: Vec<(String, String)> = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push_str(line);
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.to_string()));
+        }
+    }
+
+    for (_, value) in headers.iter_mut() {
+        *value = value.trim().to_string();
+    }
+    headers
+}
+
+/// 解析带参数的头部值（如 Content-Type、Content-Disposition），返回小写的值和参数
+pub fn parse_header_value(value: &str) -> (String, HashMap<String, String>) {
+    let (main, rest) = match value.split_once(';') {
+        Some((main, rest)) => (main, rest),
+        None => (value, ""),
+    };
+    (main.trim().to_lowercase(), parse_params(rest))
+}
+
+/// 解析 `; name=value` 参数列表，合并 RFC 2231 的分段参数（`name*0*=`）并按字符集解码
+fn parse_params(input: &str) -> HashMap<String, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut raw = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        while pos < chars.len() && (chars[pos].is_whitespace() || chars[pos] == ';') {
+            pos += 1;
+        }
+        let start = pos;
+        while pos < chars.len() && chars[pos] != '=' && chars[pos] != ';' {
+            pos += 1;
+        }
+        let name: String = chars[start..pos].iter().collect();
+        if pos >= chars.len() || chars[pos] == ';' {
+            continue;
+        }
+        pos += 1;
+        while pos < chars.len() && chars[pos].is_whitespace() {
+            pos += 1;
+        }
+
+        let mut value = String::new();
+        if chars.get(pos) == Some(&'"') {
+            pos += 1;
+            while pos < chars.len() && chars[pos] != '"' {
+                if chars[pos] == '\\' && pos + 1 < chars.len() {
+                    pos += 1;
+                }
+                value.push(chars[pos]);
+                pos += 1;
+            }
+            pos += 1;
+            while pos < chars.len() && chars[pos] != ';' {
+                pos += 1;
+            }
+        } else {
+            while pos < chars.len() && chars[pos] != ';' {
+                value.push(chars[pos]);
+                pos += 1;
+            }
+            value = value.trim().to_string();
+        }
+        raw.push((name.trim().to_lowercase(), value));
+    }
+
+    let mut params = HashMap::new();
+    // 参数名 → [(段号, 是否为扩展值, 值)]
+    let mut sections: HashMap<String, Vec<(u32, bool, String)>> = HashMap::new();
+    for (name, value) in raw {
+        let (base, rest) = match name.split_once('*') {
+            Some(split) => split,
+            None => {
+                params.entry(name).or_insert(value);
+                continue;
+            }
+        };
+        let section = if rest.is_empty() {
+            Some((0, true))
+        } else {
+            rest.trim_end_matches('*').parse().ok().map(|index| (index, rest.ends_with('*')))
+        };
+        match section {
+            Some((index, extended)) => sections.entry(base.to_string()).or_default().push((index, extended, value)),
+            None => {
+                params.entry(name).or_insert(value);
+            }
+        }
+    }
+
+    for (name, mut parts) in sections {
+        parts.sort_by_key(|(index, _, _)| *index);
+        let mut charset = String::new();
+        let mut bytes = Vec::new();
+        for (position, (_, extended, value)) in parts.iter().enumerate() {
+            if !extended {
+                bytes.extend_from_slice(value.as_bytes());
+                continue;
+            }
+            // 第一段以 `字符集'语言'` 开头
+            let mut value = value.as_str();
+            if position == 0 {
+                if let Some((set, rest)) = value.split_once('\'') {
+                    charset = set.to_string();
+                    value = rest.split_once('\'').map(|(_, v)| v).unwrap_or(rest);
+                }
+            }
+            bytes.extend(percent_decode(value));
+        }
+        let charset = if charset.is_empty() { "utf-8" } else { charset.as_str() };
+        // RFC 2231 形式优先于同名的普通参数
+        params.insert(name, decode_charset(&bytes, charset));
+    }
+
+    params
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+fn percent_decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+    while pos < bytes.len() {
+        if bytes[pos] == b'%' && pos + 2 < bytes.len() {
+            if let (Some(high), Some(low)) = (hex_value(bytes[pos + 1]), hex_value(bytes[pos + 2])) {
+                result.push(high << 4 | low);
+                pos += 3;
+                continue;
+            }
+        }
+        result.push(bytes[pos]);
+        pos += 1;
+    }
+    result
+}
+
+/// 解码 Quoted-Printable 字节，软换行（行尾的 `=`）被移除
+pub fn decode_quoted_printable(body: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(body.len());
+    let mut pos = 0;
+    while pos < body.len() {
+        if body[pos] != b'=' {
+            result.push(body[pos]);
+            pos += 1;
+            continue;
+        }
+        match (body.get(pos + 1), body.get(pos + 2)) {
+            (Some(b'\r'), Some(b'\n')) => pos += 3,
+            (Some(b'\n'), _) => pos += 2,
+            (Some(&high), Some(&low)) if hex_value(high).is_some() && hex_value(low).is_some() => {
+                result.push(hex_value(high).unwrap_or(0) << 4 | hex_value(low).unwrap_or(0));
+                pos += 3;
+            }
+            _ => {
+                result.push(b'=');
+                pos += 1;
+            }
+        }
+    }
+    result
+}
+
+/// 按分隔符拆分 multipart 正文，分隔符前的换行属于分隔符
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let delimiter = delimiter.as_bytes();
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut pos = 0;
+
+    while pos < body.len() {
+        let end = body[pos..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map(|offset| pos + offset + 1)
+            .unwrap_or(body.len());
+        let line = trim_line_end(&body[pos..end]);
+
+        if let Some(rest) = line.strip_prefix(delimiter) {
+            let closing = rest.starts_with(b"--");
+            if rest.is_empty() || closing {
+                if let Some(start) = start {
+                    parts.push(strip_line_break(&body[start..pos]));
+                }
+                if closing {
+                    return parts;
+                }
+                start = Some(end);
+            }
+        }
+        pos = end;
+    }
+
+    // 缺少结束分隔符时保留最后一部分
+    if let Some(start) = start {
+        parts.push(&body[start.min(body.len())..]);
+    }
+    parts
+}
+
+fn trim_line_end(line: &[u8]) -> &[u8] {
+    let mut end = line.len();
+    while end > 0 && matches!(line[end - 1], b'\r' | b'\n' | b' ' | b'\t') {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+fn strip_line_break(part: &[u8]) -> &[u8] {
+    part.strip_suffix(b"\r\n")
+        .or_else(|| part.strip_suffix(b"\n"))
+        .unwrap_or(part)
+}
+
+/// 解码邮件头中的 RFC 2047 编码字（`=?charset?B|Q?...?=`）
+///
+/// 相邻编码字之间的空白被忽略；同一字符集的相邻编码字先拼接字节再解码，
+/// 避免被拆到两个编码字中的多字节字符出现乱码。无法解码的编码字保留原文
+pub fn decode_rfc2047(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    // 尚未解码的相邻编码字：(字符集, 字节)
+    let mut pending: Option<(&str, Vec<u8>)> = None;
+    let mut pos = 0;
+
+    while pos < text.len() {
+        let rest = &text[pos..];
+
+        if let Some((charset, bytes, len)) = parse_encoded_word(rest) {
+            match &mut pending {
+                Some((pending_charset, pending_bytes)) if pending_charset.eq_ignore_ascii_case(charset) => {
+                    pending_bytes.extend(bytes);
+                }
+                _ => {
+                    flush_encoded_words(&mut result, pending.take());
+                    pending = Some((charset, bytes));
+                }
+            }
+            pos += len;
+            continue;
+        }
+
+        let whitespace = rest.len() - rest.trim_start_matches([' ', '\t', '\r', '\n']).len();
+        if pending.is_some() && whitespace > 0 && parse_encoded_word(&rest[whitespace..]).is_some() {
+            pos += whitespace;
+            continue;
+        }
+
+        flush_encoded_words(&mut result, pending.take());
+        let ch = rest.chars().next().unwrap_or_default();
+        result.push(ch);
+        pos += ch.len_utf8();
+    }
+
+    flush_encoded_words(&mut result, pending);
+    result
+}
+
+fn flush_encoded_words(result: &mut String, pending: Option<(&str, Vec<u8>)>) {
+    if let Some((charset, bytes)) = pending {
+        result.push_str(&decode_charset(&bytes, charset));
+    }
+}
+
+/// 解析位于开头的编码字，返回 (字符集, 解码后的字节, 编码字长度)
+fn parse_encoded_word(text: &str) -> Option<(&str, Vec<u8>, usize)> {
+    let rest = text.strip_prefix("=?")?;
+    let (charset, rest) = rest.split_once('?')?;
+    if charset.is_empty() || charset.contains(|c: char| c.is_whitespace() || c == '=') {
+        return None;
+    }
+    let (encoding, rest) = rest.split_once('?')?;
+    let end = rest.find("?=")?;
+    let encoded = &rest[..end];
+
+    let bytes = match encoding {
+        "B" | "b" => decode_b_encoding(encoded)?,
+        "Q" | "q" => decode_q_encoding(encoded),
+        _ => return None,
+    };
+    let len = text.len() - rest.len() + end + "?=".len();
+    Some((charset, bytes, len))
+}
+
+/// B 编码即 base64，部分客户端省略末尾的填充
+fn decode_b_encoding(encoded: &str) -> Option<Vec<u8>> {
+    let mut cleaned: String = encoded.chars().filter(|c| !c.is_whitespace()).collect();
+    let padding = (4 - cleaned.len() % 4) % 4;
+    cleaned.push_str(&"=".repeat(padding));
+    base64::decode(&cleaned).ok()
+}
+
+/// Q 编码类似 Quoted-Printable，`_` 表示空格
+fn decode_q_encoding(encoded: &str) -> Vec<u8> {
+    let bytes = encoded.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'_' => result.push(b' '),
+            b'=' if pos + 2 < bytes.len() => {
+                match (hex_value(bytes[pos + 1]), hex_value(bytes[pos + 2])) {
+                    (Some(high), Some(low)) => {
+                        result.push(high << 4 | low);
+                        pos += 2;
+                    }
+                    _ => result.push(b'='),
+                }
+            }
+            b => result.push(b),
+        }
+        pos += 1;
+    }
+    result
+}
+
+/// 按字符集名称解码字节，名称按 encoding_rs 的字符集表（WHATWG 编码标准）识别
+///
+/// gb2312/gbk 使用兼容 GB18030 的解码器；未知字符集或声明为 UTF-8 但内容不合法时，
+/// 先尝试 UTF-8，失败则按 GBK 解码
+pub fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    // RFC 2231 允许在字符集后附加语言（如 `utf-8*zh-cn`）
+    let label = charset.split('*').next().unwrap_or_default().trim();
+
+    match encoding_rs::Encoding::for_label(label.as_bytes()) {
+        Some(encoding) if encoding != encoding_rs::UTF_8 => {
+            let (decoded, _had_errors) = encoding.decode_without_bom_handling(bytes);
+            decoded.into_owned()
+        }
+        _ => match String::from_utf8(bytes.to_vec()) {
+            Ok(s) => s,
+            Err(_) => {
+                let (decoded, _encoding, _had_errors) = encoding_rs::GBK.decode(bytes);
+                decoded.into_owned()
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message() -> Vec<u8> {
+        let mut raw = concat!(
+            "From: Alice <alice@example.com>\r\n",
+            "Subject: =?UTF-8?B?5ZGo5oql?=\r\n",
+            "MIME-Version: 1.0\r\n",
+            "Content-Type: multipart/mixed;\r\n",
+            "\tboundary=\"mix\"\r\n",
+            "\r\n",
+            "This is a multi-part message in MIME format.\r\n",
+            "--mix\r\n",
+            "Content-Type: multipart/alternative; boundary=alt\r\n",
+            "\r\n",
+            "--alt\r\n",
+            "Content-Type: text/plain; charset=utf-8\r\n",
+            "\r\n",
+            "请查收附件。\r\n",
+            "--alt\r\n",
+            "Content-Type: text/html; charset=utf-8\r\n",
+            "Content-Transfer-Encoding: quoted-printable\r\n",
+            "\r\n",
+            "<p>=E8=AF=B7=E6=9F=A5=E6=94=B6</p>=\r\n",
+            "\r\n",
+            "--alt--\r\n",
+            "--mix\r\n",
+            "Content-Type: application/pdf\r\n",
+            "Content-Disposition: attachment;\r\n",
+            " filename*0*=UTF-8''%E5%91%A8%E6%8A%A5;\r\n",
+            " filename*1=\"-2026.pdf\"\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "JVBERi0x\r\n",
+            "LjQgdGVzdA==\r\n",
+            "--mix\r\n",
+            "Content-Type: image/png; name=\"=?UTF-8?B?5oiq5Zu+LnBuZw==?=\"\r\n",
+            "Content-ID: <shot@example.com>\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "iVBORw0K\r\n",
+            "--mix\r\n",
+            "Content-Type: application/octet-stream\r\n",
+            "Content-Disposition: attachment; filename=\"",
+        ).as_bytes().to_vec();
+        // 部分客户端直接写入 GBK 编码的文件名
+        raw.extend_from_slice(b"\xb1\xa8\xb8\xe6.doc\"\r\n\r\nDOC\r\n--mix--\r\n");
+        raw
+    }
+
+    #[test]
+    fn test_parse_part_tree() {
+        let message = MimePart::parse(&message());
+        assert_eq!(message.content_type, "multipart/mixed");
+        assert_eq!(message.header("subject"), Some("=?UTF-8?B?5ZGo5oql?="));
+        assert_eq!(message.children.len(), 4);
+
+        let ids: Vec<&str> = message.leaves().iter().map(|p| p.part_id.as_str()).collect();
+        assert_eq!(ids, vec!["1.1", "1.2", "2", "3", "4"]);
+
+        let plain = message.find("1.1").unwrap();
+        assert_eq!(plain.params.get("charset").map(String::as_str), Some("utf-8"));
+        assert_eq!(String::from_utf8(plain.decoded_body()).unwrap(), "请查收附件。");
+
+        let html = message.find("1.2").unwrap();
+        assert_eq!(String::from_utf8(html.decoded_body()).unwrap(), "<p>请查收</p>");
+
+        let image = message.find("3").unwrap();
+        assert_eq!(image.content_id.as_deref(), Some("shot@example.com"));
+    }
+
+    #[test]
+    fn test_attachments_with_encoded_filenames() {
+        let message = MimePart::parse(&message());
+        let attachments = message.attachments();
+
+        let names: Vec<(&str, &str)> = attachments
+            .iter()
+            .map(|a| (a.id.as_str(), a.filename.as_str()))
+            .collect();
+        assert_eq!(names, vec![("2", "周报-2026.pdf"), ("3", "截图.png"), ("4", "报告.doc")]);
+
+        assert_eq!(attachments[0].content_type, "application/pdf");
+        assert_eq!(attachments[0].size, 13);
+        assert_eq!(message.find("2").unwrap().decoded_body(), b"%PDF-1.4 test");
+    }
+
+    #[test]
+    fn test_single_part_and_missing_close_delimiter() {
+        let message = MimePart::parse(b"Subject: hi\n\nplain body\n");
+        assert_eq!(message.part_id, "1");
+        assert_eq!(message.content_type, "text/plain");
+        assert!(message.attachments().is_empty());
+
+        let truncated = MimePart::parse(
+            b"Content-Type: multipart/mixed; boundary=b\n\n--b\n\nfirst\n--b\nContent-Type: text/csv; name=a.csv\n\n1,2\n",
+        );
+        assert_eq!(truncated.children.len(), 2);
+        assert_eq!(truncated.children[1].body, b"1,2\n");
+        assert_eq!(truncated.attachments()[0].filename, "a.csv");
+    }
+
+    #[test]
+    fn test_decode_rfc2047_charsets() {
+        assert_eq!(decode_rfc2047("=?GBK?B?z+7Ev9bcsaijqLXaMtbco6k=?="), "项目周报（第2周）");
+        assert_eq!(decode_rfc2047("=?big5?B?tPq41Q==?="), "測試");
+        assert_eq!(decode_rfc2047("=?iso-8859-1?q?J=F6rg?= <joerg@example.de>"), "Jörg <joerg@example.de>");
+        // Q 编码的多字节 UTF-8
+        assert_eq!(decode_rfc2047("=?UTF-8?Q?=E5=91=A8=E6=8A=A5_2026?="), "周报 2026");
+        // 字符集后附加的语言（RFC 2231）和省略的填充
+        assert_eq!(decode_rfc2047("=?utf-8*zh?B?5ZGo5oql?="), "周报");
+        assert_eq!(decode_rfc2047("=?UTF-8?B?Y2Fmw6k?="), "café");
+    }
+
+    #[test]
+    fn test_decode_rfc2047_adjacent_words() {
+        // 多字节字符被拆到两个编码字中
+        assert_eq!(decode_rfc2047("=?UTF-8?B?5L2g5Q==?=\r\n =?UTF-8?B?pb0=?="), "你好");
+        // 不同字符集的相邻编码字之间的空白同样忽略，普通文本两侧的空白保留
+        assert_eq!(
+            decode_rfc2047("Re: =?gb2312?B?u9i4tA==?= =?utf-8?Q?caf=C3=A9?= ok"),
+            "Re: 回复café ok",
+        );
+        assert_eq!(decode_rfc2047("=?utf-8?B?5ZGo5oql?= (draft)"), "周报 (draft)");
+        // 无法解码的编码字保留原文
+        assert_eq!(decode_rfc2047("=?utf-8?B?!!!?= a=?b"), "=?utf-8?B?!!!?= a=?b");
+        assert_eq!(decode_rfc2047("=?utf-8?X?abc?="), "=?utf-8?X?abc?=");
+    }
+
+    /// 读取 tests/fixtures/mime 下的真实邮件样本
+    fn fixture(name: &str) -> MimePart {
+        let path = format!("{}/tests/fixtures/mime/{}", env!("CARGO_MANIFEST_DIR"), name);
+        MimePart::parse(&std::fs::read(path).unwrap())
+    }
+
+    fn leaf_ids(message: &MimePart) -> Vec<&str> {
+        message.leaves().iter().map(|p| p.part_id.as_str()).collect()
+    }
+
+    fn attachment_names(message: &MimePart) -> Vec<(String, String)> {
+        message.attachments().into_iter().map(|a| (a.id, a.filename)).collect()
+    }
+
+    #[test]
+    fn test_fixture_netease_gbk_alternative() {
+        let message = fixture("netease_gbk_alternative.eml");
+        let (plain, html) = message.body_text();
+
+        assert_eq!(plain.unwrap(), "李娜你好：\r\n\r\n本周完成了接口联调，详见附件。\r\n\r\n张伟");
+        assert!(html.unwrap().ends_with("<div>张伟</div>"));
+        assert_eq!(decode_rfc2047(message.header("Subject").unwrap()), "项目周报（第2周）");
+        assert_eq!(decode_rfc2047(message.header("From").unwrap()), "\"张伟\" <zhangwei@163.com>");
+        assert!(message.attachments().is_empty());
+    }
+
+    #[test]
+    fn test_fixture_outlook_related_inside_mixed() {
+        let message = fixture("outlook_related_mixed.eml");
+        assert_eq!(leaf_ids(&message), vec!["1.1.1", "1.1.2", "1.2", "2"]);
+
+        let (plain, html) = message.body_text();
+        assert!(plain.unwrap().contains("summarizes the changes."));
+        assert!(html.unwrap().contains("src=\"cid:image001.png@01DA1234.56789AB0\""));
+
+        let image = message.find("1.2").unwrap();
+        assert_eq!(image.content_id.as_deref(), Some("image001.png@01DA1234.56789AB0"));
+        assert!(image.decoded_body().starts_with(b"\x89PNG"));
+
+        // 正文引用的图片作为内嵌资源，不列为附件
+        assert_eq!(attachment_names(&message), vec![("2".to_string(), "Q1 Budget.xlsx".to_string())]);
+        let resources = message.inline_resources();
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0].part_id, "1.2");
+        assert_eq!(resources[0].content_type, "image/png");
+        assert_eq!(resources[0].size, 70);
+    }
+
+    #[test]
+    fn test_replace_cid_urls() {
+        let html = concat!(
+            "<img src=\"cid:logo@mail\"><img src='CID:missing'>",
+            "<div style=\"background:url(cid:bg%40mail)\">see cid:logo@mail</div>",
+        );
+        let replaced = replace_cid_urls(html, |id| match id {
+            "logo@mail" => Some(data_uri("image/png", b"png")),
+            "bg@mail" => Some("data:image/gif;base64,R0lG".to_string()),
+            _ => None,
+        });
+
+        assert_eq!(
+            replaced,
+            concat!(
+                "<img src=\"data:image/png;base64,cG5n\"><img src='CID:missing'>",
+                "<div style=\"background:url(data:image/gif;base64,R0lG)\">see cid:logo@mail</div>",
+            ),
+        );
+
+        // 邮件中的类型不能跳出 src 属性
+        assert_eq!(data_uri("image/svg+xml", b"png"), "data:image/svg+xml;base64,cG5n");
+        for bad in ["image/png\" onerror=\"alert(1)", "image/png'", "image", "image/", "text/html;charset=x"] {
+            assert_eq!(data_uri(bad, b"png"), "data:application/octet-stream;base64,cG5n", "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_fixture_forwarded_message() {
+        let message = fixture("forwarded_rfc822.eml");
+        let (plain, html) = message.body_text();
+
+        // 转发的邮件作为附件，不混入正文
+        assert_eq!(plain.unwrap(), "John, see the minutes below.");
+        assert!(html.is_none());
+        assert_eq!(attachment_names(&message), vec![("2".to_string(), "会议纪要.eml".to_string())]);
+
+        let forwarded = message.find("2").unwrap().message.as_deref().unwrap();
+        assert_eq!(forwarded.header("From"), Some("Li Na <li.na@example.com>"));
+        assert_eq!(message.find("2.1").unwrap().text(), "一、下周一上线。\n二、周三复盘。");
+        assert_eq!(forwarded.body_text().1.unwrap(), "<ol><li>下周一上线。</li><li>周三复盘。</li></ol>");
+    }
+
+    #[test]
+    fn test_fixture_qq_lf_line_endings() {
+        let message = fixture("qq_lf_gb18030_attachment.eml");
+        assert_eq!(message.body_text().0.unwrap(), "测试已通过，报告见附件。");
+        assert_eq!(decode_rfc2047(message.header("Subject").unwrap()), "测试报告");
+        assert_eq!(attachment_names(&message), vec![("2".to_string(), "测试报告.txt".to_string())]);
+        assert_eq!(message.find("2").unwrap().decoded_body(), b"ok\n");
+    }
+
+    #[test]
+    fn test_fixture_single_part_8bit() {
+        let message = fixture("plain_utf8_8bit.eml");
+        assert_eq!(leaf_ids(&message), vec!["1"]);
+
+        let (plain, html) = message.body_text();
+        assert!(plain.unwrap().contains("周五中午一起吃饭吗？\nReply by Thursday."));
+        assert!(html.is_none());
+    }
+}
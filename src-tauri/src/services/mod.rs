@@ -1,10 +1,34 @@
 // 服务层模块
 pub mod imap_service;
+pub mod imap_pool;
+pub mod imap_raw;
+pub mod mime;
+pub mod address;
 pub mod smtp_service;
 pub mod ai_service;
 pub mod storage_service;
+pub mod search_index;
+pub mod search_query;
+pub mod rule_engine;
+pub mod sieve_service;
+pub mod managesieve_service;
+pub mod sync_service;
+pub mod idle_service;
+#[cfg(test)]
+pub(crate) mod test_support;
 
 pub use imap_service::*;
+pub use imap_pool::*;
+pub use imap_raw::*;
+pub use mime::*;
+pub use address::*;
 pub use smtp_service::*;
 pub use ai_service::*;
 pub use storage_service::*;
+pub use search_index::*;
+pub use search_query::*;
+pub use rule_engine::*;
+pub use sieve_service::*;
+pub use managesieve_service::*;
+pub use sync_service::*;
+pub use idle_service::*;
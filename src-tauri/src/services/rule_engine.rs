@@ -0,0 +1,863 @@
+use crate::models::{
+    ConditionNode, Email, EmailSummary, FilterAction, FilterCondition, FilterField, FilterOperator,
+    FilterRule, MatchType,
+};
+use crate::services::ImapService;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 一条规则在一封邮件上的执行记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleExecution {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub uid: u32,
+    pub actions: Vec<FilterAction>,
+    pub errors: Vec<String>,
+}
+
+/// 对一批邮件执行规则后的结果
+#[derive(Debug, Clone)]
+pub struct RuleOutcome {
+    pub executions: Vec<RuleExecution>,
+    /// 执行后仍留在当前文件夹的邮件（已去掉被移动或删除的邮件）
+    pub remaining: Vec<EmailSummary>,
+}
+
+/// 试运行或追溯执行单条规则的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleRunResult {
+    pub dry_run: bool,
+    pub matched_uids: Vec<u32>,
+    pub executions: Vec<RuleExecution>,
+}
+
+/// 规则评估时看到的一封邮件
+pub struct RuleMessage<'a> {
+    pub account_id: &'a str,
+    pub folder: &'a str,
+    pub summary: &'a EmailSummary,
+    /// 邮件详情（收件人、邮件头、完整正文），可能尚未获取
+    pub detail: Option<&'a Email>,
+}
+
+/// 过滤规则引擎：对新邮件逐条评估已启用的规则并执行动作
+pub struct RuleEngine {
+    rules: Vec<FilterRule>,
+    /// 条件中的正则表达式，按表达式文本在创建引擎时编译一次
+    regexes: HashMap<String, Regex>,
+}
+
+impl RuleEngine {
+    pub fn new(rules: Vec<FilterRule>) -> Self {
+        let mut rules: Vec<FilterRule> = rules.into_iter().filter(|r| r.enabled).collect();
+        rules.sort_by_key(|r| r.priority);
+        Self::compile(rules)
+    }
+
+    /// 只包含单条规则的引擎，不论规则是否启用（用于试运行和追溯执行）
+    pub fn for_rule(rule: FilterRule) -> Self {
+        Self::compile(vec![rule])
+    }
+
+    /// 编译规则中的正则表达式，跳过无效的规则（如保存于校验之前的规则）
+    fn compile(rules: Vec<FilterRule>) -> Self {
+        let mut regexes = HashMap::new();
+        let rules = rules
+            .into_iter()
+            .filter(|rule| match Self::check_rule(rule, &mut regexes) {
+                Ok(()) => true,
+                Err(e) => {
+                    eprintln!("跳过无效的过滤规则 '{}': {}", rule.name, e);
+                    false
+                }
+            })
+            .collect();
+        Self { rules, regexes }
+    }
+
+    /// 检查规则是否有条件，以及条件中的正则表达式和数值是否有效
+    pub fn validate_rule(rule: &FilterRule) -> Result<(), String> {
+        Self::check_rule(rule, &mut HashMap::new())
+    }
+
+    /// 检查规则，并把编译好的正则表达式放入 `regexes`
+    fn check_rule(rule: &FilterRule, regexes: &mut HashMap<String, Regex>) -> Result<(), String> {
+        if rule.conditions.is_empty() {
+            return Err("规则至少需要一个条件".to_string());
+        }
+        rule.conditions.iter().try_for_each(|node| Self::check_node(node, regexes))
+    }
+
+    fn check_node(node: &ConditionNode, regexes: &mut HashMap<String, Regex>) -> Result<(), String> {
+        match node {
+            // 空的“全部满足”组会匹配所有邮件
+            ConditionNode::Group { conditions, .. } if conditions.is_empty() => {
+                Err("条件组不能为空".to_string())
+            }
+            ConditionNode::Group { conditions, .. } => {
+                conditions.iter().try_for_each(|node| Self::check_node(node, regexes))
+            }
+            ConditionNode::Not { not } => Self::check_node(not, regexes),
+            ConditionNode::Condition(condition) => match condition.operator {
+                FilterOperator::Regex if regexes.contains_key(&condition.value) => Ok(()),
+                FilterOperator::Regex => {
+                    let re = Regex::new(&condition.value)
+                        .map_err(|e| format!("正则表达式无效 '{}': {}", condition.value, e))?;
+                    regexes.insert(condition.value.clone(), re);
+                    Ok(())
+                }
+                FilterOperator::Before | FilterOperator::After => parse_date_value(&condition.value)
+                    .map(|_| ())
+                    .ok_or_else(|| format!("日期格式无效: {}", condition.value)),
+                FilterOperator::WithinDays => condition.value.trim().parse::<i64>()
+                    .map(|_| ())
+                    .map_err(|_| format!("天数无效: {}", condition.value)),
+                FilterOperator::GreaterThan | FilterOperator::LessThan => parse_size_value(&condition.value)
+                    .map(|_| ())
+                    .ok_or_else(|| format!("大小格式无效: {}", condition.value)),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    /// 试运行：返回匹配任意规则的邮件 UID，不执行任何动作
+    pub fn dry_run(
+        &self,
+        account_id: &str,
+        folder: &str,
+        emails: &[EmailSummary],
+        details: &HashMap<u32, Email>,
+    ) -> Vec<u32> {
+        emails
+            .iter()
+            .filter(|email| {
+                let message = RuleMessage {
+                    account_id,
+                    folder,
+                    summary: email,
+                    detail: details.get(&email.uid),
+                };
+                !self.matching_rules(&message).is_empty()
+            })
+            .map(|email| email.uid)
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 是否有规则依赖邮件详情（收件人、抄送、任意邮件头、完整正文或附件）
+    pub fn needs_detail(&self) -> bool {
+        fn node_needs_detail(node: &ConditionNode) -> bool {
+            match node {
+                ConditionNode::Group { conditions, .. } => conditions.iter().any(node_needs_detail),
+                ConditionNode::Not { not } => node_needs_detail(not),
+                ConditionNode::Condition(c) => matches!(
+                    c.field,
+                    FilterField::To
+                        | FilterField::Cc
+                        | FilterField::Header(_)
+                        | FilterField::Body
+                        | FilterField::HasAttachment
+                ),
+            }
+        }
+
+        self.rules
+            .iter()
+            .any(|rule| rule.conditions.iter().any(node_needs_detail))
+    }
+
+    /// 规则需要邮件详情时，为尚未取得详情的邮件补齐详情
+    ///
+    /// 使用 BODY.PEEK[] 读取，规则运行不会把新邮件标记为已读；单封读取失败只记录日志。
+    pub async fn load_details(
+        &self,
+        imap_service: &ImapService,
+        folder: &str,
+        emails: &[EmailSummary],
+        details: &mut HashMap<u32, Email>,
+    ) {
+        if !self.needs_detail() {
+            return;
+        }
+        for email in emails {
+            if details.contains_key(&email.uid) {
+                continue;
+            }
+            match imap_service.fetch_email_detail_peek(folder, email.uid).await {
+                Ok(detail) => {
+                    details.insert(email.uid, detail);
+                }
+                Err(e) => eprintln!("获取邮件详情失败 (UID: {}): {}", email.uid, e),
+            }
+        }
+    }
+
+    /// 按优先级返回匹配该邮件的规则，遇到“停止处理后续规则”的规则即截止
+    pub fn matching_rules(&self, message: &RuleMessage) -> Vec<&FilterRule> {
+        let mut matched = Vec::new();
+        for rule in self.rules.iter().filter(|rule| self.rule_matches(rule, message)) {
+            matched.push(rule);
+            if rule.stop_processing {
+                break;
+            }
+        }
+        matched
+    }
+
+    /// 规则需在作用范围内，并按组合方式评估顶层条件；没有条件的规则不匹配任何邮件
+    fn rule_matches(&self, rule: &FilterRule, message: &RuleMessage) -> bool {
+        rule.applies_to(message.account_id, message.folder)
+            && !rule.conditions.is_empty()
+            && self.group_matches(rule.match_type, &rule.conditions, message)
+    }
+
+    fn group_matches(&self, match_type: MatchType, nodes: &[ConditionNode], message: &RuleMessage) -> bool {
+        match match_type {
+            MatchType::All => nodes.iter().all(|n| self.node_matches(n, message)),
+            MatchType::Any => nodes.iter().any(|n| self.node_matches(n, message)),
+        }
+    }
+
+    fn node_matches(&self, node: &ConditionNode, message: &RuleMessage) -> bool {
+        match node {
+            ConditionNode::Group { group, conditions } => self.group_matches(*group, conditions, message),
+            ConditionNode::Not { not } => !self.node_matches(not, message),
+            ConditionNode::Condition(condition) => self.condition_matches(condition, message),
+        }
+    }
+
+    fn condition_matches(&self, condition: &FilterCondition, message: &RuleMessage) -> bool {
+        match condition.operator {
+            FilterOperator::Before | FilterOperator::After | FilterOperator::WithinDays => {
+                Self::date_matches(condition, message)
+            }
+            FilterOperator::GreaterThan | FilterOperator::LessThan => {
+                Self::size_matches(condition, message)
+            }
+            _ => self.text_matches(condition, message),
+        }
+    }
+
+    fn text_matches(&self, condition: &FilterCondition, message: &RuleMessage) -> bool {
+        let actual = Self::field_value(&condition.field, message);
+        let actual_lower = actual.to_lowercase();
+        let expected_lower = condition.value.to_lowercase();
+
+        match condition.operator {
+            FilterOperator::Contains => actual_lower.contains(&expected_lower),
+            FilterOperator::NotContains => !actual_lower.contains(&expected_lower),
+            FilterOperator::Equals => actual_lower.trim() == expected_lower.trim(),
+            FilterOperator::NotEquals => actual_lower.trim() != expected_lower.trim(),
+            FilterOperator::Regex => self.regexes
+                .get(&condition.value)
+                .is_some_and(|re| re.is_match(&actual)),
+            _ => false,
+        }
+    }
+
+    /// 日期条件：只对 Date 字段有效，邮件日期无法解析时不匹配
+    fn date_matches(condition: &FilterCondition, message: &RuleMessage) -> bool {
+        if condition.field != FilterField::Date {
+            return false;
+        }
+
+        let date = match DateTime::parse_from_rfc3339(&message.summary.date) {
+            Ok(dt) => dt.with_timezone(&Utc),
+            Err(_) => return false,
+        };
+
+        match condition.operator {
+            FilterOperator::Before => parse_date_value(&condition.value).is_some_and(|d| date < d),
+            FilterOperator::After => parse_date_value(&condition.value).is_some_and(|d| date > d),
+            FilterOperator::WithinDays => condition.value.trim().parse::<i64>()
+                .is_ok_and(|days| date >= Utc::now() - Duration::days(days)),
+            _ => false,
+        }
+    }
+
+    /// 大小条件：只对 Size 字段有效
+    fn size_matches(condition: &FilterCondition, message: &RuleMessage) -> bool {
+        if condition.field != FilterField::Size {
+            return false;
+        }
+
+        let size = message.detail
+            .map(|d| d.size)
+            .unwrap_or(message.summary.size);
+
+        match (condition.operator, parse_size_value(&condition.value)) {
+            (FilterOperator::GreaterThan, Some(limit)) => size > limit,
+            (FilterOperator::LessThan, Some(limit)) => size < limit,
+            _ => false,
+        }
+    }
+
+    /// 取出条件字段对应的邮件内容，优先使用邮件详情
+    fn field_value(field: &FilterField, message: &RuleMessage) -> String {
+        let summary = message.summary;
+        let detail = message.detail;
+
+        match field {
+            FilterField::From => detail
+                .map(|d| d.from.clone())
+                .unwrap_or_else(|| summary.from.clone()),
+            FilterField::To => detail
+                .map(|d| d.to.join(", "))
+                .unwrap_or_default(),
+            FilterField::Cc => detail
+                .map(|d| d.cc.join(", "))
+                .unwrap_or_default(),
+            FilterField::Subject => summary.subject.clone(),
+            FilterField::Body => detail
+                .map(|d| d.body.clone())
+                .unwrap_or_else(|| summary.body.clone()),
+            FilterField::Date => summary.date.clone(),
+            FilterField::Header(name) => detail
+                .and_then(|d| d.header(name))
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+            FilterField::Size => detail
+                .map(|d| d.size)
+                .unwrap_or(summary.size)
+                .to_string(),
+            FilterField::HasAttachment => detail
+                .map(|d| d.has_attachment)
+                .unwrap_or(summary.has_attachment)
+                .to_string(),
+            FilterField::Category => summary.category.clone().unwrap_or_default(),
+            FilterField::Folder => message.folder.to_string(),
+            FilterField::Account => message.account_id.to_string(),
+        }
+    }
+
+    /// 对文件夹中的一批邮件执行规则动作
+    ///
+    /// 邮件被移动或删除后不再执行后续动作，也不会出现在 `remaining` 中。
+    pub async fn apply(
+        &self,
+        imap_service: &ImapService,
+        account_id: &str,
+        folder: &str,
+        emails: Vec<EmailSummary>,
+        details: &HashMap<u32, Email>,
+    ) -> RuleOutcome {
+        let mut executions = Vec::new();
+        let mut remaining = Vec::new();
+
+        for mut email in emails {
+            let message = RuleMessage {
+                account_id,
+                folder,
+                summary: &email,
+                detail: details.get(&email.uid),
+            };
+            let matched: Vec<FilterRule> = self
+                .matching_rules(&message)
+                .into_iter()
+                .cloned()
+                .collect();
+
+            let mut removed = false;
+            for rule in matched {
+                let mut execution = RuleExecution {
+                    rule_id: rule.id.clone(),
+                    rule_name: rule.name.clone(),
+                    uid: email.uid,
+                    actions: Vec::new(),
+                    errors: Vec::new(),
+                };
+
+                for action in &rule.actions {
+                    let result = match action {
+                        FilterAction::MoveToFolder(dest) => imap_service
+                            .move_email(folder, email.uid, dest)
+                            .await
+                            .map(|_| removed = true),
+                        FilterAction::MarkAsRead => imap_service
+                            .mark_as_read(folder, email.uid)
+                            .await
+                            .map(|_| email.is_read = true),
+                        FilterAction::MarkAsStarred => imap_service
+                            .mark_as_starred(folder, email.uid)
+                            .await
+                            .map(|_| email.is_starred = true),
+                        FilterAction::Delete => imap_service
+                            .delete_email(folder, email.uid)
+                            .await
+                            .map(|_| removed = true),
+                        FilterAction::AddTag(tag) => {
+                            // 本地标签总是生效，服务器关键字仅在标签合法时同步
+                            if let Err(e) = imap_service.add_keyword(folder, email.uid, tag).await {
+                                eprintln!("同步标签到服务器失败 (UID: {}): {}", email.uid, e);
+                            }
+                            if !email.tags.contains(tag) {
+                                email.tags.push(tag.clone());
+                            }
+                            Ok(())
+                        }
+                    };
+
+                    match result {
+                        Ok(()) => execution.actions.push(action.clone()),
+                        Err(e) => execution.errors.push(e),
+                    }
+
+                    if removed {
+                        break;
+                    }
+                }
+
+                executions.push(execution);
+                if removed {
+                    break;
+                }
+            }
+
+            if !removed {
+                remaining.push(email);
+            }
+        }
+
+        RuleOutcome { executions, remaining }
+    }
+}
+
+/// 解析日期条件的值：YYYY-MM-DD（按 UTC 零点）或 RFC 3339
+pub(crate) fn parse_date_value(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return date.and_hms_opt(0, 0, 0).map(|dt| dt.and_utc());
+    }
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// 解析大小条件的值，支持 K/M/G（1024 进制）后缀，例如 "10M"、"500KB"
+pub(crate) fn parse_size_value(value: &str) -> Option<u64> {
+    let upper = value.trim().to_uppercase();
+    let number = upper.trim_end_matches('B');
+    let (digits, multiplier) = match number.chars().last()? {
+        'K' => (&number[..number.len() - 1], 1024),
+        'M' => (&number[..number.len() - 1], 1024 * 1024),
+        'G' => (&number[..number.len() - 1], 1024 * 1024 * 1024),
+        _ => (number, 1),
+    };
+    digits.trim().parse::<u64>().ok().map(|n| n * multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EmailHeader;
+    use crate::services::imap_service::tests::{inbox_uids, mailboxes, service, spawn_server};
+    use crate::services::test_support::{self, rule};
+    use std::sync::Arc;
+
+    fn summary(uid: u32, subject: &str, from: &str) -> EmailSummary {
+        EmailSummary { body: "本周的周报".to_string(), ..test_support::summary(uid, subject, from) }
+    }
+
+    fn detail(summary: &EmailSummary, to: &[&str], headers: &[(&str, &str)]) -> Email {
+        Email {
+            to: to.iter().map(|s| s.to_string()).collect(),
+            body: format!("{}\n\n附件见下方", summary.body),
+            headers: headers
+                .iter()
+                .map(|(name, value)| EmailHeader { name: name.to_string(), value: value.to_string() })
+                .collect(),
+            ..test_support::detail(summary)
+        }
+    }
+
+    fn condition(field: FilterField, operator: FilterOperator, value: &str) -> ConditionNode {
+        ConditionNode::Condition(FilterCondition { field, operator, value: value.to_string() })
+    }
+
+    fn rule_matches(rule: &FilterRule, message: &RuleMessage) -> bool {
+        !RuleEngine::for_rule(rule.clone()).matching_rules(message).is_empty()
+    }
+
+    fn matches(node: ConditionNode, summary: &EmailSummary, detail: Option<&Email>) -> bool {
+        let message = RuleMessage { account_id: "work", folder: "INBOX", summary, detail };
+        rule_matches(&rule("r", vec![node], Vec::new()), &message)
+    }
+
+    #[test]
+    fn test_text_conditions() {
+        let email = summary(1, "Weekly Report 第3周", "Alice <alice@example.com>");
+
+        assert!(matches(condition(FilterField::From, FilterOperator::Contains, "ALICE@"), &email, None));
+        assert!(matches(condition(FilterField::From, FilterOperator::NotContains, "bob"), &email, None));
+        assert!(matches(condition(FilterField::Subject, FilterOperator::Equals, " weekly report 第3周 "), &email, None));
+        assert!(!matches(condition(FilterField::Subject, FilterOperator::NotEquals, "Weekly Report 第3周"), &email, None));
+        assert!(matches(condition(FilterField::Subject, FilterOperator::Regex, r"^Weekly .* 第\d周$"), &email, None));
+        // 正则区分大小写，无效的正则不匹配
+        assert!(!matches(condition(FilterField::Subject, FilterOperator::Regex, "^weekly"), &email, None));
+        assert!(!matches(condition(FilterField::Subject, FilterOperator::Regex, "(unclosed"), &email, None));
+        assert!(matches(condition(FilterField::Folder, FilterOperator::Equals, "inbox"), &email, None));
+        assert!(matches(condition(FilterField::Account, FilterOperator::Equals, "work"), &email, None));
+        assert!(matches(condition(FilterField::HasAttachment, FilterOperator::Equals, "false"), &email, None));
+
+        // 没有条件的规则不匹配任何邮件
+        let message = RuleMessage { account_id: "work", folder: "INBOX", summary: &email, detail: None };
+        assert!(!rule_matches(&rule("empty", Vec::new(), Vec::new()), &message));
+    }
+
+    #[test]
+    fn test_detail_conditions() {
+        let mut email = summary(1, "Invoice", "billing@example.com");
+        email.has_attachment = false;
+        let mut full = detail(&email, &["Bob <bob@example.com>"], &[("List-Id", "<news.example.com>")]);
+        full.has_attachment = true;
+
+        let to_bob = || condition(FilterField::To, FilterOperator::Contains, "bob@");
+        let list = || condition(FilterField::Header("list-id".to_string()), FilterOperator::Contains, "news");
+        let with_attachment = || condition(FilterField::HasAttachment, FilterOperator::Equals, "true");
+        let full_body = || condition(FilterField::Body, FilterOperator::Contains, "附件见下方");
+
+        // 没有详情时收件人和邮件头为空，正文只检查摘要
+        for node in [to_bob(), list(), with_attachment(), full_body()] {
+            assert!(!matches(node.clone(), &email, None));
+            assert!(matches(node, &email, Some(&full)));
+        }
+
+        for (node, needs) in [
+            (to_bob(), true),
+            (list(), true),
+            (with_attachment(), true),
+            (full_body(), true),
+            (condition(FilterField::Subject, FilterOperator::Contains, "x"), false),
+            (ConditionNode::Not { not: Box::new(with_attachment()) }, true),
+        ] {
+            let engine = RuleEngine::new(vec![rule("r", vec![node.clone()], Vec::new())]);
+            assert_eq!(engine.needs_detail(), needs, "{:?}", node);
+        }
+    }
+
+    #[test]
+    fn test_legacy_condition_format() {
+        // 条件树之前保存的规则：条件是扁平的 {field, operator, value}，没有组合方式、优先级等字段
+        let json = r#"{
+            "id": "legacy",
+            "name": "旧规则",
+            "conditions": [
+                {"field": "From", "operator": "Contains", "value": "alice"},
+                {"field": {"Header": "List-Id"}, "operator": "Contains", "value": "news"}
+            ],
+            "actions": [{"MoveToFolder": "Archive"}, "MarkAsRead"],
+            "enabled": true
+        }"#;
+        let legacy: FilterRule = serde_json::from_str(json).unwrap();
+
+        assert_eq!(legacy.match_type, MatchType::All);
+        assert_eq!((legacy.priority, legacy.stop_processing), (0, false));
+        assert!(legacy.account_ids.is_empty() && legacy.folders.is_empty());
+        assert!(matches!(
+            &legacy.conditions[1],
+            ConditionNode::Condition(FilterCondition { field: FilterField::Header(name), .. }) if name == "List-Id"
+        ));
+
+        // 旧条件按“全部满足”评估
+        let email = summary(1, "Newsletter", "Alice <alice@example.com>");
+        let full = detail(&email, &[], &[("List-ID", "<news.example.com>")]);
+        let message = |detail| RuleMessage { account_id: "work", folder: "INBOX", summary: &email, detail };
+        assert!(rule_matches(&legacy, &message(Some(&full))));
+        assert!(!rule_matches(&legacy, &message(None)));
+
+        // 单个条件仍序列化为原来的格式
+        let value = serde_json::to_value(&legacy.conditions[0]).unwrap();
+        assert_eq!(value, serde_json::json!({"field": "From", "operator": "Contains", "value": "alice"}));
+    }
+
+    #[test]
+    fn test_nested_groups_and_not() {
+        // (发件人是 alice 或 bob) 且 不是 (主题含“广告” 且 没有附件)
+        let json = r#"[
+            {"group": "Any", "conditions": [
+                {"field": "From", "operator": "Contains", "value": "alice"},
+                {"field": "From", "operator": "Contains", "value": "bob"}
+            ]},
+            {"not": {"group": "All", "conditions": [
+                {"field": "Subject", "operator": "Contains", "value": "广告"},
+                {"field": "HasAttachment", "operator": "Equals", "value": "false"}
+            ]}}
+        ]"#;
+        let conditions: Vec<ConditionNode> = serde_json::from_str(json).unwrap();
+        assert!(matches!(&conditions[0], ConditionNode::Group { group: MatchType::Any, conditions } if conditions.len() == 2));
+        assert!(matches!(&conditions[1], ConditionNode::Not { not } if matches!(**not, ConditionNode::Group { .. })));
+
+        let mut rule = rule("nested", conditions, Vec::new());
+        let check = |rule: &FilterRule, email: &EmailSummary| {
+            rule_matches(rule, &RuleMessage { account_id: "work", folder: "INBOX", summary: email, detail: None })
+        };
+
+        let mut ad = summary(2, "广告：限时优惠", "bob@example.com");
+        assert!(check(&rule, &summary(1, "周报", "alice@example.com")));
+        assert!(!check(&rule, &summary(1, "周报", "carol@example.com")));
+        assert!(!check(&rule, &ad));
+        ad.has_attachment = true;
+        assert!(check(&rule, &ad));
+
+        // 顶层改为任一满足后，只要不是无附件的广告即可
+        rule.match_type = MatchType::Any;
+        assert!(check(&rule, &summary(3, "周报", "carol@example.com")));
+        ad.has_attachment = false;
+        assert!(check(&rule, &ad));
+    }
+
+    #[test]
+    fn test_date_and_size_conditions() {
+        let email = summary(1, "Report", "alice@example.com");
+        let date = |operator, value: &str| matches(condition(FilterField::Date, operator, value), &email, None);
+
+        // 邮件日期为 2026-03-02T09:00:00Z，日期值按 UTC 零点
+        assert!(date(FilterOperator::Before, "2026-03-03"));
+        assert!(!date(FilterOperator::Before, "2026-03-02"));
+        assert!(date(FilterOperator::After, "2026-03-02"));
+        assert!(!date(FilterOperator::After, "2026-03-02T10:00:00+00:00"));
+        assert!(date(FilterOperator::After, "2026-03-02T16:00:00+08:00"));
+        assert!(!date(FilterOperator::Before, "不是日期"));
+        // 日期运算符只作用于 Date 字段
+        assert!(!matches(condition(FilterField::Subject, FilterOperator::Before, "2030-01-01"), &email, None));
+
+        let mut recent = summary(2, "Report", "alice@example.com");
+        recent.date = (Utc::now() - Duration::days(2)).to_rfc3339();
+        let within = |email: &EmailSummary, days: &str| {
+            matches(condition(FilterField::Date, FilterOperator::WithinDays, days), email, None)
+        };
+        assert!(within(&recent, "7") && within(&recent, " 3 "));
+        assert!(!within(&recent, "1"));
+        // 无法解析的邮件日期不匹配
+        recent.date = "Mon, 01 Jan 2024".to_string();
+        assert!(!within(&recent, "100000"));
+
+        // 摘要大小为 2048 字节，有详情时以详情为准
+        let size = |operator, value: &str, detail: Option<&Email>| {
+            matches(condition(FilterField::Size, operator, value), &email, detail)
+        };
+        assert!(size(FilterOperator::GreaterThan, "1K", None));
+        assert!(!size(FilterOperator::GreaterThan, "2KB", None));
+        assert!(size(FilterOperator::LessThan, "1m", None));
+        assert!(!size(FilterOperator::LessThan, "2048", None));
+        let mut large = detail(&email, &[], &[]);
+        large.size = 5 * 1024 * 1024;
+        assert!(size(FilterOperator::GreaterThan, "4M", Some(&large)));
+        assert!(!size(FilterOperator::LessThan, "4M", Some(&large)));
+        assert!(!matches(condition(FilterField::Subject, FilterOperator::GreaterThan, "1"), &email, None));
+
+        assert_eq!(parse_size_value("10M"), Some(10 * 1024 * 1024));
+        assert_eq!(parse_size_value("500kb"), Some(500 * 1024));
+        assert_eq!(parse_size_value("B"), None);
+    }
+
+    #[test]
+    fn test_matching_rules_order_and_stop() {
+        let email = summary(1, "[ci] build failed", "ci@example.com");
+        let message = RuleMessage { account_id: "work", folder: "INBOX", summary: &email, detail: None };
+        let from_ci = || vec![condition(FilterField::From, FilterOperator::Contains, "ci@")];
+        let with_priority = |id: &str, priority: i32| {
+            let mut rule = rule(id, from_ci(), Vec::new());
+            rule.priority = priority;
+            rule
+        };
+        let ids = |engine: &RuleEngine| -> Vec<String> {
+            engine.matching_rules(&message).iter().map(|r| r.id.clone()).collect()
+        };
+
+        // 按优先级从小到大，优先级相同时保持原有顺序，未启用的规则被忽略
+        let mut disabled = with_priority("disabled", -1);
+        disabled.enabled = false;
+        let unmatched = rule("unmatched", vec![condition(FilterField::Subject, FilterOperator::Contains, "周报")], Vec::new());
+        let engine = RuleEngine::new(vec![
+            with_priority("c", 5),
+            with_priority("a", 1),
+            disabled,
+            unmatched,
+            with_priority("b1", 3),
+            with_priority("b2", 3),
+        ]);
+        assert_eq!(ids(&engine), vec!["a", "b1", "b2", "c"]);
+
+        // 匹配到“停止处理后续规则”的规则后截止，不匹配的停止规则不影响后续规则
+        let mut stop = with_priority("stop", 2);
+        stop.stop_processing = true;
+        let mut stop_unmatched = rule("stop-unmatched", vec![condition(FilterField::From, FilterOperator::Contains, "bob")], Vec::new());
+        stop_unmatched.stop_processing = true;
+        let engine = RuleEngine::new(vec![
+            with_priority("a", 1),
+            stop_unmatched,
+            stop,
+            with_priority("c", 3),
+        ]);
+        assert_eq!(ids(&engine), vec!["a", "stop"]);
+    }
+
+    #[test]
+    fn test_rule_scope() {
+        let email = summary(1, "[ci] build failed", "ci@example.com");
+        let mut scoped = rule("scoped", vec![condition(FilterField::From, FilterOperator::Contains, "ci@")], Vec::new());
+        scoped.account_ids = vec!["work".to_string()];
+        scoped.folders = vec!["INBOX".to_string(), "CI".to_string()];
+
+        assert!(scoped.applies_to("work", "INBOX") && scoped.applies_to("work", "CI"));
+        // 文件夹名称区分大小写
+        assert!(!scoped.applies_to("work", "inbox"));
+        assert!(!scoped.applies_to("home", "INBOX"));
+
+        let matched = |rule: &FilterRule, account_id, folder| {
+            let message = RuleMessage { account_id, folder, summary: &email, detail: None };
+            RuleEngine::new(vec![rule.clone()]).matching_rules(&message).len() == 1
+        };
+        assert!(matched(&scoped, "work", "CI"));
+        assert!(!matched(&scoped, "work", "Sent"));
+        assert!(!matched(&scoped, "home", "INBOX"));
+
+        // 为空表示所有账户或所有文件夹
+        scoped.account_ids.clear();
+        assert!(matched(&scoped, "home", "INBOX"));
+        scoped.folders.clear();
+        assert!(matched(&scoped, "home", "Sent"));
+    }
+
+    #[test]
+    fn test_validate_rule() {
+        let valid = rule("ok", vec![condition(FilterField::Subject, FilterOperator::Regex, r"^\[(ci|build)\]")], Vec::new());
+        assert!(RuleEngine::validate_rule(&valid).is_ok());
+
+        let invalid = |node: ConditionNode| RuleEngine::validate_rule(&rule("bad", vec![node], Vec::new()));
+        let err = invalid(condition(FilterField::Subject, FilterOperator::Regex, "(unclosed")).unwrap_err();
+        assert!(err.contains("正则表达式无效"), "{}", err);
+        // 嵌套在组和取反中的条件同样检查
+        let nested = ConditionNode::Group {
+            group: MatchType::Any,
+            conditions: vec![ConditionNode::Not {
+                not: Box::new(condition(FilterField::From, FilterOperator::Regex, "[a-")),
+            }],
+        };
+        assert!(invalid(nested).is_err());
+        assert!(invalid(condition(FilterField::Date, FilterOperator::Before, "下周")).is_err());
+        assert!(invalid(condition(FilterField::Date, FilterOperator::WithinDays, "7d")).is_err());
+        assert!(invalid(condition(FilterField::Size, FilterOperator::GreaterThan, "10X")).is_err());
+
+        // 没有条件的规则和空的条件组
+        let err = RuleEngine::validate_rule(&rule("empty", Vec::new(), Vec::new())).unwrap_err();
+        assert!(err.contains("至少需要一个条件"), "{}", err);
+        assert!(invalid(ConditionNode::Group { group: MatchType::All, conditions: Vec::new() }).is_err());
+
+        // 引擎跳过无效的规则，其余规则照常执行
+        let email = summary(1, "[ci] build failed", "ci@example.com");
+        let message = RuleMessage { account_id: "work", folder: "INBOX", summary: &email, detail: None };
+        let broken = rule("broken", vec![condition(FilterField::Subject, FilterOperator::Regex, "(unclosed")], Vec::new());
+        let engine = RuleEngine::new(vec![broken, valid]);
+        let matched: Vec<&str> = engine.matching_rules(&message).iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(matched, vec!["ok"]);
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let emails = vec![
+            summary(1, "[ci] build failed", "ci@example.com"),
+            summary(2, "午饭", "bob@example.com"),
+            summary(3, "[ci] build passed", "ci@example.com"),
+            summary(4, "周会", "alice@example.com"),
+        ];
+        let details: HashMap<u32, Email> = [(4, detail(&emails[3], &["team@example.com"], &[]))].into();
+
+        let from_ci = rule("ci", vec![condition(FilterField::From, FilterOperator::Contains, "ci@")], Vec::new());
+        let to_team = rule("team", vec![condition(FilterField::To, FilterOperator::Contains, "team@")], Vec::new());
+
+        // 匹配任意一条规则即计入，只有有详情的邮件能匹配收件人条件
+        let engine = RuleEngine::new(vec![from_ci.clone(), to_team]);
+        assert_eq!(engine.dry_run("work", "INBOX", &emails, &details), vec![1, 3, 4]);
+
+        // 试运行不考虑规则是否启用，但仍遵守作用范围
+        let mut disabled = from_ci;
+        disabled.enabled = false;
+        disabled.folders = vec!["INBOX".to_string()];
+        assert!(RuleEngine::new(vec![disabled.clone()]).dry_run("work", "INBOX", &emails, &details).is_empty());
+        assert_eq!(RuleEngine::for_rule(disabled.clone()).dry_run("work", "INBOX", &emails, &details), vec![1, 3]);
+        assert!(RuleEngine::for_rule(disabled).dry_run("work", "Sent", &emails, &details).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_apply_actions_and_remaining() {
+        let state = mailboxes(&[10, 20, 30, 40]);
+        let imap = service(spawn_server(Arc::clone(&state)));
+
+        let subject = |value: &str| condition(FilterField::Subject, FilterOperator::Contains, value);
+        let engine = RuleEngine::new(vec![
+            rule("archive", vec![subject("存档")], vec![FilterAction::MoveToFolder("Archive".to_string())]),
+            rule("read", vec![subject("通知")], vec![FilterAction::MarkAsRead, FilterAction::AddTag("work".to_string())]),
+            rule("delete", vec![subject("垃圾")], vec![FilterAction::Delete, FilterAction::MarkAsStarred]),
+        ]);
+
+        let emails = vec![
+            summary(10, "存档 通知", "a@example.com"),
+            summary(20, "系统通知", "a@example.com"),
+            summary(30, "垃圾广告", "a@example.com"),
+            summary(40, "你好", "a@example.com"),
+        ];
+        let outcome = engine.apply(&imap, "work", "INBOX", emails, &HashMap::new()).await;
+
+        // 移动后不再执行后续规则，删除后不再执行后续动作
+        let executed: Vec<(&str, u32, usize)> = outcome
+            .executions
+            .iter()
+            .map(|e| (e.rule_id.as_str(), e.uid, e.actions.len()))
+            .collect();
+        assert_eq!(executed, vec![("archive", 10, 1), ("read", 20, 2), ("delete", 30, 1)]);
+        assert!(outcome.executions.iter().all(|e| e.errors.is_empty()));
+
+        // 被移动或删除的邮件不在 remaining 中，其余邮件带上动作的结果
+        let remaining: Vec<(u32, bool, Vec<String>)> = outcome
+            .remaining
+            .iter()
+            .map(|e| (e.uid, e.is_read, e.tags.clone()))
+            .collect();
+        assert_eq!(remaining, vec![(20, true, vec!["work".to_string()]), (40, false, Vec::new())]);
+
+        assert_eq!(inbox_uids(&state), vec![20, 40]);
+        let state = state.lock().unwrap();
+        assert_eq!(state.folders["Archive"].len(), 1);
+        let flags = &state.folders["INBOX"][0].flags;
+        assert!(flags.contains(&"\\Seen".to_string()) && flags.contains(&"work".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_rule_details_do_not_mark_seen() {
+        let state = mailboxes(&[10, 20, 30]);
+        let imap = service(spawn_server(Arc::clone(&state)));
+
+        let engine = RuleEngine::new(vec![rule(
+            "body",
+            vec![condition(FilterField::Body, FilterOperator::Contains, "Body 20")],
+            vec![FilterAction::AddTag("work".to_string())],
+        )]);
+        let emails: Vec<EmailSummary> = [10, 20, 30]
+            .iter()
+            .map(|uid| summary(*uid, "", "alice@example.com"))
+            .collect();
+
+        let mut details = HashMap::new();
+        engine.load_details(&imap, "INBOX", &emails, &mut details).await;
+        assert_eq!(details.len(), 3);
+
+        let outcome = engine.apply(&imap, "work", "INBOX", emails, &details).await;
+        let matched: Vec<u32> = outcome.executions.iter().map(|e| e.uid).collect();
+        assert_eq!(matched, vec![20]);
+
+        // 读取正文不会把邮件标记为已读
+        let state = state.lock().unwrap();
+        assert!(state.folders["INBOX"]
+            .iter()
+            .all(|m| !m.flags.iter().any(|f| f == "\\Seen")));
+        assert_eq!(state.folders["INBOX"][1].flags, vec!["work".to_string()]);
+    }
+}
@@ -0,0 +1,324 @@
+use sled::Tree;
+use std::collections::{BTreeSet, HashSet};
+
+/// 索引词的最大长度（字符），过长的词（如长链接）截断后索引
+const MAX_TERM_CHARS: usize = 48;
+
+/// 被索引的邮件
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct IndexedDoc {
+    pub account_id: String,
+    pub folder: String,
+    pub uid: u32,
+}
+
+impl IndexedDoc {
+    fn from_key(key: &[u8]) -> Option<Self> {
+        let key = std::str::from_utf8(key).ok()?;
+        let mut parts = key.split('\0');
+        Some(Self {
+            account_id: parts.next()?.to_string(),
+            folder: parts.next()?.to_string(),
+            uid: parts.next()?.parse().ok()?,
+        })
+    }
+}
+
+fn doc_key(account_id: &str, folder: &str, uid: u32) -> Vec<u8> {
+    format!("{}\0{}\0{}", account_id, folder, uid).into_bytes()
+}
+
+/// 查询中的一个词，prefix 为 true 时匹配以它开头的索引词
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryTerm {
+    pub term: String,
+    pub prefix: bool,
+}
+
+/// 本地全文索引（倒排索引），保存在 sled 中，离线时也可搜索
+///
+/// postings: `词\0账户\0文件夹\0UID` → 空
+/// documents: `账户\0文件夹\0UID` → 该邮件已索引的词（JSON），用于删除
+pub struct SearchIndex {
+    postings: Tree,
+    documents: Tree,
+}
+
+impl SearchIndex {
+    pub fn new(postings: Tree, documents: Tree) -> Self {
+        Self { postings, documents }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// 将文本加入邮件的索引；邮件内容不会变化，摘要和详情分别写入时索引词取并集
+    pub fn index(&self, account_id: &str, folder: &str, uid: u32, text: &str) -> Result<(), String> {
+        let key = doc_key(account_id, folder, uid);
+        let mut terms = self.document_terms(&key)?;
+        let before = terms.len();
+        terms.extend(tokenize(text));
+        if terms.len() == before && before > 0 {
+            return Ok(());
+        }
+
+        for term in &terms {
+            self.postings
+                .insert(posting_key(term, &key), Vec::new())
+                .map_err(|e| format!("写入搜索索引失败: {}", e))?;
+        }
+
+        let value = serde_json::to_vec(&terms)
+            .map_err(|e| format!("序列化搜索索引失败: {}", e))?;
+        self.documents
+            .insert(key, value)
+            .map_err(|e| format!("写入搜索索引失败: {}", e))?;
+
+        Ok(())
+    }
+
+    /// 从索引中移除一封邮件
+    pub fn remove(&self, account_id: &str, folder: &str, uid: u32) -> Result<(), String> {
+        self.remove_key(&doc_key(account_id, folder, uid))
+    }
+
+    /// 移除文件夹中全部邮件的索引
+    pub fn remove_folder(&self, account_id: &str, folder: &str) -> Result<(), String> {
+        let prefix = format!("{}\0{}\0", account_id, folder);
+        let keys: Vec<Vec<u8>> = self.documents
+            .scan_prefix(prefix.as_bytes())
+            .map(|item| item.map(|(k, _)| k.to_vec()))
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("扫描搜索索引失败: {}", e))?;
+
+        for key in keys {
+            self.remove_key(&key)?;
+        }
+        Ok(())
+    }
+
+    /// 查找包含全部查询词的邮件，account_id 为 None 时搜索所有账户
+    pub fn search(&self, query: &str, account_id: Option<&str>) -> Result<Vec<IndexedDoc>, String> {
+        let terms = query_terms(query);
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut matched: Option<HashSet<Vec<u8>>> = None;
+        for term in &terms {
+            let docs = self.lookup(term)?;
+            matched = Some(match matched {
+                Some(previous) => previous.intersection(&docs).cloned().collect(),
+                None => docs,
+            });
+            if matched.as_ref().is_some_and(HashSet::is_empty) {
+                break;
+            }
+        }
+
+        let mut docs: Vec<IndexedDoc> = matched
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|key| IndexedDoc::from_key(key))
+            .filter(|doc| account_id.is_none_or(|id| doc.account_id == id))
+            .collect();
+        docs.sort();
+        Ok(docs)
+    }
+
+    fn lookup(&self, term: &QueryTerm) -> Result<HashSet<Vec<u8>>, String> {
+        let mut prefix = term.term.as_bytes().to_vec();
+        if !term.prefix {
+            prefix.push(0);
+        }
+
+        let mut docs = HashSet::new();
+        for item in self.postings.scan_prefix(&prefix) {
+            let (key, _) = item.map_err(|e| format!("读取搜索索引失败: {}", e))?;
+            if let Some(split) = key.iter().position(|b| *b == 0) {
+                docs.insert(key[split + 1..].to_vec());
+            }
+        }
+        Ok(docs)
+    }
+
+    fn document_terms(&self, key: &[u8]) -> Result<BTreeSet<String>, String> {
+        match self.documents.get(key).map_err(|e| format!("读取搜索索引失败: {}", e))? {
+            Some(value) => serde_json::from_slice(&value)
+                .map_err(|e| format!("反序列化搜索索引失败: {}", e)),
+            None => Ok(BTreeSet::new()),
+        }
+    }
+
+    fn remove_key(&self, key: &[u8]) -> Result<(), String> {
+        for term in self.document_terms(key)? {
+            self.postings
+                .remove(posting_key(&term, key))
+                .map_err(|e| format!("删除搜索索引失败: {}", e))?;
+        }
+        self.documents
+            .remove(key)
+            .map_err(|e| format!("删除搜索索引失败: {}", e))?;
+        Ok(())
+    }
+}
+
+fn posting_key(term: &str, doc_key: &[u8]) -> Vec<u8> {
+    let mut key = term.as_bytes().to_vec();
+    key.push(0);
+    key.extend_from_slice(doc_key);
+    key
+}
+
+/// 中日韩文字：没有空格分词，按单字和相邻两字（bigram）索引
+fn is_cjk(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x3040..=0x30FF       // 平假名、片假名
+            | 0x3400..=0x4DBF // 扩展 A
+            | 0x4E00..=0x9FFF // 基本汉字
+            | 0xAC00..=0xD7AF // 韩文音节
+            | 0xF900..=0xFAFF // 兼容汉字
+            | 0x20000..=0x2FA1F
+    )
+}
+
+/// 文本中的连续片段：拉丁字母/数字组成的词或一段中日韩文字
+enum Segment {
+    Word(String),
+    Cjk(Vec<char>),
+}
+
+fn segments(text: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut word = String::new();
+    let mut cjk = Vec::new();
+
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                segments.push(Segment::Word(std::mem::take(&mut word)));
+            }
+            cjk.push(c);
+        } else {
+            if !cjk.is_empty() {
+                segments.push(Segment::Cjk(std::mem::take(&mut cjk)));
+            }
+            if c.is_alphanumeric() {
+                word.push(c);
+            } else if !word.is_empty() {
+                segments.push(Segment::Word(std::mem::take(&mut word)));
+            }
+        }
+    }
+
+    if !word.is_empty() {
+        segments.push(Segment::Word(word));
+    }
+    if !cjk.is_empty() {
+        segments.push(Segment::Cjk(cjk));
+    }
+    segments
+}
+
+/// 将文本切分为索引词：拉丁文字按词，中日韩文字同时产生单字和 bigram
+pub fn tokenize(text: &str) -> BTreeSet<String> {
+    let mut terms = BTreeSet::new();
+    for segment in segments(text) {
+        match segment {
+            Segment::Word(word) => {
+                terms.insert(word.chars().take(MAX_TERM_CHARS).collect());
+            }
+            Segment::Cjk(chars) => {
+                terms.extend(chars.iter().map(|c| c.to_string()));
+                terms.extend(chars.windows(2).map(|pair| pair.iter().collect::<String>()));
+            }
+        }
+    }
+    terms
+}
+
+/// 将查询切分为查询词
+/// 拉丁文字的词按前缀匹配（输入过程中即可得到结果）；中文单字按单字匹配，多字按相邻两字匹配
+pub fn query_terms(query: &str) -> Vec<QueryTerm> {
+    let mut terms = Vec::new();
+    for segment in segments(query) {
+        match segment {
+            Segment::Word(word) => terms.push(QueryTerm {
+                term: word.chars().take(MAX_TERM_CHARS).collect(),
+                prefix: true,
+            }),
+            Segment::Cjk(chars) if chars.len() == 1 => terms.push(QueryTerm {
+                term: chars[0].to_string(),
+                prefix: false,
+            }),
+            Segment::Cjk(chars) => {
+                terms.extend(chars.windows(2).map(|pair| QueryTerm {
+                    term: pair.iter().collect(),
+                    prefix: false,
+                }));
+            }
+        }
+    }
+    terms.dedup();
+    terms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> SearchIndex {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        SearchIndex::new(db.open_tree("postings").unwrap(), db.open_tree("documents").unwrap())
+    }
+
+    fn uids(docs: &[IndexedDoc]) -> Vec<u32> {
+        docs.iter().map(|doc| doc.uid).collect()
+    }
+
+    #[test]
+    fn test_tokenize_mixed_text() {
+        let terms = tokenize("Q3季度报告 sent by Alice@Example.com");
+        for term in ["q3", "季", "季度", "度报", "报告", "sent", "alice", "example", "com"] {
+            assert!(terms.contains(term), "missing {}", term);
+        }
+        assert!(!terms.contains("季度报告"));
+    }
+
+    #[test]
+    fn test_search_cjk_and_prefix() {
+        let index = index();
+        index.index("a1", "INBOX", 1, "关于第三季度报告的讨论").unwrap();
+        index.index("a1", "INBOX", 2, "季度预算 invoice").unwrap();
+        index.index("a2", "Sent", 3, "Invoice for 报告").unwrap();
+
+        assert_eq!(uids(&index.search("季度报告", None).unwrap()), vec![1]);
+        assert_eq!(uids(&index.search("季度", None).unwrap()), vec![1, 2]);
+        assert_eq!(uids(&index.search("报", None).unwrap()), vec![1, 3]);
+        assert_eq!(uids(&index.search("inv", None).unwrap()), vec![2, 3]);
+        assert_eq!(uids(&index.search("inv", Some("a2")).unwrap()), vec![3]);
+        assert!(index.search("季度 invoice 报告", None).unwrap().is_empty());
+        assert!(index.search("  ", None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_index_updates_and_removal() {
+        let index = index();
+        index.index("a1", "INBOX", 1, "周报").unwrap();
+        // 缓存详情后正文中的词同样可搜索
+        index.index("a1", "INBOX", 1, "附件在这里").unwrap();
+        index.index("a1", "INBOX", 2, "周报").unwrap();
+        index.index("a1", "Archive", 3, "周报").unwrap();
+
+        assert_eq!(uids(&index.search("周报 附件", None).unwrap()), vec![1]);
+
+        index.remove("a1", "INBOX", 2).unwrap();
+        assert_eq!(uids(&index.search("周报", None).unwrap()), vec![3, 1]);
+
+        index.remove_folder("a1", "INBOX").unwrap();
+        assert_eq!(uids(&index.search("周报", None).unwrap()), vec![3]);
+        assert!(index.search("附件", None).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,631 @@
+use crate::models::{Email, EmailSummary, SearchCriteria, SearchQuery, SearchTerm};
+use crate::services::imap_raw::quote;
+use chrono::NaiveDate;
+
+/// 词法单元
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    LParen,
+    RParen,
+    Or,
+    Not,
+    /// 字段（如 from）和内容；不带字段的词 field 为 None
+    Word { field: Option<String>, value: String },
+}
+
+/// 支持的字段名
+const FIELDS: &[&str] = &[
+    "from", "to", "cc", "subject", "body", "has", "is", "before", "after", "since",
+    "larger", "smaller", "folder", "in", "tag", "label",
+];
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c == '(' {
+            tokens.push(Token::LParen);
+            pos += 1;
+        } else if c == ')' {
+            tokens.push(Token::RParen);
+            pos += 1;
+        } else if c == '-' && chars.get(pos + 1).is_some_and(|n| !n.is_whitespace()) {
+            tokens.push(Token::Not);
+            pos += 1;
+        } else if c == '"' {
+            let (value, next) = read_quoted(&chars, pos)?;
+            tokens.push(Token::Word { field: None, value });
+            pos = next;
+        } else {
+            let start = pos;
+            while pos < chars.len() && !chars[pos].is_whitespace() && !matches!(chars[pos], '(' | ')' | ':') {
+                pos += 1;
+            }
+            let word: String = chars[start..pos].iter().collect();
+
+            // 已知字段后跟冒号时读取字段内容，否则冒号作为普通字符（如时间 10:30）
+            let field = word.to_lowercase();
+            if chars.get(pos) == Some(&':') && FIELDS.contains(&field.as_str()) {
+                pos += 1;
+                let value = if chars.get(pos) == Some(&'"') {
+                    let (value, next) = read_quoted(&chars, pos)?;
+                    pos = next;
+                    value
+                } else {
+                    let start = pos;
+                    while pos < chars.len() && !chars[pos].is_whitespace() && !matches!(chars[pos], '(' | ')') {
+                        pos += 1;
+                    }
+                    chars[start..pos].iter().collect()
+                };
+                tokens.push(Token::Word { field: Some(field), value });
+                continue;
+            }
+
+            while pos < chars.len() && !chars[pos].is_whitespace() && !matches!(chars[pos], '(' | ')') {
+                pos += 1;
+            }
+            let word: String = chars[start..pos].iter().collect();
+            if word == "OR" || word == "|" {
+                tokens.push(Token::Or);
+            } else {
+                tokens.push(Token::Word { field: None, value: word });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// 读取从 start（引号处）开始的带引号短语，支持 `\"` 转义，返回内容和结束后的位置
+fn read_quoted(chars: &[char], start: usize) -> Result<(String, usize), String> {
+    let mut value = String::new();
+    let mut pos = start + 1;
+    while pos < chars.len() {
+        match chars[pos] {
+            '\\' if pos + 1 < chars.len() => {
+                value.push(chars[pos + 1]);
+                pos += 2;
+            }
+            '"' => return Ok((value, pos + 1)),
+            c => {
+                value.push(c);
+                pos += 1;
+            }
+        }
+    }
+    Err("引号未闭合".to_string())
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    /// 连续的条件之间为“与”关系
+    fn and_expr(&mut self) -> Result<SearchQuery, String> {
+        let mut terms = Vec::new();
+        while let Some(token) = self.peek() {
+            match token {
+                Token::RParen => break,
+                Token::Or => return Err("OR 前缺少条件".to_string()),
+                _ => terms.push(self.or_expr()?),
+            }
+        }
+        Ok(combine(terms, SearchQuery::And))
+    }
+
+    fn or_expr(&mut self) -> Result<SearchQuery, String> {
+        let mut terms = vec![self.unary()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            if matches!(self.peek(), None | Some(Token::RParen) | Some(Token::Or)) {
+                return Err("OR 后缺少条件".to_string());
+            }
+            terms.push(self.unary()?);
+        }
+        Ok(combine(terms, SearchQuery::Or))
+    }
+
+    fn unary(&mut self) -> Result<SearchQuery, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or("查询意外结束")?;
+        self.pos += 1;
+
+        match token {
+            Token::Not => Ok(SearchQuery::Not(Box::new(self.unary()?))),
+            Token::LParen => {
+                let inner = self.and_expr()?;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err("括号未闭合".to_string());
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Token::RParen => Err("多余的右括号".to_string()),
+            Token::Or => Err("OR 前缺少条件".to_string()),
+            Token::Word { field, value } => parse_term(field.as_deref(), &value).map(SearchQuery::Term),
+        }
+    }
+}
+
+fn combine(mut terms: Vec<SearchQuery>, group: fn(Vec<SearchQuery>) -> SearchQuery) -> SearchQuery {
+    match terms.len() {
+        0 => SearchQuery::All,
+        1 => terms.remove(0),
+        _ => group(terms),
+    }
+}
+
+fn parse_term(field: Option<&str>, value: &str) -> Result<SearchTerm, String> {
+    let field = match field {
+        Some(field) => field,
+        None => return Ok(SearchTerm::Text(value.to_string())),
+    };
+    if value.trim().is_empty() {
+        return Err(format!("条件 {}: 缺少内容", field));
+    }
+
+    let value = value.trim();
+    let term = match field {
+        "from" => SearchTerm::From(value.to_string()),
+        "to" => SearchTerm::To(value.to_string()),
+        "cc" => SearchTerm::Cc(value.to_string()),
+        "subject" => SearchTerm::Subject(value.to_string()),
+        "body" => SearchTerm::Body(value.to_string()),
+        "has" => match value.to_lowercase().as_str() {
+            "attachment" | "attachments" => SearchTerm::HasAttachment,
+            _ => return Err(format!("不支持的条件 has:{}", value)),
+        },
+        "is" => match value.to_lowercase().as_str() {
+            "read" => SearchTerm::Read,
+            "unread" => SearchTerm::Unread,
+            "starred" | "flagged" => SearchTerm::Starred,
+            "unstarred" | "unflagged" => SearchTerm::Unstarred,
+            _ => return Err(format!("不支持的条件 is:{}", value)),
+        },
+        "before" => SearchTerm::Before(parse_date(value)?),
+        "after" | "since" => SearchTerm::After(parse_date(value)?),
+        "larger" => SearchTerm::Larger(parse_size(value)?),
+        "smaller" => SearchTerm::Smaller(parse_size(value)?),
+        "folder" | "in" => SearchTerm::Folder(value.to_string()),
+        "tag" | "label" => SearchTerm::Tag(value.to_string()),
+        _ => return Err(format!("不支持的字段: {}", field)),
+    };
+    Ok(term)
+}
+
+/// 日期格式为 YYYY-MM-DD 或 YYYY/MM/DD
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y/%m/%d"))
+        .map_err(|_| format!("无效的日期: {}", value))
+}
+
+/// 大小可带 K/M 后缀（如 500K、2M）
+fn parse_size(value: &str) -> Result<u64, String> {
+    let upper = value.to_uppercase();
+    let (number, unit) = if let Some(n) = upper.strip_suffix('M') {
+        (n, 1024 * 1024)
+    } else if let Some(n) = upper.strip_suffix('K') {
+        (n, 1024)
+    } else {
+        (upper.as_str(), 1)
+    };
+    number
+        .parse::<u64>()
+        .map(|n| n.saturating_mul(unit))
+        .map_err(|_| format!("无效的大小: {}", value))
+}
+
+fn contains(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+impl SearchQuery {
+    /// 解析搜索框中的查询语句
+    ///
+    /// 支持 `字段:内容`（内容含空格时加引号）、不带字段的词、`-` 取反、`OR` 和括号，
+    /// 连续的条件之间为“与”关系
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut parser = Parser { tokens: tokenize(input)?, pos: 0 };
+        let query = parser.and_expr()?;
+        if parser.pos < parser.tokens.len() {
+            return Err("多余的右括号".to_string());
+        }
+        Ok(query)
+    }
+
+    /// 由搜索表单的条件生成查询，各条件之间为“与”关系，没有条件时搜索全部
+    pub fn from_criteria(criteria: &SearchCriteria) -> Result<Self, String> {
+        let value = |value: &Option<String>| {
+            value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+        };
+        let date = |value: &Option<String>| -> Result<Option<NaiveDate>, String> {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(|v| NaiveDate::parse_from_str(v, "%Y-%m-%d").map_err(|_| format!("无效的日期: {}", v)))
+                .transpose()
+        };
+
+        let mut terms: Vec<SearchTerm> = [
+            value(&criteria.from).map(SearchTerm::From),
+            value(&criteria.to).map(SearchTerm::To),
+            value(&criteria.subject).map(SearchTerm::Subject),
+            value(&criteria.body).map(SearchTerm::Body),
+            value(&criteria.text).map(SearchTerm::Text),
+            date(&criteria.since)?.map(SearchTerm::After),
+            date(&criteria.before)?.map(SearchTerm::Before),
+            criteria.seen.map(|seen| if seen { SearchTerm::Read } else { SearchTerm::Unread }),
+            criteria.flagged.map(|flagged| if flagged { SearchTerm::Starred } else { SearchTerm::Unstarred }),
+            criteria.larger.map(|size| SearchTerm::Larger(size.into())),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        Ok(match terms.len() {
+            0 => SearchQuery::All,
+            1 => SearchQuery::Term(terms.remove(0)),
+            _ => SearchQuery::And(terms.into_iter().map(SearchQuery::Term).collect()),
+        })
+    }
+
+    /// 查询限定的文件夹；folder: 只能出现在顶层的“与”条件中
+    pub fn folders(&self) -> Result<Vec<String>, String> {
+        let top: &[SearchQuery] = match self {
+            SearchQuery::And(terms) => terms,
+            other => std::slice::from_ref(other),
+        };
+
+        let mut folders = Vec::new();
+        for term in top {
+            match term {
+                SearchQuery::Term(SearchTerm::Folder(folder)) => folders.push(folder.clone()),
+                other if other.has_folder() => return Err("folder: 不能用于 OR 或取反条件".to_string()),
+                _ => {}
+            }
+        }
+        Ok(folders)
+    }
+
+    fn has_folder(&self) -> bool {
+        match self {
+            SearchQuery::All => false,
+            SearchQuery::And(terms) | SearchQuery::Or(terms) => terms.iter().any(Self::has_folder),
+            SearchQuery::Not(term) => term.has_folder(),
+            SearchQuery::Term(term) => matches!(term, SearchTerm::Folder(_)),
+        }
+    }
+
+    /// 是否需要邮件详情（收件人、抄送、完整正文或附件）才能判断
+    pub fn needs_detail(&self) -> bool {
+        match self {
+            SearchQuery::All => false,
+            SearchQuery::And(terms) | SearchQuery::Or(terms) => terms.iter().any(Self::needs_detail),
+            SearchQuery::Not(term) => term.needs_detail(),
+            SearchQuery::Term(term) => matches!(
+                term,
+                SearchTerm::Text(_)
+                    | SearchTerm::To(_)
+                    | SearchTerm::Cc(_)
+                    | SearchTerm::Body(_)
+                    | SearchTerm::HasAttachment
+            ),
+        }
+    }
+
+    /// 判断缓存中的邮件是否匹配；没有详情时收件人和抄送条件不匹配，正文只检查摘要中的部分
+    pub fn matches(&self, folder: &str, email: &EmailSummary, detail: Option<&Email>) -> bool {
+        match self {
+            SearchQuery::All => true,
+            SearchQuery::And(terms) => terms.iter().all(|t| t.matches(folder, email, detail)),
+            SearchQuery::Or(terms) => terms.iter().any(|t| t.matches(folder, email, detail)),
+            SearchQuery::Not(term) => !term.matches(folder, email, detail),
+            SearchQuery::Term(term) => term_matches(term, folder, email, detail),
+        }
+    }
+
+    /// 转换为 UID SEARCH 参数，folder: 条件由调用方选择文件夹，这里视为全部匹配
+    ///
+    /// 含中文等非 ASCII 字符时声明 CHARSET UTF-8，这些字符串以字面量发送：服务器支持 LITERAL+（RFC 7888）时
+    /// 为非同步字面量 `{n+}`，否则为同步字面量 `{n}`，需要用 `RawImapConnection::run` 等服务器的继续请求后发送
+    ///
+    /// IMAP SEARCH 没有附件条件，含 has:attachment 时返回错误，
+    /// 先用 `with_attachment` 分别代入有、无附件两种情况
+    pub fn to_imap(&self, literal_plus: bool) -> Result<String, String> {
+        let mut non_ascii = false;
+        let query = match self {
+            // 顶层的“与”条件不需要括号
+            SearchQuery::And(terms) => terms
+                .iter()
+                .map(|t| t.imap_key(literal_plus, &mut non_ascii))
+                .collect::<Result<Vec<_>, _>>()?
+                .join(" "),
+            other => other.imap_key(literal_plus, &mut non_ascii)?,
+        };
+        Ok(if non_ascii { format!("CHARSET UTF-8 {}", query) } else { query })
+    }
+
+    /// 是否含 has:attachment 条件
+    pub fn has_attachment_term(&self) -> bool {
+        match self {
+            SearchQuery::All => false,
+            SearchQuery::And(terms) | SearchQuery::Or(terms) => terms.iter().any(Self::has_attachment_term),
+            SearchQuery::Not(term) => term.has_attachment_term(),
+            SearchQuery::Term(term) => matches!(term, SearchTerm::HasAttachment),
+        }
+    }
+
+    /// 把 has:attachment 条件代入为成立或不成立，得到可以在服务器端搜索的查询；
+    /// 邮件是否有附件由 BODYSTRUCTURE 判断后，从对应的结果中选取
+    pub fn with_attachment(&self, has_attachment: bool) -> SearchQuery {
+        match self {
+            SearchQuery::And(terms) => SearchQuery::And(terms.iter().map(|t| t.with_attachment(has_attachment)).collect()),
+            SearchQuery::Or(terms) => SearchQuery::Or(terms.iter().map(|t| t.with_attachment(has_attachment)).collect()),
+            SearchQuery::Not(term) => SearchQuery::Not(Box::new(term.with_attachment(has_attachment))),
+            SearchQuery::Term(SearchTerm::HasAttachment) if has_attachment => SearchQuery::All,
+            SearchQuery::Term(SearchTerm::HasAttachment) => SearchQuery::Not(Box::new(SearchQuery::All)),
+            other => other.clone(),
+        }
+    }
+
+    fn imap_key(&self, literal_plus: bool, non_ascii: &mut bool) -> Result<String, String> {
+        match self {
+            SearchQuery::All => Ok("ALL".to_string()),
+            SearchQuery::And(terms) => {
+                let keys = terms
+                    .iter()
+                    .map(|t| t.imap_key(literal_plus, non_ascii))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(format!("({})", keys.join(" ")))
+            }
+            SearchQuery::Or(terms) => {
+                // IMAP 的 OR 只接受两个条件，多个条件向右嵌套
+                let mut keys = terms
+                    .iter()
+                    .map(|t| t.imap_key(literal_plus, non_ascii))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut key = keys.pop().unwrap_or_else(|| "ALL".to_string());
+                while let Some(left) = keys.pop() {
+                    key = format!("OR {} {}", left, key);
+                }
+                Ok(key)
+            }
+            SearchQuery::Not(term) => Ok(format!("NOT {}", term.imap_key(literal_plus, non_ascii)?)),
+            SearchQuery::Term(term) => term_imap_key(term, literal_plus, non_ascii),
+        }
+    }
+}
+
+fn term_matches(term: &SearchTerm, folder: &str, email: &EmailSummary, detail: Option<&Email>) -> bool {
+    let body_matches = |value: &str| match detail {
+        Some(detail) => contains(&detail.body, value),
+        None => contains(&email.body, value) || contains(&email.preview, value),
+    };
+    let recipients = |select: fn(&Email) -> &Vec<String>, value: &str| {
+        detail.is_some_and(|detail| select(detail).iter().any(|addr| contains(addr, value)))
+    };
+    let date = || {
+        chrono::DateTime::parse_from_rfc3339(&email.date)
+            .ok()
+            .map(|date| date.date_naive())
+    };
+
+    match term {
+        SearchTerm::Text(value) => {
+            contains(&email.subject, value)
+                || contains(&email.from, value)
+                || body_matches(value)
+                || recipients(|d| &d.to, value)
+                || recipients(|d| &d.cc, value)
+        }
+        SearchTerm::From(value) => contains(&email.from, value),
+        SearchTerm::To(value) => recipients(|d| &d.to, value),
+        SearchTerm::Cc(value) => recipients(|d| &d.cc, value),
+        SearchTerm::Subject(value) => contains(&email.subject, value),
+        SearchTerm::Body(value) => body_matches(value),
+        SearchTerm::HasAttachment => {
+            email.has_attachment || detail.is_some_and(|detail| detail.has_attachment)
+        }
+        SearchTerm::Read => email.is_read,
+        SearchTerm::Unread => !email.is_read,
+        SearchTerm::Starred => email.is_starred,
+        SearchTerm::Unstarred => !email.is_starred,
+        SearchTerm::Before(before) => date().is_some_and(|date| date < *before),
+        SearchTerm::After(after) => date().is_some_and(|date| date >= *after),
+        SearchTerm::Larger(size) => email.size > *size,
+        SearchTerm::Smaller(size) => email.size < *size,
+        SearchTerm::Folder(name) => folder.eq_ignore_ascii_case(name),
+        SearchTerm::Tag(tag) => email.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+    }
+}
+
+fn search_string(value: &str, literal_plus: bool) -> String {
+    if !value.is_ascii() {
+        let plus = if literal_plus { "+" } else { "" };
+        format!("{{{}{}}}\r\n{}", value.len(), plus, value)
+    } else {
+        // 带引号的字符串不能包含换行
+        quote(&value.replace(['\r', '\n'], " "))
+    }
+}
+
+fn term_imap_key(term: &SearchTerm, literal_plus: bool, non_ascii: &mut bool) -> Result<String, String> {
+    let mut string = |key: &str, value: &str| {
+        *non_ascii |= !value.is_ascii();
+        format!("{} {}", key, search_string(value, literal_plus))
+    };
+    let date = |key: &str, date: &NaiveDate| format!("{} {}", key, date.format("%-d-%b-%Y"));
+
+    let key = match term {
+        SearchTerm::Text(value) => string("TEXT", value),
+        SearchTerm::From(value) => string("FROM", value),
+        SearchTerm::To(value) => string("TO", value),
+        SearchTerm::Cc(value) => string("CC", value),
+        SearchTerm::Subject(value) => string("SUBJECT", value),
+        SearchTerm::Body(value) => string("BODY", value),
+        SearchTerm::HasAttachment => return Err("附件条件无法在服务器端搜索".to_string()),
+        SearchTerm::Read => "SEEN".to_string(),
+        SearchTerm::Unread => "UNSEEN".to_string(),
+        SearchTerm::Starred => "FLAGGED".to_string(),
+        SearchTerm::Unstarred => "UNFLAGGED".to_string(),
+        SearchTerm::Before(d) => date("BEFORE", d),
+        SearchTerm::After(d) => date("SINCE", d),
+        SearchTerm::Larger(size) => format!("LARGER {}", size),
+        SearchTerm::Smaller(size) => format!("SMALLER {}", size),
+        SearchTerm::Folder(_) => "ALL".to_string(),
+        SearchTerm::Tag(tag) => {
+            let is_atom = !tag.is_empty() && tag.chars().all(|c| {
+                c.is_ascii_graphic() && !matches!(c, '(' | ')' | '{' | '%' | '*' | '"' | '\\' | ']')
+            });
+            if !is_atom {
+                return Err(format!("标签 '{}' 不能作为IMAP关键字搜索", tag));
+            }
+            format!("KEYWORD {}", tag)
+        }
+    };
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support;
+
+    fn term(term: SearchTerm) -> SearchQuery {
+        SearchQuery::Term(term)
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn summary(subject: &str, from: &str, date: &str) -> EmailSummary {
+        EmailSummary {
+            date: date.to_string(),
+            has_attachment: true,
+            preview: "请查收本周的周报".to_string(),
+            size: 4096,
+            tags: vec!["work".to_string()],
+            ..test_support::summary(1, subject, from)
+        }
+    }
+
+    #[test]
+    fn test_parse_fielded_query() {
+        let query = SearchQuery::parse(
+            "from:alice subject:\"周报 第3周\" has:attachment is:unread before:2026-01-01 folder:INBOX",
+        ).unwrap();
+
+        assert_eq!(query, SearchQuery::And(vec![
+            term(SearchTerm::From("alice".to_string())),
+            term(SearchTerm::Subject("周报 第3周".to_string())),
+            term(SearchTerm::HasAttachment),
+            term(SearchTerm::Unread),
+            term(SearchTerm::Before(date("2026-01-01"))),
+            term(SearchTerm::Folder("INBOX".to_string())),
+        ]));
+        assert_eq!(query.folders().unwrap(), vec!["INBOX".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_or_not_and_groups() {
+        let query = SearchQuery::parse("(from:alice OR from:bob) -is:read 会议 10:30").unwrap();
+        assert_eq!(query, SearchQuery::And(vec![
+            SearchQuery::Or(vec![
+                term(SearchTerm::From("alice".to_string())),
+                term(SearchTerm::From("bob".to_string())),
+            ]),
+            SearchQuery::Not(Box::new(term(SearchTerm::Read))),
+            term(SearchTerm::Text("会议".to_string())),
+            term(SearchTerm::Text("10:30".to_string())),
+        ]));
+
+        assert_eq!(SearchQuery::parse("  ").unwrap(), SearchQuery::All);
+        assert_eq!(SearchQuery::parse("larger:2M").unwrap(), term(SearchTerm::Larger(2 * 1024 * 1024)));
+
+        for bad in ["(from:alice", "from:alice)", "OR x", "x OR", "before:tomorrow", "is:maybe", "subject:\"open", "from:"] {
+            assert!(SearchQuery::parse(bad).is_err(), "{}", bad);
+        }
+        assert!(SearchQuery::parse("from:a OR folder:Sent").unwrap().folders().is_err());
+    }
+
+    #[test]
+    fn test_translate_to_imap() {
+        let query = SearchQuery::parse("from:alice subject:周报 is:unread after:2026-01-05 folder:INBOX").unwrap();
+        assert_eq!(
+            query.to_imap(false).unwrap(),
+            "CHARSET UTF-8 FROM \"alice\" SUBJECT {6}\r\n周报 UNSEEN SINCE 5-Jan-2026 ALL"
+        );
+
+        let query = SearchQuery::parse("from:a OR from:b OR from:c -(is:starred larger:10K)").unwrap();
+        assert_eq!(
+            query.to_imap(false).unwrap(),
+            "OR FROM \"a\" OR FROM \"b\" FROM \"c\" NOT (FLAGGED LARGER 10240)"
+        );
+
+        // 附件条件代入成立或不成立后才能在服务器端搜索
+        let query = SearchQuery::parse("has:attachment -from:bob").unwrap();
+        assert!(query.has_attachment_term() && query.to_imap(false).is_err());
+        assert_eq!(query.with_attachment(true).to_imap(false).unwrap(), "ALL NOT FROM \"bob\"");
+        assert_eq!(query.with_attachment(false).to_imap(false).unwrap(), "NOT ALL NOT FROM \"bob\"");
+    }
+
+    #[test]
+    fn test_translate_criteria_to_imap() {
+        let criteria = SearchCriteria {
+            from: Some("张三".to_string()),
+            subject: Some("say \"hi\"".to_string()),
+            since: Some("2024-01-05".to_string()),
+            seen: Some(false),
+            flagged: Some(true),
+            larger: Some(1024),
+            ..SearchCriteria::default()
+        };
+        let query = SearchQuery::from_criteria(&criteria).unwrap();
+
+        assert_eq!(
+            query.to_imap(false).unwrap(),
+            "CHARSET UTF-8 FROM {6}\r\n张三 SUBJECT \"say \\\"hi\\\"\" SINCE 5-Jan-2024 UNSEEN FLAGGED LARGER 1024"
+        );
+        assert!(query.to_imap(true).unwrap().starts_with("CHARSET UTF-8 FROM {6+}\r\n张三 SUBJECT"));
+
+        // 纯 ASCII 条件不声明字符集，空条件搜索全部
+        let ascii = SearchCriteria { text: Some(" invoice ".to_string()), ..SearchCriteria::default() };
+        assert_eq!(SearchQuery::from_criteria(&ascii).unwrap().to_imap(true).unwrap(), "TEXT \"invoice\"");
+        let empty = SearchCriteria { from: Some("  ".to_string()), ..SearchCriteria::default() };
+        assert_eq!(SearchQuery::from_criteria(&empty).unwrap(), SearchQuery::All);
+        assert_eq!(SearchQuery::All.to_imap(false).unwrap(), "ALL");
+
+        let bad = SearchCriteria { before: Some("2024/01/05".to_string()), ..SearchCriteria::default() };
+        assert!(SearchQuery::from_criteria(&bad).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_against_cached_summary() {
+        let email = summary("周报 第3周", "Alice <alice@example.com>", "2025-12-30T09:00:00+00:00");
+        let matches = |q: &str| SearchQuery::parse(q).unwrap().matches("INBOX", &email, None);
+
+        assert!(matches("from:alice subject:周报 has:attachment is:unread before:2026-01-01 folder:inbox"));
+        assert!(matches("周报 tag:work larger:4000"));
+        assert!(matches("from:bob OR subject:第3周"));
+        assert!(!matches("after:2026-01-01"));
+        assert!(!matches("-from:alice"));
+        // 没有详情时无法判断收件人
+        assert!(!matches("to:bob"));
+        assert!(SearchQuery::parse("has:attachment").unwrap().needs_detail());
+        assert!(!SearchQuery::parse("subject:周报 is:unread").unwrap().needs_detail());
+    }
+}
@@ -0,0 +1,963 @@
+use crate::models::{
+    ConditionNode, FilterAction, FilterCondition, FilterField, FilterOperator, FilterRule, MatchType,
+};
+use crate::services::rule_engine::{parse_date_value, parse_size_value};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Sieve 脚本导入结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SieveImport {
+    pub rules: Vec<FilterRule>,
+    /// 无法转换而被跳过的语句说明
+    pub warnings: Vec<String>,
+}
+
+/// 过滤规则与 RFC 5228 Sieve 脚本之间的转换
+///
+/// 支持 fileinto、imap4flags（addflag/setflag）、discard、stop，
+/// 以及 relational、date、regex、body 扩展中能与规则模型对应的部分。
+pub struct SieveService;
+
+impl SieveService {
+    /// 将规则导出为 Sieve 脚本
+    ///
+    /// 只导出已启用、适用于该账户且作用于收件箱的规则；
+    /// 无法在服务器端表达的规则以注释形式保留在脚本中。
+    pub fn export_rules(rules: &[FilterRule], account_id: Option<&str>) -> String {
+        let mut requires = BTreeSet::new();
+        let mut blocks = Vec::new();
+
+        let mut rules: Vec<&FilterRule> = rules
+            .iter()
+            .filter(|r| r.enabled)
+            .filter(|r| match account_id {
+                Some(id) => r.account_ids.is_empty() || r.account_ids.iter().any(|a| a == id),
+                None => true,
+            })
+            .collect();
+        rules.sort_by_key(|r| r.priority);
+
+        for rule in rules {
+            match Self::export_rule(rule, &mut requires) {
+                Ok(block) => blocks.push(block),
+                Err(reason) => blocks.push(format!(
+                    "# 规则 '{}' 无法转换为 Sieve: {}\n",
+                    rule.name.replace('\n', " "),
+                    reason
+                )),
+            }
+        }
+
+        let mut script = String::from("# MailFlow 生成的 Sieve 脚本\n");
+        if !requires.is_empty() {
+            let list: Vec<String> = requires.iter().map(|r| quote(r)).collect();
+            script.push_str(&format!("require [{}];\n", list.join(", ")));
+        }
+        for block in blocks {
+            script.push('\n');
+            script.push_str(&block);
+        }
+        script
+    }
+
+    fn export_rule(rule: &FilterRule, requires: &mut BTreeSet<&'static str>) -> Result<String, String> {
+        if !rule.folders.is_empty() && !rule.folders.iter().any(|f| f.eq_ignore_ascii_case("INBOX")) {
+            return Err("规则只作用于收件箱以外的文件夹".to_string());
+        }
+        if rule.conditions.is_empty() {
+            return Err("规则没有条件".to_string());
+        }
+
+        let test = Self::export_group(rule.match_type, &rule.conditions, requires)?;
+
+        // imap4flags 要求先设置标志再 fileinto，标志才会随邮件一起保存
+        let mut flag_lines = Vec::new();
+        let mut delivery_lines = Vec::new();
+        for action in &rule.actions {
+            match action {
+                FilterAction::MarkAsRead => {
+                    requires.insert("imap4flags");
+                    flag_lines.push(format!("addflag {};", quote("\\Seen")));
+                }
+                FilterAction::MarkAsStarred => {
+                    requires.insert("imap4flags");
+                    flag_lines.push(format!("addflag {};", quote("\\Flagged")));
+                }
+                FilterAction::AddTag(tag) => {
+                    requires.insert("imap4flags");
+                    flag_lines.push(format!("addflag {};", quote(tag)));
+                }
+                FilterAction::MoveToFolder(folder) => {
+                    requires.insert("fileinto");
+                    delivery_lines.push(format!("fileinto {};", quote(folder)));
+                }
+                FilterAction::Delete => delivery_lines.push("discard;".to_string()),
+            }
+        }
+        if rule.stop_processing {
+            delivery_lines.push("stop;".to_string());
+        }
+
+        let mut block = format!("# rule: {}\nif {} {{\n", rule.name.replace('\n', " "), test);
+        for line in flag_lines.iter().chain(delivery_lines.iter()) {
+            block.push_str("    ");
+            block.push_str(line);
+            block.push('\n');
+        }
+        block.push_str("}\n");
+        Ok(block)
+    }
+
+    fn export_group(
+        match_type: MatchType,
+        nodes: &[ConditionNode],
+        requires: &mut BTreeSet<&'static str>,
+    ) -> Result<String, String> {
+        if nodes.is_empty() {
+            // 空的全部满足组恒为真，空的任一满足组恒为假
+            return Ok(match match_type {
+                MatchType::All => "true".to_string(),
+                MatchType::Any => "false".to_string(),
+            });
+        }
+
+        let tests = nodes
+            .iter()
+            .map(|n| Self::export_node(n, requires))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if tests.len() == 1 {
+            return Ok(tests.into_iter().next().unwrap_or_default());
+        }
+
+        let keyword = match match_type {
+            MatchType::All => "allof",
+            MatchType::Any => "anyof",
+        };
+        Ok(format!("{}({})", keyword, tests.join(", ")))
+    }
+
+    fn export_node(node: &ConditionNode, requires: &mut BTreeSet<&'static str>) -> Result<String, String> {
+        match node {
+            ConditionNode::Group { group, conditions } => Self::export_group(*group, conditions, requires),
+            ConditionNode::Not { not } => Ok(format!("not {}", Self::export_node(not, requires)?)),
+            ConditionNode::Condition(condition) => Self::export_condition(condition, requires),
+        }
+    }
+
+    fn export_condition(condition: &FilterCondition, requires: &mut BTreeSet<&'static str>) -> Result<String, String> {
+        let value = &condition.value;
+
+        match (&condition.field, condition.operator) {
+            (FilterField::Size, FilterOperator::GreaterThan) => parse_size_value(value)
+                .map(|n| format!("size :over {}", n))
+                .ok_or_else(|| format!("大小格式无效: {}", value)),
+            (FilterField::Size, FilterOperator::LessThan) => parse_size_value(value)
+                .map(|n| format!("size :under {}", n))
+                .ok_or_else(|| format!("大小格式无效: {}", value)),
+            (FilterField::Date, FilterOperator::Before | FilterOperator::After) => {
+                let date = parse_date_value(value)
+                    .ok_or_else(|| format!("日期格式无效: {}", value))?;
+                requires.insert("date");
+                requires.insert("relational");
+                let relation = if condition.operator == FilterOperator::Before { "lt" } else { "gt" };
+                Ok(format!(
+                    "date :value {} \"date\" \"date\" {}",
+                    quote(relation),
+                    quote(&date.format("%Y-%m-%d").to_string())
+                ))
+            }
+            (FilterField::Body, _) => {
+                requires.insert("body");
+                let (negate, match_type) = Self::export_match_type(condition, requires)?;
+                Ok(format!("{}body :text {} {}", negate, match_type, quote(value)))
+            }
+            (field, _) => {
+                let header = match field {
+                    FilterField::From => "from".to_string(),
+                    FilterField::To => "to".to_string(),
+                    FilterField::Cc => "cc".to_string(),
+                    FilterField::Subject => "subject".to_string(),
+                    FilterField::Header(name) => name.clone(),
+                    other => return Err(format!("条件字段 {:?} 不能在服务器端判断", other)),
+                };
+                let (negate, match_type) = Self::export_match_type(condition, requires)?;
+                let value = match condition.operator {
+                    FilterOperator::Regex => value.strip_prefix("(?i)").unwrap_or(value),
+                    _ => value.as_str(),
+                };
+                Ok(format!("{}header {} {} {}", negate, match_type, quote(&header), quote(value)))
+            }
+        }
+    }
+
+    /// 文本运算符对应的 Sieve 匹配类型，返回 (取反前缀, 匹配参数)
+    fn export_match_type(
+        condition: &FilterCondition,
+        requires: &mut BTreeSet<&'static str>,
+    ) -> Result<(&'static str, &'static str), String> {
+        match condition.operator {
+            FilterOperator::Contains => Ok(("", ":contains")),
+            FilterOperator::NotContains => Ok(("not ", ":contains")),
+            FilterOperator::Equals => Ok(("", ":is")),
+            FilterOperator::NotEquals => Ok(("not ", ":is")),
+            FilterOperator::Regex => {
+                requires.insert("regex");
+                // 规则中的正则默认区分大小写，Sieve 默认比较器不区分
+                if condition.value.starts_with("(?i)") {
+                    Ok(("", ":regex"))
+                } else {
+                    Ok(("", ":regex :comparator \"i;octet\""))
+                }
+            }
+            other => Err(format!("运算符 {:?} 不适用于文本字段", other)),
+        }
+    }
+
+    /// 将 Sieve 脚本转换为规则，规则按脚本顺序设置优先级
+    pub fn import_script(script: &str) -> Result<SieveImport, String> {
+        let tokens = tokenize(script)?;
+        let commands = Parser::new(tokens).parse_commands(false)?;
+
+        let mut importer = Importer::default();
+        importer.import_block(&commands, &[], None);
+
+        Ok(SieveImport {
+            rules: importer.rules,
+            warnings: importer.warnings,
+        })
+    }
+}
+
+/// 生成 Sieve 引号字符串
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// === 词法分析 ===
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Identifier(String),
+    Tag(String),
+    Str(String),
+    Number(u64),
+    Comment(String),
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Comma,
+    Semicolon,
+}
+
+fn tokenize(script: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = script.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '#' => {
+                let start = i + 1;
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                tokens.push(Token::Comment(chars[start..i].iter().collect::<String>().trim().to_string()));
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                if i >= chars.len() {
+                    return Err("Sieve 脚本中的注释未结束".to_string());
+                }
+                i += 2;
+            }
+            '"' => {
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        Some('\\') => {
+                            if let Some(&next) = chars.get(i + 1) {
+                                value.push(next);
+                            }
+                            i += 2;
+                        }
+                        Some('"') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&ch) => {
+                            value.push(ch);
+                            i += 1;
+                        }
+                        None => return Err("Sieve 脚本中的字符串未结束".to_string()),
+                    }
+                }
+                tokens.push(Token::Str(value));
+            }
+            ':' => {
+                let start = i + 1;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '-') {
+                    i += 1;
+                }
+                tokens.push(Token::Tag(chars[start..i].iter().collect::<String>().to_lowercase()));
+            }
+            c if c.is_ascii_digit() => {
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let number: u64 = chars[start..i]
+                    .iter()
+                    .collect::<String>()
+                    .parse()
+                    .map_err(|_| "Sieve 脚本中的数字无效".to_string())?;
+                let unit = match chars.get(i).map(|c| c.to_ascii_uppercase()) {
+                    Some('K') => 1024,
+                    Some('M') => 1024 * 1024,
+                    Some('G') => 1024 * 1024 * 1024,
+                    _ => 1,
+                };
+                if unit > 1 {
+                    i += 1;
+                }
+                let number = number
+                    .checked_mul(unit)
+                    .ok_or_else(|| "Sieve 脚本中的数字过大".to_string())?;
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let ident = chars[start..i].iter().collect::<String>().to_lowercase();
+
+                if ident == "text" && chars.get(i) == Some(&':') {
+                    // 多行字符串：text: 之后的行直到单独的 "."
+                    while i < chars.len() && chars[i] != '\n' {
+                        i += 1;
+                    }
+                    i += 1;
+                    let rest: String = chars[i.min(chars.len())..].iter().collect();
+                    let mut value = Vec::new();
+                    let mut consumed = 0;
+                    let mut terminated = false;
+                    for line in rest.split_inclusive('\n') {
+                        consumed += line.chars().count();
+                        let content = line.trim_end_matches('\n').trim_end_matches('\r');
+                        if content == "." {
+                            terminated = true;
+                            break;
+                        }
+                        value.push(content.strip_prefix('.').filter(|l| l.starts_with('.')).unwrap_or(content).to_string());
+                    }
+                    if !terminated {
+                        return Err("Sieve 脚本中的多行字符串未结束".to_string());
+                    }
+                    i += consumed;
+                    tokens.push(Token::Str(value.join("\n")));
+                } else {
+                    tokens.push(Token::Identifier(ident));
+                }
+            }
+            '[' => { tokens.push(Token::LBracket); i += 1; }
+            ']' => { tokens.push(Token::RBracket); i += 1; }
+            '(' => { tokens.push(Token::LParen); i += 1; }
+            ')' => { tokens.push(Token::RParen); i += 1; }
+            '{' => { tokens.push(Token::LBrace); i += 1; }
+            '}' => { tokens.push(Token::RBrace); i += 1; }
+            ',' => { tokens.push(Token::Comma); i += 1; }
+            ';' => { tokens.push(Token::Semicolon); i += 1; }
+            other => return Err(format!("Sieve 脚本中出现无法识别的字符: {}", other)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+// === 语法分析 ===
+
+#[derive(Debug, Clone)]
+enum Argument {
+    Tag(String),
+    Strings(Vec<String>),
+    Number(u64),
+}
+
+#[derive(Debug, Clone)]
+struct Test {
+    name: String,
+    args: Vec<Argument>,
+    tests: Vec<Test>,
+}
+
+#[derive(Debug, Clone)]
+enum Command {
+    /// if / elsif / else 链，else 分支的条件为 None
+    If {
+        branches: Vec<(Option<Test>, Vec<Command>)>,
+        comment: Option<String>,
+    },
+    Action {
+        name: String,
+        args: Vec<Argument>,
+    },
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self { tokens, pos: 0 }
+    }
+
+    /// 跳过注释，返回下一个有效记号
+    fn peek(&mut self) -> Option<&Token> {
+        while let Some(Token::Comment(_)) = self.tokens.get(self.pos) {
+            self.pos += 1;
+        }
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        self.peek();
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(ref token) if *token == expected => Ok(()),
+            other => Err(format!("Sieve 语法错误：期望 {:?}，实际为 {:?}", expected, other)),
+        }
+    }
+
+    fn parse_commands(&mut self, in_block: bool) -> Result<Vec<Command>, String> {
+        let mut commands = Vec::new();
+        loop {
+            match self.peek() {
+                None if in_block => return Err("Sieve 语法错误：代码块未结束".to_string()),
+                None => return Ok(commands),
+                Some(Token::RBrace) if in_block => {
+                    self.pos += 1;
+                    return Ok(commands);
+                }
+                _ => {}
+            }
+
+            // 紧挨在命令前的注释用于规则命名（导出时写入 "# rule: 名称"）
+            let comment_before = match self.pos.checked_sub(1).map(|i| &self.tokens[i]) {
+                Some(Token::Comment(text)) => Some(text.clone()),
+                _ => None,
+            };
+
+            let name = match self.next() {
+                Some(Token::Identifier(name)) => name,
+                other => return Err(format!("Sieve 语法错误：期望命令，实际为 {:?}", other)),
+            };
+
+            if name == "if" {
+                let mut branches = Vec::new();
+                let test = self.parse_test()?;
+                self.expect(Token::LBrace)?;
+                branches.push((Some(test), self.parse_commands(true)?));
+
+                loop {
+                    match self.peek() {
+                        Some(Token::Identifier(word)) if word == "elsif" => {
+                            self.pos += 1;
+                            let test = self.parse_test()?;
+                            self.expect(Token::LBrace)?;
+                            branches.push((Some(test), self.parse_commands(true)?));
+                        }
+                        Some(Token::Identifier(word)) if word == "else" => {
+                            self.pos += 1;
+                            self.expect(Token::LBrace)?;
+                            branches.push((None, self.parse_commands(true)?));
+                            break;
+                        }
+                        _ => break,
+                    }
+                }
+
+                commands.push(Command::If { branches, comment: comment_before });
+            } else {
+                let args = self.parse_arguments()?;
+                self.expect(Token::Semicolon)?;
+                commands.push(Command::Action { name, args });
+            }
+        }
+    }
+
+    fn parse_arguments(&mut self) -> Result<Vec<Argument>, String> {
+        let mut args = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Token::Tag(_)) => {
+                    if let Some(Token::Tag(tag)) = self.next() {
+                        args.push(Argument::Tag(tag));
+                    }
+                }
+                Some(Token::Number(_)) => {
+                    if let Some(Token::Number(n)) = self.next() {
+                        args.push(Argument::Number(n));
+                    }
+                }
+                Some(Token::Str(_)) => {
+                    if let Some(Token::Str(s)) = self.next() {
+                        args.push(Argument::Strings(vec![s]));
+                    }
+                }
+                Some(Token::LBracket) => {
+                    self.pos += 1;
+                    let mut list = Vec::new();
+                    loop {
+                        match self.next() {
+                            Some(Token::Str(s)) => list.push(s),
+                            other => return Err(format!("Sieve 语法错误：字符串列表中出现 {:?}", other)),
+                        }
+                        match self.next() {
+                            Some(Token::Comma) => continue,
+                            Some(Token::RBracket) => break,
+                            other => return Err(format!("Sieve 语法错误：字符串列表中出现 {:?}", other)),
+                        }
+                    }
+                    args.push(Argument::Strings(list));
+                }
+                _ => return Ok(args),
+            }
+        }
+    }
+
+    fn parse_test(&mut self) -> Result<Test, String> {
+        let name = match self.next() {
+            Some(Token::Identifier(name)) => name,
+            other => return Err(format!("Sieve 语法错误：期望测试，实际为 {:?}", other)),
+        };
+        let args = self.parse_arguments()?;
+
+        let mut tests = Vec::new();
+        match self.peek() {
+            Some(Token::LParen) => {
+                self.pos += 1;
+                loop {
+                    tests.push(self.parse_test()?);
+                    match self.next() {
+                        Some(Token::Comma) => continue,
+                        Some(Token::RParen) => break,
+                        other => return Err(format!("Sieve 语法错误：测试列表中出现 {:?}", other)),
+                    }
+                }
+            }
+            Some(Token::Identifier(_)) => tests.push(self.parse_test()?),
+            _ => {}
+        }
+
+        Ok(Test { name, args, tests })
+    }
+}
+
+// === 转换为规则 ===
+
+#[derive(Default)]
+struct Importer {
+    rules: Vec<FilterRule>,
+    warnings: Vec<String>,
+}
+
+impl Importer {
+    /// 转换一个代码块；parent 为外层 if 的条件，用于展开嵌套 if
+    fn import_block(&mut self, commands: &[Command], parent: &[ConditionNode], name: Option<String>) {
+        let mut actions = Vec::new();
+        let mut stop = false;
+
+        for command in commands {
+            match command {
+                Command::Action { name: action, args } => match action.as_str() {
+                    "require" | "keep" => {}
+                    "stop" => stop = true,
+                    "discard" => actions.push(FilterAction::Delete),
+                    "fileinto" => match last_strings(args).and_then(|s| s.first().cloned()) {
+                        Some(folder) => actions.push(FilterAction::MoveToFolder(folder)),
+                        None => self.warnings.push("fileinto 缺少目标文件夹".to_string()),
+                    },
+                    "addflag" | "setflag" => {
+                        for flag in last_strings(args).unwrap_or_default() {
+                            // 一个字符串里可以用空格分隔多个标志
+                            for flag in flag.split_whitespace() {
+                                actions.push(match flag.to_ascii_lowercase().as_str() {
+                                    "\\seen" => FilterAction::MarkAsRead,
+                                    "\\flagged" => FilterAction::MarkAsStarred,
+                                    "\\deleted" => FilterAction::Delete,
+                                    _ => FilterAction::AddTag(flag.to_string()),
+                                });
+                            }
+                        }
+                    }
+                    other => self.warnings.push(format!("不支持的 Sieve 动作: {}", other)),
+                },
+                Command::If { branches, comment } => {
+                    // elsif / else 分支需要排除之前所有分支的条件
+                    let mut previous: Vec<ConditionNode> = Vec::new();
+                    let rule_name = comment
+                        .as_deref()
+                        .and_then(|c| c.strip_prefix("rule:"))
+                        .map(|c| c.trim().to_string());
+
+                    for (test, block) in branches {
+                        let mut conditions = parent.to_vec();
+                        conditions.extend(previous.iter().map(|p| ConditionNode::Not { not: Box::new(p.clone()) }));
+
+                        let converted = match test {
+                            Some(test) => match convert_test(test) {
+                                Ok(node) => Some(node),
+                                Err(reason) => {
+                                    self.warnings.push(format!("已跳过条件分支: {}", reason));
+                                    None
+                                }
+                            },
+                            None => None,
+                        };
+
+                        match (test, converted) {
+                            (Some(_), Some(node)) => {
+                                conditions.push(node.clone());
+                                previous.push(node);
+                                self.import_block(block, &conditions, rule_name.clone());
+                            }
+                            (None, _) => self.import_block(block, &conditions, rule_name.clone()),
+                            // 无法转换的分支之后的分支也无法准确表达
+                            (Some(_), None) => break,
+                        }
+                    }
+                }
+            }
+        }
+
+        if actions.is_empty() && !stop {
+            return;
+        }
+
+        let conditions = if parent.is_empty() {
+            // 顶层无条件动作：用空的全部满足组表示恒真
+            vec![ConditionNode::Group { group: MatchType::All, conditions: Vec::new() }]
+        } else {
+            parent.to_vec()
+        };
+
+        let index = self.rules.len();
+        self.rules.push(FilterRule {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.unwrap_or_else(|| format!("Sieve 规则 {}", index + 1)),
+            conditions,
+            match_type: MatchType::All,
+            actions,
+            enabled: true,
+            priority: index as i32,
+            stop_processing: stop,
+            account_ids: Vec::new(),
+            folders: Vec::new(),
+        });
+    }
+}
+
+/// 最后一个字符串参数（Sieve 命令的主参数总在最后）
+fn last_strings(args: &[Argument]) -> Option<Vec<String>> {
+    args.iter().rev().find_map(|a| match a {
+        Argument::Strings(s) => Some(s.clone()),
+        _ => None,
+    })
+}
+
+/// 测试参数按类型拆分后的结果
+struct TestArgs<'a> {
+    tags: Vec<&'a str>,
+    comparator: Option<&'a str>,
+    /// relational 扩展 :value / :count 的关系运算符
+    relation: Option<&'a str>,
+    strings: Vec<&'a Vec<String>>,
+    number: Option<u64>,
+}
+
+impl<'a> TestArgs<'a> {
+    fn new(args: &'a [Argument]) -> Self {
+        let mut parsed = TestArgs { tags: Vec::new(), comparator: None, relation: None, strings: Vec::new(), number: None };
+        let mut iter = args.iter().peekable();
+
+        while let Some(arg) = iter.next() {
+            match arg {
+                Argument::Tag(tag) => {
+                    parsed.tags.push(tag.as_str());
+                    // 这些标签自带一个字符串参数
+                    if matches!(tag.as_str(), "comparator" | "value" | "count") {
+                        if let Some(Argument::Strings(value)) = iter.peek().copied() {
+                            let value = value.first().map(|s| s.as_str());
+                            if tag == "comparator" {
+                                parsed.comparator = value;
+                            } else {
+                                parsed.relation = value;
+                            }
+                            iter.next();
+                        }
+                    }
+                }
+                Argument::Strings(list) => parsed.strings.push(list),
+                Argument::Number(n) => parsed.number = Some(*n),
+            }
+        }
+
+        parsed
+    }
+
+    fn has(&self, tag: &str) -> bool {
+        self.tags.contains(&tag)
+    }
+}
+
+fn convert_test(test: &Test) -> Result<ConditionNode, String> {
+    let args = TestArgs::new(&test.args);
+    let strings = &args.strings;
+
+    match test.name.as_str() {
+        "true" => Ok(ConditionNode::Group { group: MatchType::All, conditions: Vec::new() }),
+        "false" => Ok(ConditionNode::Group { group: MatchType::Any, conditions: Vec::new() }),
+        "not" => {
+            let inner = test.tests.first().ok_or("not 缺少测试")?;
+            Ok(ConditionNode::Not { not: Box::new(convert_test(inner)?) })
+        }
+        "allof" | "anyof" => {
+            let conditions = test.tests.iter().map(convert_test).collect::<Result<Vec<_>, _>>()?;
+            let group = if test.name == "allof" { MatchType::All } else { MatchType::Any };
+            Ok(ConditionNode::Group { group, conditions })
+        }
+        "size" => {
+            let limit = args.number.ok_or("size 缺少大小")?;
+            let operator = if args.has("over") {
+                FilterOperator::GreaterThan
+            } else if args.has("under") {
+                FilterOperator::LessThan
+            } else {
+                return Err("size 需要 :over 或 :under".to_string());
+            };
+            Ok(leaf(FilterField::Size, operator, limit.to_string()))
+        }
+        "exists" => {
+            let names = strings.first().ok_or("exists 缺少邮件头名称")?;
+            Ok(all_of(names.iter().map(|n| leaf(header_field(n), FilterOperator::NotEquals, String::new())).collect()))
+        }
+        "header" | "address" | "body" => {
+            let (fields, keys): (Vec<FilterField>, &Vec<String>) = if test.name == "body" {
+                (vec![FilterField::Body], *strings.first().ok_or("body 缺少匹配值")?)
+            } else {
+                if strings.len() < 2 {
+                    return Err(format!("{} 缺少邮件头或匹配值", test.name));
+                }
+                (strings[0].iter().map(|n| header_field(n)).collect(), strings[1])
+            };
+
+            let mut alternatives = Vec::new();
+            for field in &fields {
+                for key in keys {
+                    let (operator, value) = convert_match(&args, key)?;
+                    // address 测试比较的是地址部分，规则只能在完整邮件头中查找
+                    let (operator, value) = if test.name == "address" && operator == FilterOperator::Equals {
+                        if args.has("domain") {
+                            (FilterOperator::Contains, format!("@{}", value))
+                        } else if args.has("localpart") {
+                            (FilterOperator::Contains, format!("{}@", value))
+                        } else {
+                            (FilterOperator::Contains, value)
+                        }
+                    } else {
+                        (operator, value)
+                    };
+                    alternatives.push(leaf(field.clone(), operator, value));
+                }
+            }
+
+            Ok(if alternatives.len() == 1 {
+                alternatives.remove(0)
+            } else {
+                ConditionNode::Group { group: MatchType::Any, conditions: alternatives }
+            })
+        }
+        "date" => {
+            // date :value "lt" "date" "date" "2026-01-01"
+            let relation = args.relation.ok_or("仅支持 date :value 比较")?;
+            if strings.len() < 3 {
+                return Err("date 缺少参数".to_string());
+            }
+            let part = strings[1].first().map(|s| s.as_str()).unwrap_or_default();
+            if !strings[0].iter().any(|h| h.eq_ignore_ascii_case("date")) || part != "date" {
+                return Err("仅支持按 Date 邮件头的日期部分比较".to_string());
+            }
+            let value = strings[2].first().cloned().unwrap_or_default();
+            let operator = match relation {
+                "lt" | "le" => FilterOperator::Before,
+                "gt" | "ge" => FilterOperator::After,
+                other => return Err(format!("不支持的日期比较: {}", other)),
+            };
+            Ok(leaf(FilterField::Date, operator, value))
+        }
+        other => Err(format!("不支持的 Sieve 测试: {}", other)),
+    }
+}
+
+/// Sieve 匹配类型转换为规则运算符
+fn convert_match(args: &TestArgs, key: &str) -> Result<(FilterOperator, String), String> {
+    let case_sensitive = args.comparator == Some("i;octet");
+    if args.has("regex") {
+        let value = if case_sensitive { key.to_string() } else { format!("(?i){}", key) };
+        Ok((FilterOperator::Regex, value))
+    } else if args.has("matches") {
+        Ok((FilterOperator::Regex, glob_to_regex(key, case_sensitive)))
+    } else if args.has("contains") {
+        Ok((FilterOperator::Contains, key.to_string()))
+    } else if args.relation.is_some() {
+        Err("不支持 relational 扩展的文本比较".to_string())
+    } else {
+        // 未指定匹配类型时 Sieve 默认为 :is
+        Ok((FilterOperator::Equals, key.to_string()))
+    }
+}
+
+/// 将 Sieve :matches 通配符转换为正则表达式
+fn glob_to_regex(pattern: &str, case_sensitive: bool) -> String {
+    let mut regex = String::from(if case_sensitive { "^" } else { "(?i)^" });
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    regex.push_str(&regex::escape(&escaped.to_string()));
+                }
+            }
+            other => regex.push_str(&regex::escape(&other.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+fn header_field(name: &str) -> FilterField {
+    match name.to_ascii_lowercase().as_str() {
+        "from" => FilterField::From,
+        "to" => FilterField::To,
+        "cc" => FilterField::Cc,
+        "subject" => FilterField::Subject,
+        _ => FilterField::Header(name.to_string()),
+    }
+}
+
+fn leaf(field: FilterField, operator: FilterOperator, value: String) -> ConditionNode {
+    ConditionNode::Condition(FilterCondition { field, operator, value })
+}
+
+fn all_of(conditions: Vec<ConditionNode>) -> ConditionNode {
+    if conditions.len() == 1 {
+        conditions.into_iter().next().unwrap_or(ConditionNode::Group { group: MatchType::All, conditions: Vec::new() })
+    } else {
+        ConditionNode::Group { group: MatchType::All, conditions }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::rule;
+
+    #[test]
+    fn test_import_if_elsif_else() {
+        let script = r#"
+require ["fileinto", "imap4flags"];
+# rule: 周报
+if header :contains "subject" "周报" {
+    addflag "\\Seen";
+    fileinto "Reports";
+    stop;
+} elsif size :over 1M {
+    discard;
+} else {
+    keep;
+}
+"#;
+        let imported = SieveService::import_script(script).unwrap();
+        assert_eq!(imported.rules.len(), 2);
+
+        let first = &imported.rules[0];
+        assert_eq!(first.name, "周报");
+        assert!(first.stop_processing);
+        assert!(matches!(first.actions[0], FilterAction::MarkAsRead));
+        assert!(matches!(&first.actions[1], FilterAction::MoveToFolder(f) if f == "Reports"));
+
+        // elsif 分支带有对前一分支条件的取反
+        let second = &imported.rules[1];
+        assert_eq!(second.conditions.len(), 2);
+        assert!(matches!(second.conditions[0], ConditionNode::Not { .. }));
+        assert!(matches!(second.actions[0], FilterAction::Delete));
+    }
+
+    #[test]
+    fn test_import_rejects_oversized_number() {
+        let err = SieveService::import_script("if size :over 17179869184G { discard; }").unwrap_err();
+        assert!(err.contains("数字过大"), "{}", err);
+    }
+
+    #[test]
+    fn test_export_then_import_round_trip() {
+        let conditions = vec![
+            leaf(FilterField::From, FilterOperator::Contains, "news@".to_string()),
+            ConditionNode::Not {
+                not: Box::new(leaf(FilterField::Subject, FilterOperator::Equals, "重要 \"通知\"".to_string())),
+            },
+        ];
+        let actions = vec![FilterAction::AddTag("newsletter".to_string()), FilterAction::MoveToFolder("News".to_string())];
+        let rule = FilterRule { name: "Newsletters".to_string(), ..rule("r1", conditions, actions) };
+
+        let script = SieveService::export_rules(&[rule], None);
+        assert!(script.contains("require [\"fileinto\", \"imap4flags\"];"));
+        assert!(script.contains("addflag \"newsletter\";\n    fileinto \"News\";"));
+
+        let imported = SieveService::import_script(&script).unwrap();
+        assert!(imported.warnings.is_empty());
+        assert_eq!(imported.rules.len(), 1);
+        assert_eq!(imported.rules[0].name, "Newsletters");
+
+        match &imported.rules[0].conditions[0] {
+            ConditionNode::Group { group: MatchType::All, conditions } => {
+                assert!(matches!(&conditions[1], ConditionNode::Not { not } if matches!(
+                    not.as_ref(),
+                    ConditionNode::Condition(c) if c.value == "重要 \"通知\""
+                )));
+            }
+            other => panic!("unexpected condition: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unsupported_rules_become_comments() {
+        let conditions = vec![leaf(FilterField::Category, FilterOperator::Equals, "ads".to_string())];
+        let rule = FilterRule { name: "AI".to_string(), ..rule("r2", conditions, vec![FilterAction::Delete]) };
+
+        let script = SieveService::export_rules(&[rule], None);
+        assert!(script.contains("# 规则 'AI' 无法转换为 Sieve"));
+        assert!(!script.contains("discard"));
+    }
+}
@@ -1,9 +1,17 @@
-use crate::models::{AppConfig, EmailAccount, FilterRule, FolderSyncState, CachedEmailList, EmailSummary, Email};
+use crate::models::{AppConfig, EmailAccount, FilterRule, FolderSyncState, CachedEmailList, EmailSummary, Email, LocalSearchHit, SavedSearch, SearchQuery, EmailSource, SpecialFolder};
+use crate::services::search_index::SearchIndex;
 use keyring::Entry;
 use sled::{Db, Tree};
 use serde_json;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+
+/// 虚拟文件夹最近一次解析出的全部邮件，翻页时复用
+struct SavedSearchHits {
+    search: SavedSearch,
+    emails: Vec<EmailSummary>,
+}
 
 pub struct StorageService {
     db: Arc<Db>,
@@ -13,6 +21,9 @@ pub struct StorageService {
     sync_state_tree: Arc<Tree>,
     email_summaries_tree: Arc<Tree>,
     email_details_tree: Arc<Tree>,
+    inline_resources_tree: Arc<Tree>,
+    search_index: Arc<SearchIndex>,
+    saved_search_hits: Arc<Mutex<Option<SavedSearchHits>>>,
 }
 
 impl StorageService {
@@ -30,6 +41,11 @@ impl StorageService {
         let db = sled::open(&db_path)
             .map_err(|e| format!("打开数据库失败: {}", e))?;
 
+        Self::with_db(db)
+    }
+
+    /// 在已打开的数据库上创建存储服务（测试中使用临时数据库）
+    pub(crate) fn with_db(db: Db) -> Result<Self, String> {
         let accounts_tree = db.open_tree("accounts")
             .map_err(|e| format!("打开accounts表失败: {}", e))?;
 
@@ -48,7 +64,16 @@ impl StorageService {
         let email_details_tree = db.open_tree("email_details")
             .map_err(|e| format!("打开email_details表失败: {}", e))?;
 
-        Ok(Self {
+        let inline_resources_tree = db.open_tree("inline_resources")
+            .map_err(|e| format!("打开inline_resources表失败: {}", e))?;
+
+        let search_postings_tree = db.open_tree("search_postings")
+            .map_err(|e| format!("打开search_postings表失败: {}", e))?;
+
+        let search_documents_tree = db.open_tree("search_documents")
+            .map_err(|e| format!("打开search_documents表失败: {}", e))?;
+
+        let storage = Self {
             db: Arc::new(db),
             accounts_tree: Arc::new(accounts_tree),
             config_tree: Arc::new(config_tree),
@@ -56,7 +81,19 @@ impl StorageService {
             sync_state_tree: Arc::new(sync_state_tree),
             email_summaries_tree: Arc::new(email_summaries_tree),
             email_details_tree: Arc::new(email_details_tree),
-        })
+            inline_resources_tree: Arc::new(inline_resources_tree),
+            search_index: Arc::new(SearchIndex::new(search_postings_tree, search_documents_tree)),
+            saved_search_hits: Arc::new(Mutex::new(None)),
+        };
+
+        // 升级前已有的缓存没有索引，首次启动时补建
+        if storage.search_index.is_empty() {
+            if let Err(e) = storage.rebuild_search_index() {
+                eprintln!("重建搜索索引失败: {}", e);
+            }
+        }
+
+        Ok(storage)
     }
 
     // === 账户管理 ===
@@ -248,6 +285,27 @@ impl StorageService {
             rules.push(rule);
         }
 
+        // 按优先级排序，优先级相同时保持键顺序
+        rules.sort_by_key(|r: &FilterRule| r.priority);
+
+        Ok(rules)
+    }
+
+    /// 按给定的 ID 顺序重新设置规则优先级，未列出的规则排在最后
+    pub fn reorder_filter_rules(&self, ordered_ids: &[String]) -> Result<Vec<FilterRule>, String> {
+        let mut rules = self.get_filter_rules()?;
+        rules.sort_by_key(|r| {
+            ordered_ids
+                .iter()
+                .position(|id| *id == r.id)
+                .unwrap_or(ordered_ids.len())
+        });
+
+        for (index, rule) in rules.iter_mut().enumerate() {
+            rule.priority = index as i32;
+            self.save_filter_rule(rule)?;
+        }
+
         Ok(rules)
     }
 
@@ -260,6 +318,105 @@ impl StorageService {
         Ok(())
     }
 
+    // === 已保存的搜索（虚拟文件夹） ===
+    pub fn save_saved_search(&self, search: &SavedSearch) -> Result<(), String> {
+        let key = format!("search:{}", search.id);
+        let value = serde_json::to_vec(search)
+            .map_err(|e| format!("序列化已保存的搜索失败: {}", e))?;
+
+        self.config_tree
+            .insert(key.as_bytes(), value)
+            .map_err(|e| format!("保存搜索失败: {}", e))?;
+
+        Ok(())
+    }
+
+    pub fn get_saved_search(&self, id: &str) -> Result<Option<SavedSearch>, String> {
+        let key = format!("search:{}", id);
+        let value = self.config_tree.get(key.as_bytes())
+            .map_err(|e| format!("读取已保存的搜索失败: {}", e))?;
+
+        match value {
+            Some(v) => {
+                let search = serde_json::from_slice(&v)
+                    .map_err(|e| format!("反序列化已保存的搜索失败: {}", e))?;
+                Ok(Some(search))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_saved_searches(&self) -> Result<Vec<SavedSearch>, String> {
+        let mut searches = Vec::new();
+
+        for item in self.config_tree.scan_prefix(b"search:") {
+            let (_, value) = item.map_err(|e| format!("读取已保存的搜索失败: {}", e))?;
+            let search = serde_json::from_slice(&value)
+                .map_err(|e| format!("反序列化已保存的搜索失败: {}", e))?;
+            searches.push(search);
+        }
+
+        searches.sort_by(|a: &SavedSearch, b| a.name.cmp(&b.name));
+        Ok(searches)
+    }
+
+    pub fn delete_saved_search(&self, id: &str) -> Result<(), String> {
+        let key = format!("search:{}", id);
+        self.config_tree
+            .remove(key.as_bytes())
+            .map_err(|e| format!("删除搜索失败: {}", e))?;
+
+        Ok(())
+    }
+
+    /// 列出虚拟文件夹中的一页邮件，从本地缓存的邮件摘要中解析
+    /// 每封邮件的 source 记录其所在的账户和文件夹，id 中也包含文件夹，不同文件夹中 UID 相同的邮件不会重复
+    ///
+    /// 第一页时解析全部结果并保留，之后的页面从中截取，不再重新扫描缓存；
+    /// 翻页期间缓存的变化也不会使页面错位
+    pub fn list_saved_search(&self, search: &SavedSearch, offset: usize, limit: usize) -> Result<Vec<EmailSummary>, String> {
+        let mut snapshot = self.saved_search_hits.lock().unwrap();
+        let reusable = offset > 0 && snapshot.as_ref().is_some_and(|hits| hits.search == *search);
+        if !reusable {
+            let query = SearchQuery::parse(&search.query)?;
+            let emails = self.search_cached(&query, &search.account_ids, usize::MAX)?
+                .into_iter()
+                .map(|hit| EmailSummary {
+                    id: format!("{}_{}_{}", hit.account_id, hit.folder, hit.email.uid),
+                    source: Some(EmailSource {
+                        account_id: hit.account_id,
+                        folder: hit.folder,
+                    }),
+                    ..hit.email
+                })
+                .collect();
+            *snapshot = Some(SavedSearchHits { search: search.clone(), emails });
+        }
+
+        let emails = snapshot.as_ref().map(|hits| hits.emails.as_slice()).unwrap_or_default();
+        Ok(emails.iter().skip(offset).take(limit).cloned().collect())
+    }
+
+    // === 特殊用途文件夹 ===
+
+    /// 记录账户中特殊用途文件夹的实际名称，离线时统一视图仍可读取缓存
+    pub fn save_special_folder(&self, account_id: &str, role: SpecialFolder, folder: &str) -> Result<(), String> {
+        let key = format!("special:{}:{}", account_id, role.as_str());
+        self.config_tree
+            .insert(key.as_bytes(), folder.as_bytes())
+            .map_err(|e| format!("保存文件夹信息失败: {}", e))?;
+
+        Ok(())
+    }
+
+    pub fn get_special_folder(&self, account_id: &str, role: SpecialFolder) -> Result<Option<String>, String> {
+        let key = format!("special:{}:{}", account_id, role.as_str());
+        let value = self.config_tree.get(key.as_bytes())
+            .map_err(|e| format!("读取文件夹信息失败: {}", e))?;
+
+        Ok(value.map(|v| String::from_utf8_lossy(&v).to_string()))
+    }
+
     // === 文件夹同步状态 ===
 
     /// 保存文件夹同步状态
@@ -293,16 +450,35 @@ impl StorageService {
 
     // === 邮件摘要缓存 ===
 
-    /// 缓存邮件摘要列表
+    /// 缓存邮件摘要列表（替换原有缓存）
     pub fn cache_email_summaries(&self, account_id: &str, folder: &str, emails: &[EmailSummary]) -> Result<(), String> {
-        let key = format!("{}:{}", account_id, folder);
-        let cached = CachedEmailList {
+        // 不再缓存且没有详情的邮件移出索引
+        if let Some(previous) = self.get_cached_email_summaries(account_id, folder)? {
+            let kept: HashSet<u32> = emails.iter().map(|e| e.uid).collect();
+            for email in previous.emails.iter().filter(|e| !kept.contains(&e.uid)) {
+                let key = format!("{}:{}:{}", account_id, folder, email.uid);
+                let has_detail = self.email_details_tree
+                    .contains_key(key.as_bytes())
+                    .map_err(|e| format!("获取缓存邮件详情失败: {}", e))?;
+                if !has_detail {
+                    self.search_index.remove(account_id, folder, email.uid)?;
+                }
+            }
+        }
+        self.index_summaries(account_id, folder, emails)?;
+
+        self.save_cached_list(&CachedEmailList {
             account_id: account_id.to_string(),
             folder: folder.to_string(),
             emails: emails.to_vec(),
             last_updated: chrono::Utc::now().timestamp(),
-        };
-        let value = serde_json::to_vec(&cached)
+            reached_oldest: false,
+        })
+    }
+
+    fn save_cached_list(&self, cached: &CachedEmailList) -> Result<(), String> {
+        let key = format!("{}:{}", cached.account_id, cached.folder);
+        let value = serde_json::to_vec(cached)
             .map_err(|e| format!("序列化邮件列表失败: {}", e))?;
 
         self.email_summaries_tree
@@ -336,8 +512,11 @@ impl StorageService {
                 folder: folder.to_string(),
                 emails: Vec::new(),
                 last_updated: 0,
+                reached_oldest: false,
             });
 
+        self.index_summaries(account_id, folder, new_emails)?;
+
         // 将新邮件插入到列表开头（最新的在前）
         for email in new_emails.iter().rev() {
             // 避免重复
@@ -347,7 +526,122 @@ impl StorageService {
         }
 
         cached.last_updated = chrono::Utc::now().timestamp();
-        self.cache_email_summaries(account_id, folder, &cached.emails)
+        self.save_cached_list(&cached)
+    }
+
+    /// 将更早的邮件（翻页取得）合并到缓存末尾，已缓存的页面保持不变
+    /// reached_oldest 表示已取到文件夹中最早的邮件
+    pub fn merge_older_emails(
+        &self,
+        account_id: &str,
+        folder: &str,
+        older_emails: &[EmailSummary],
+        reached_oldest: bool,
+    ) -> Result<(), String> {
+        let mut cached = self.get_cached_email_summaries(account_id, folder)?
+            .unwrap_or_else(|| CachedEmailList {
+                account_id: account_id.to_string(),
+                folder: folder.to_string(),
+                emails: Vec::new(),
+                last_updated: 0,
+                reached_oldest: false,
+            });
+
+        // 只追加比缓存中最早的邮件更早的邮件，保持列表从新到旧
+        let oldest = cached.emails.iter().map(|e| e.uid).min().unwrap_or(u32::MAX);
+        let mut older: Vec<EmailSummary> = older_emails
+            .iter()
+            .filter(|e| e.uid < oldest)
+            .cloned()
+            .collect();
+        older.sort_by_key(|e| std::cmp::Reverse(e.uid));
+        older.dedup_by_key(|e| e.uid);
+        self.index_summaries(account_id, folder, &older)?;
+
+        cached.emails.extend(older);
+        cached.reached_oldest = reached_oldest;
+        self.save_cached_list(&cached)
+    }
+
+    /// 按服务器上的标志更新缓存中的已读/星标状态，并移除已删除的邮件
+    /// 返回状态有变化的邮件
+    pub fn apply_flag_changes(
+        &self,
+        account_id: &str,
+        folder: &str,
+        flags: &HashMap<u32, Vec<String>>,
+        vanished: &[u32],
+    ) -> Result<Vec<EmailSummary>, String> {
+        // 详情、内嵌资源和搜索索引中可能有摘要列表之外的邮件（如只打开过详情），总是清理
+        for uid in vanished {
+            self.search_index.remove(account_id, folder, *uid)?;
+            let key = format!("{}:{}:{}", account_id, folder, uid);
+            self.email_details_tree
+                .remove(key.as_bytes())
+                .map_err(|e| format!("删除邮件详情缓存失败: {}", e))?;
+            self.remove_inline_resources(&format!("{}:", key))?;
+        }
+
+        let mut cached = match self.get_cached_email_summaries(account_id, folder)? {
+            Some(cached) => cached,
+            None => return Ok(Vec::new()),
+        };
+
+        let has_flag = |flags: &[String], name: &str| flags.iter().any(|f| f.eq_ignore_ascii_case(name));
+        let mut updated = Vec::new();
+        for email in cached.emails.iter_mut() {
+            if let Some(flags) = flags.get(&email.uid) {
+                let is_read = has_flag(flags, "\\Seen");
+                let is_starred = has_flag(flags, "\\Flagged");
+                if email.is_read != is_read || email.is_starred != is_starred {
+                    email.is_read = is_read;
+                    email.is_starred = is_starred;
+                    updated.push(email.clone());
+                }
+            }
+        }
+
+        let vanished: HashSet<u32> = vanished.iter().copied().collect();
+        let before = cached.emails.len();
+        cached.emails.retain(|email| !vanished.contains(&email.uid));
+
+        if updated.is_empty() && cached.emails.len() == before {
+            return Ok(updated);
+        }
+
+        cached.last_updated = chrono::Utc::now().timestamp();
+        self.save_cached_list(&cached)?;
+        Ok(updated)
+    }
+
+    /// 用执行规则等操作后的摘要替换缓存中的同一封邮件，并清理已移走或删除的邮件
+    pub fn update_cached_emails(
+        &self,
+        account_id: &str,
+        folder: &str,
+        updated: &[EmailSummary],
+        removed: &[u32],
+    ) -> Result<(), String> {
+        self.apply_flag_changes(account_id, folder, &HashMap::new(), removed)?;
+
+        let mut cached = match self.get_cached_email_summaries(account_id, folder)? {
+            Some(cached) => cached,
+            None => return Ok(()),
+        };
+        let updated: HashMap<u32, &EmailSummary> = updated.iter().map(|e| (e.uid, e)).collect();
+        let mut changed = false;
+        for email in cached.emails.iter_mut() {
+            if let Some(new) = updated.get(&email.uid) {
+                *email = (*new).clone();
+                changed = true;
+            }
+        }
+        if !changed {
+            return Ok(());
+        }
+
+        cached.last_updated = chrono::Utc::now().timestamp();
+        self.save_cached_list(&cached)
     }
 
     // === 邮件详情缓存 ===
@@ -362,7 +656,7 @@ impl StorageService {
             .insert(key.as_bytes(), value)
             .map_err(|e| format!("缓存邮件详情失败: {}", e))?;
 
-        Ok(())
+        self.index_detail(account_id, folder, uid, email)
     }
 
     /// 获取缓存的邮件详情
@@ -381,6 +675,28 @@ impl StorageService {
         }
     }
 
+    /// 获取文件夹下所有已缓存的邮件详情（按 UID 索引）
+    pub fn get_cached_email_details(&self, account_id: &str, folder: &str) -> Result<HashMap<u32, Email>, String> {
+        let prefix = format!("{}:{}:", account_id, folder);
+        let mut details = HashMap::new();
+
+        for item in self.email_details_tree.scan_prefix(prefix.as_bytes()) {
+            let (key, value) = item.map_err(|e| format!("读取邮件详情缓存失败: {}", e))?;
+
+            // 跳过名称以当前文件夹为前缀的子文件夹
+            let uid = match String::from_utf8_lossy(&key[prefix.len()..]).parse::<u32>() {
+                Ok(uid) => uid,
+                Err(_) => continue,
+            };
+
+            let email = serde_json::from_slice(&value)
+                .map_err(|e| format!("反序列化邮件详情失败: {}", e))?;
+            details.insert(uid, email);
+        }
+
+        Ok(details)
+    }
+
     /// 清除文件夹的所有缓存
     pub fn clear_all_cache(&self, account_id: &str, folder: &str) -> Result<(), String> {
         // 清除邮件摘要缓存
@@ -408,9 +724,177 @@ impl StorageService {
                 .map_err(|e| format!("删除邮件详情缓存失败: {}", e))?;
         }
 
+        // 清除内嵌资源缓存
+        self.remove_inline_resources(&prefix)?;
+
+        // 清除搜索索引
+        self.search_index.remove_folder(account_id, folder)
+    }
+
+    // === 内嵌资源缓存 ===
+
+    /// 缓存 HTML 正文引用的内嵌资源（键为 账户:文件夹:UID:Content-ID）
+    pub fn cache_inline_resource(&self, account_id: &str, folder: &str, uid: u32, content_id: &str, data: &[u8]) -> Result<(), String> {
+        let key = format!("{}:{}:{}:{}", account_id, folder, uid, content_id);
+        self.inline_resources_tree
+            .insert(key.as_bytes(), data)
+            .map_err(|e| format!("缓存内嵌资源失败: {}", e))?;
+        Ok(())
+    }
+
+    /// 获取缓存的内嵌资源
+    pub fn get_inline_resource(&self, account_id: &str, folder: &str, uid: u32, content_id: &str) -> Result<Option<Vec<u8>>, String> {
+        let key = format!("{}:{}:{}:{}", account_id, folder, uid, content_id);
+        let value = self.inline_resources_tree.get(key.as_bytes())
+            .map_err(|e| format!("获取缓存内嵌资源失败: {}", e))?;
+
+        Ok(value.map(|v| v.to_vec()))
+    }
+
+    fn remove_inline_resources(&self, prefix: &str) -> Result<(), String> {
+        let keys: Vec<Vec<u8>> = self.inline_resources_tree
+            .scan_prefix(prefix.as_bytes())
+            .map(|item| item.map(|(k, _)| k.to_vec()))
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("扫描内嵌资源缓存失败: {}", e))?;
+
+        for key in keys {
+            self.inline_resources_tree
+                .remove(&key)
+                .map_err(|e| format!("删除内嵌资源缓存失败: {}", e))?;
+        }
+        Ok(())
+    }
+
+    // === 本地全文搜索 ===
+
+    fn index_summaries(&self, account_id: &str, folder: &str, emails: &[EmailSummary]) -> Result<(), String> {
+        for email in emails {
+            let text = [
+                email.subject.as_str(),
+                email.from.as_str(),
+                email.preview.as_str(),
+                email.body.as_str(),
+            ].join("\n");
+            self.search_index.index(account_id, folder, email.uid, &text)?;
+        }
+        Ok(())
+    }
+
+    fn index_detail(&self, account_id: &str, folder: &str, uid: u32, email: &Email) -> Result<(), String> {
+        let text = [
+            email.subject.as_str(),
+            email.from.as_str(),
+            email.to.join(" ").as_str(),
+            email.cc.join(" ").as_str(),
+            email.body.as_str(),
+        ].join("\n");
+        self.search_index.index(account_id, folder, uid, &text)
+    }
+
+    /// 为全部已缓存的邮件摘要和详情建立索引
+    fn rebuild_search_index(&self) -> Result<(), String> {
+        for item in self.email_summaries_tree.iter() {
+            let (_, value) = item.map_err(|e| format!("读取邮件列表缓存失败: {}", e))?;
+            if let Ok(cached) = serde_json::from_slice::<CachedEmailList>(&value) {
+                self.index_summaries(&cached.account_id, &cached.folder, &cached.emails)?;
+            }
+        }
+
+        for item in self.email_details_tree.iter() {
+            let (key, value) = item.map_err(|e| format!("读取邮件详情缓存失败: {}", e))?;
+            // 键格式为 账户:文件夹:UID，文件夹名中可能含有冒号
+            let key = String::from_utf8_lossy(&key).to_string();
+            let location = key.split_once(':')
+                .and_then(|(account_id, rest)| rest.rsplit_once(':').map(|(folder, _)| (account_id, folder)));
+            if let (Some((account_id, folder)), Ok(email)) = (location, serde_json::from_slice::<Email>(&value)) {
+                self.index_detail(account_id, folder, email.uid, &email)?;
+            }
+        }
+
         Ok(())
     }
 
+    /// 在本地缓存中搜索邮件（离线可用），account_id 为 None 时搜索全部账户
+    /// 结果按日期从新到旧排列
+    pub fn search_local(&self, query: &str, account_id: Option<&str>, limit: usize) -> Result<Vec<LocalSearchHit>, String> {
+        let docs = self.search_index.search(query, account_id)?;
+
+        let mut summaries: HashMap<(String, String), HashMap<u32, EmailSummary>> = HashMap::new();
+        let mut hits = Vec::new();
+
+        for doc in docs {
+            let folder_key = (doc.account_id.clone(), doc.folder.clone());
+            if !summaries.contains_key(&folder_key) {
+                let cached = self.get_cached_email_summaries(&doc.account_id, &doc.folder)?
+                    .map(|cached| cached.emails.into_iter().map(|e| (e.uid, e)).collect())
+                    .unwrap_or_default();
+                summaries.insert(folder_key.clone(), cached);
+            }
+
+            let email = match summaries[&folder_key].get(&doc.uid) {
+                Some(email) => email.clone(),
+                None => match self.get_cached_email_detail(&doc.account_id, &doc.folder, doc.uid)? {
+                    Some(detail) => detail.to_summary(),
+                    None => continue,
+                },
+            };
+
+            hits.push(LocalSearchHit {
+                account_id: doc.account_id,
+                folder: doc.folder,
+                email,
+            });
+        }
+
+        hits.sort_by(|a, b| b.email.date.cmp(&a.email.date));
+        hits.truncate(limit);
+        Ok(hits)
+    }
+
+    /// 按查询语句在本地缓存中筛选邮件（离线可用），account_ids 为空时搜索全部账户
+    /// 查询需要收件人或正文时读取已缓存的详情，结果按日期从新到旧排列
+    pub fn search_cached(&self, query: &SearchQuery, account_ids: &[String], limit: usize) -> Result<Vec<LocalSearchHit>, String> {
+        let folders = query.folders()?;
+        let mut hits = Vec::new();
+
+        for item in self.email_summaries_tree.iter() {
+            let (key, value) = item.map_err(|e| format!("读取邮件列表缓存失败: {}", e))?;
+            // 先按键（账户:文件夹）筛选，范围之外的列表不必反序列化
+            let key = String::from_utf8_lossy(&key);
+            let Some((account_id, folder)) = key.split_once(':') else { continue };
+            if (!account_ids.is_empty() && !account_ids.iter().any(|id| id == account_id))
+                || (!folders.is_empty() && !folders.iter().any(|f| f.eq_ignore_ascii_case(folder)))
+            {
+                continue;
+            }
+            let cached = match serde_json::from_slice::<CachedEmailList>(&value) {
+                Ok(cached) => cached,
+                Err(_) => continue,
+            };
+
+            let details = if query.needs_detail() {
+                self.get_cached_email_details(&cached.account_id, &cached.folder)?
+            } else {
+                HashMap::new()
+            };
+
+            for email in cached.emails {
+                if query.matches(&cached.folder, &email, details.get(&email.uid)) {
+                    hits.push(LocalSearchHit {
+                        account_id: cached.account_id.clone(),
+                        folder: cached.folder.clone(),
+                        email,
+                    });
+                }
+            }
+        }
+
+        hits.sort_by(|a, b| b.email.date.cmp(&a.email.date));
+        hits.truncate(limit);
+        Ok(hits)
+    }
+
     pub fn flush(&self) -> Result<(), String> {
         self.db
             .flush()
@@ -429,6 +913,124 @@ impl Clone for StorageService {
             sync_state_tree: Arc::clone(&self.sync_state_tree),
             email_summaries_tree: Arc::clone(&self.email_summaries_tree),
             email_details_tree: Arc::clone(&self.email_details_tree),
+            inline_resources_tree: Arc::clone(&self.inline_resources_tree),
+            search_index: Arc::clone(&self.search_index),
+            saved_search_hits: Arc::clone(&self.saved_search_hits),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::{self, storage, summary};
+
+    fn rule(id: &str, priority: i32) -> FilterRule {
+        FilterRule { priority, ..test_support::rule(id, Vec::new(), Vec::new()) }
+    }
+
+    fn ids(rules: &[FilterRule]) -> Vec<&str> {
+        rules.iter().map(|r| r.id.as_str()).collect()
+    }
+
+    fn email(uid: u32, subject: &str) -> Email {
+        let summary = EmailSummary { body: format!("{} 正文", subject), ..summary(uid, subject, "alice@example.com") };
+        test_support::detail(&summary)
+    }
+
+    #[test]
+    fn test_vanished_mail_removed_without_summary_changes() {
+        let storage = storage();
+        let kept = email(1, "kept");
+        storage.cache_email_summaries("work", "INBOX", &[kept.to_summary()]).unwrap();
+
+        // UID 2 只缓存了详情和内嵌资源，不在摘要列表中
+        let opened = email(2, "quarterly");
+        storage.cache_email_detail("work", "INBOX", 2, &opened).unwrap();
+        storage.cache_inline_resource("work", "INBOX", 2, "logo@mail", b"png").unwrap();
+        assert_eq!(storage.search_local("quarterly", None, 10).unwrap().len(), 1);
+
+        // 没有标志变化，也没有摘要被移除
+        let updated = storage.apply_flag_changes("work", "INBOX", &HashMap::new(), &[2]).unwrap();
+        assert!(updated.is_empty());
+
+        assert!(storage.get_cached_email_detail("work", "INBOX", 2).unwrap().is_none());
+        assert!(storage.get_inline_resource("work", "INBOX", 2, "logo@mail").unwrap().is_none());
+        assert!(storage.search_local("quarterly", None, 10).unwrap().is_empty());
+        let cached = storage.get_cached_email_summaries("work", "INBOX").unwrap().unwrap();
+        assert_eq!(cached.emails.iter().map(|e| e.uid).collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn test_update_cached_emails_after_rules() {
+        let storage = storage();
+        let emails: Vec<EmailSummary> = (1..=3).map(|uid| email(uid, "notice").to_summary()).collect();
+        storage.cache_email_summaries("work", "INBOX", &emails).unwrap();
+        storage.cache_email_detail("work", "INBOX", 3, &email(3, "notice")).unwrap();
+
+        // UID 1 被标记已读并加上标签，UID 3 被移走，其余缓存保持不变
+        let mut read = emails[0].clone();
+        read.is_read = true;
+        read.tags.push("work".to_string());
+        storage.update_cached_emails("work", "INBOX", &[read.clone()], &[3]).unwrap();
+
+        let cached = storage.get_cached_email_summaries("work", "INBOX").unwrap().unwrap();
+        assert_eq!(cached.emails.iter().map(|e| e.uid).collect::<Vec<_>>(), vec![1, 2]);
+        assert!(cached.emails[0].is_read);
+        assert_eq!(cached.emails[0].tags, read.tags);
+        assert!(storage.get_cached_email_detail("work", "INBOX", 3).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_saved_search_pages() {
+        let storage = storage();
+        let report = |uid: u32, day: u32| EmailSummary {
+            date: format!("2026-03-{:02}T09:00:00+00:00", day),
+            ..email(uid, "report").to_summary()
+        };
+        storage.cache_email_summaries("work", "INBOX", &[report(1, 1), report(2, 3), report(3, 5)]).unwrap();
+        storage.cache_email_summaries("work", "Archive", &[report(1, 4)]).unwrap();
+        storage.cache_email_summaries("home", "INBOX", &[report(1, 6)]).unwrap();
+
+        let search = SavedSearch {
+            id: "reports".to_string(),
+            name: "报告".to_string(),
+            query: "subject:report".to_string(),
+            account_ids: vec!["work".to_string()],
+        };
+        let ids = |offset, limit| -> Vec<String> {
+            storage.list_saved_search(&search, offset, limit).unwrap().into_iter().map(|e| e.id).collect()
+        };
+
+        // 不同文件夹中 UID 相同的邮件 id 不重复
+        let first = storage.list_saved_search(&search, 0, 2).unwrap();
+        assert_eq!(first.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), vec!["work_INBOX_3", "work_Archive_1"]);
+        assert_eq!(first[1].source, Some(EmailSource { account_id: "work".to_string(), folder: "Archive".to_string() }));
+
+        // 翻页期间到达的新邮件不使后面的页面错位，重新打开第一页时才出现
+        storage.cache_email_summaries("work", "INBOX", &[report(1, 1), report(2, 3), report(3, 5), report(4, 7)]).unwrap();
+        assert_eq!(ids(2, 2), vec!["work_INBOX_2", "work_INBOX_1"]);
+        assert!(ids(4, 2).is_empty());
+        assert_eq!(ids(0, 2), vec!["work_INBOX_4", "work_INBOX_3"]);
+    }
+
+    #[test]
+    fn test_reorder_filter_rules() {
+        let storage = storage();
+        for (id, priority) in [("a", 0), ("b", 1), ("c", 2), ("d", 3)] {
+            storage.save_filter_rule(&rule(id, priority)).unwrap();
+        }
+
+        // 未列出的规则排在最后，并保持原有顺序；未知的 ID 被忽略
+        let ordered = ["c", "x", "a"].map(str::to_string);
+        let rules = storage.reorder_filter_rules(&ordered).unwrap();
+        assert_eq!(ids(&rules), vec!["c", "a", "b", "d"]);
+        assert_eq!(rules.iter().map(|r| r.priority).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+
+        // 新的优先级已保存
+        assert_eq!(ids(&storage.get_filter_rules().unwrap()), vec!["c", "a", "b", "d"]);
+
+        let rules = storage.reorder_filter_rules(&[]).unwrap();
+        assert_eq!(ids(&rules), vec!["c", "a", "b", "d"]);
+    }
+}
@@ -0,0 +1,796 @@
+use crate::models::{EmailSummary, FolderSyncState};
+use crate::services::{ImapService, RuleEngine, StorageService};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Runtime};
+use tokio::sync::{broadcast, watch, Notify};
+
+/// 调度器检查到期账户的间隔（秒）
+const SCHEDULER_TICK_SECONDS: u64 = 30;
+
+/// 定期同步时每个文件夹最多拉取的新邮件数
+const SCHEDULED_SYNC_LIMIT: usize = 50;
+
+/// 一次文件夹同步的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncResult {
+    /// 是否进行了全量同步
+    pub full_sync: bool,
+    /// 本次从服务器取得的邮件（增量同步时为执行过滤规则后仍留在文件夹中的新邮件）
+    pub emails: Vec<EmailSummary>,
+}
+
+/// 文件夹同步：根据 UIDVALIDITY/UIDNEXT 决定全量或增量同步，并执行过滤规则
+pub struct SyncService;
+
+impl SyncService {
+    /// 同步一个文件夹
+    /// 增量同步得到新邮件时发送 `mail://new` 事件
+    pub async fn sync_folder<R: Runtime>(
+        app: &AppHandle<R>,
+        storage: &StorageService,
+        imap_service: &ImapService,
+        account_id: &str,
+        folder: &str,
+        limit: usize,
+        force: bool,
+    ) -> Result<SyncResult, String> {
+        let now = chrono::Utc::now().timestamp();
+
+        // 1. 检查同步状态
+        let sync_state = storage.get_folder_sync_state(account_id, folder)?;
+
+        // 2. 获取当前文件夹状态
+        let (uid_validity, uid_next) = imap_service.get_folder_status(folder).await?;
+
+        // 3. 判断是否需要全量同步
+        let need_full_sync = force || sync_state.is_none() ||
+            sync_state.as_ref().map(|s| s.uid_validity) != Some(uid_validity);
+
+        if need_full_sync {
+            let fetched_emails = imap_service.fetch_emails(folder, limit, 0).await?;
+            let mut reached_oldest = fetched_emails.len() < limit;
+
+            // 缓存结果；UIDVALIDITY 未变时保留已缓存的更早页面，其中被删除的邮件由下次增量同步清理
+            let mut cached_emails = fetched_emails.clone();
+            let same_validity = sync_state.as_ref().map(|s| s.uid_validity) == Some(uid_validity);
+            if same_validity && !reached_oldest {
+                let oldest = fetched_emails.last().map(|e| e.uid).unwrap_or(0);
+                if let Some(cached) = storage.get_cached_email_summaries(account_id, folder)? {
+                    reached_oldest = cached.reached_oldest;
+                    cached_emails.extend(cached.emails.into_iter().filter(|e| e.uid < oldest));
+                }
+            }
+            storage.cache_email_summaries(account_id, folder, &cached_emails)?;
+            if reached_oldest {
+                storage.merge_older_emails(account_id, folder, &[], true)?;
+            }
+
+            // 更新同步状态
+            let last_uid = fetched_emails.first().map(|e| e.uid).unwrap_or(0);
+            let state = FolderSyncState {
+                account_id: account_id.to_string(),
+                folder: folder.to_string(),
+                last_uid,
+                uid_validity,
+                last_sync_time: now,
+                // 下次增量同步时重新核对全部标志并记录 HIGHESTMODSEQ
+                highest_modseq: 0,
+            };
+            storage.save_folder_sync_state(&state)?;
+
+            return Ok(SyncResult { full_sync: true, emails: fetched_emails });
+        }
+
+        // 增量同步
+        let (last_uid, modseq) = sync_state
+            .map(|s| (s.last_uid, s.highest_modseq))
+            .unwrap_or((0, 0));
+
+        // 先同步已缓存邮件的已读/星标状态和其他客户端的删除
+        let highest_modseq = Self::sync_flags(
+            app, storage, imap_service, account_id, folder, uid_validity, modseq,
+        ).await.unwrap_or_else(|e| {
+            eprintln!("同步邮件标志失败 ({} / {}): {}", account_id, folder, e);
+            modseq
+        });
+
+        let new_emails = if uid_next > last_uid + 1 {
+            imap_service.fetch_new_emails(folder, last_uid, limit).await?
+        } else {
+            Vec::new()
+        };
+
+        // 被规则移走的邮件同样计入同步进度，避免重复拉取
+        let new_last_uid = new_emails.iter().map(|e| e.uid).max().unwrap_or(last_uid);
+
+        let new_emails = if new_emails.is_empty() {
+            new_emails
+        } else {
+            // 执行过滤规则
+            let new_emails = Self::apply_filter_rules(
+                app, storage, imap_service, account_id, folder, new_emails,
+            ).await;
+
+            // 追加到缓存
+            storage.append_cached_emails(account_id, folder, &new_emails)?;
+            new_emails
+        };
+
+        // 更新同步状态
+        let updated_state = FolderSyncState {
+            account_id: account_id.to_string(),
+            folder: folder.to_string(),
+            last_uid: new_last_uid,
+            uid_validity,
+            last_sync_time: now,
+            highest_modseq,
+        };
+        storage.save_folder_sync_state(&updated_state)?;
+
+        if !new_emails.is_empty() {
+            let payload = serde_json::json!({
+                "account_id": account_id,
+                "folder": folder,
+                "emails": new_emails,
+            });
+            if let Err(e) = app.emit("mail://new", payload) {
+                eprintln!("发送新邮件事件失败: {}", e);
+            }
+        }
+
+        Ok(SyncResult { full_sync: false, emails: new_emails })
+    }
+
+    /// 从缓存中取出一页邮件，页面超出缓存范围时先向服务器取得更早的邮件并合并到缓存末尾
+    pub async fn load_page(
+        storage: &StorageService,
+        imap_service: &ImapService,
+        account_id: &str,
+        folder: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<EmailSummary>, String> {
+        let cached = storage.get_cached_email_summaries(account_id, folder)?;
+        let (cached_len, oldest, reached_oldest) = match &cached {
+            Some(cached) => (
+                cached.emails.len(),
+                cached.emails.iter().map(|e| e.uid).min(),
+                cached.reached_oldest,
+            ),
+            None => (0, None, false),
+        };
+
+        let wanted = offset + limit;
+        if cached_len < wanted && !reached_oldest {
+            // 至少取一整页，减少继续向后翻页时的请求次数
+            let count = (wanted - cached_len).max(limit);
+            let (uid_validity, older) = imap_service
+                .fetch_older_emails(folder, oldest.unwrap_or(u32::MAX), count)
+                .await?;
+
+            let sync_state = storage.get_folder_sync_state(account_id, folder)?;
+            if sync_state.as_ref().map(|s| s.uid_validity) == Some(uid_validity) {
+                storage.merge_older_emails(account_id, folder, &older, older.len() < count)?;
+            } else {
+                // 还没有同步状态，或 UIDVALIDITY 已变化（缓存中的 UID 失效）：
+                // 从最新的邮件重新建立缓存，并先记录 UIDVALIDITY，之后的增量同步以此为准
+                let (uid_validity, newest) = match oldest {
+                    Some(_) => imap_service.fetch_older_emails(folder, u32::MAX, wanted).await?,
+                    None => (uid_validity, older),
+                };
+                storage.save_folder_sync_state(&FolderSyncState {
+                    account_id: account_id.to_string(),
+                    folder: folder.to_string(),
+                    last_uid: newest.first().map(|e| e.uid).unwrap_or(0),
+                    uid_validity,
+                    last_sync_time: chrono::Utc::now().timestamp(),
+                    highest_modseq: 0,
+                })?;
+                storage.cache_email_summaries(account_id, folder, &newest)?;
+                if newest.len() < wanted {
+                    storage.merge_older_emails(account_id, folder, &[], true)?;
+                }
+            }
+        } else if let Some(cached) = cached {
+            return Ok(cached.emails.into_iter().skip(offset).take(limit).collect());
+        }
+
+        Ok(storage.get_cached_email_summaries(account_id, folder)?
+            .map(|cached| cached.emails.into_iter().skip(offset).take(limit).collect())
+            .unwrap_or_default())
+    }
+
+    /// 将服务器上的标志变化和删除同步到缓存，有变化时发送 `mail://changed` 事件
+    /// 返回新的 HIGHESTMODSEQ
+    async fn sync_flags<R: Runtime>(
+        app: &AppHandle<R>,
+        storage: &StorageService,
+        imap_service: &ImapService,
+        account_id: &str,
+        folder: &str,
+        uid_validity: u32,
+        modseq: u64,
+    ) -> Result<u64, String> {
+        let known_uids: Vec<u32> = storage.get_cached_email_summaries(account_id, folder)?
+            .map(|cached| cached.emails.iter().map(|e| e.uid).collect())
+            .unwrap_or_default();
+
+        let changes = imap_service
+            .fetch_flag_changes(folder, uid_validity, modseq, &known_uids)
+            .await?;
+        let updated = storage.apply_flag_changes(account_id, folder, &changes.flags, &changes.vanished)?;
+
+        if !updated.is_empty() || !changes.vanished.is_empty() {
+            let payload = serde_json::json!({
+                "account_id": account_id,
+                "folder": folder,
+                "updated": updated,
+                "vanished": changes.vanished,
+            });
+            if let Err(e) = app.emit("mail://changed", payload) {
+                eprintln!("发送邮件变化事件失败: {}", e);
+            }
+        }
+
+        Ok(changes.highest_modseq)
+    }
+
+    /// 对增量同步得到的新邮件执行已启用的过滤规则
+    /// 返回仍留在当前文件夹的邮件，并通过 `rules://applied` 事件报告触发的规则
+    async fn apply_filter_rules<R: Runtime>(
+        app: &AppHandle<R>,
+        storage: &StorageService,
+        imap_service: &ImapService,
+        account_id: &str,
+        folder: &str,
+        emails: Vec<EmailSummary>,
+    ) -> Vec<EmailSummary> {
+        let rules = match storage.get_filter_rules() {
+            Ok(rules) => rules,
+            Err(e) => {
+                eprintln!("读取过滤规则失败: {}", e);
+                return emails;
+            }
+        };
+
+        let engine = RuleEngine::new(rules);
+        if engine.is_empty() {
+            return emails;
+        }
+
+        // 规则涉及收件人或完整正文时需要邮件详情
+        let mut details = HashMap::new();
+        engine.load_details(imap_service, folder, &emails, &mut details).await;
+
+        let outcome = engine.apply(imap_service, account_id, folder, emails, &details).await;
+
+        for email in &outcome.remaining {
+            if let Some(detail) = details.get(&email.uid) {
+                let _ = storage.cache_email_detail(account_id, folder, email.uid, detail);
+            }
+        }
+
+        // 移入目标文件夹的邮件 UID 更大，由目标文件夹的下次增量同步取得，无需清除其缓存
+        if !outcome.executions.is_empty() {
+            let payload = serde_json::json!({
+                "account_id": account_id,
+                "folder": folder,
+                "executions": outcome.executions,
+            });
+            if let Err(e) = app.emit("rules://applied", payload) {
+                eprintln!("发送规则执行事件失败: {}", e);
+            }
+        }
+
+        outcome.remaining
+    }
+}
+
+/// 同步的账户和文件夹
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SyncTarget {
+    pub account_id: String,
+    pub folder: String,
+}
+
+/// 后台同步状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncStatus {
+    /// 定期同步是否在运行
+    pub running: bool,
+    pub offline: bool,
+    /// 正在同步的文件夹
+    pub syncing: Vec<SyncTarget>,
+    /// 最近一轮定期同步的时间
+    pub last_run: Option<i64>,
+}
+
+type SyncOutcome = Result<SyncResult, String>;
+
+/// 进行中的一次同步及其参数
+struct InFlightSync {
+    sender: broadcast::Sender<SyncOutcome>,
+    limit: usize,
+    force: bool,
+}
+
+impl InFlightSync {
+    /// 这次同步的结果能否满足另一个请求：不能用增量同步代替强制全量同步，也不能用更小的数量代替更大的
+    fn covers(&self, limit: usize, force: bool) -> bool {
+        (self.force || !force) && self.limit >= limit
+    }
+}
+
+/// 后台同步管理器
+/// 负责定期同步所有账户的常用文件夹，并合并同一文件夹的并发同步请求：
+/// 已有同步在进行且能满足请求时，后来的请求等待并共享它的结果；
+/// 后来的请求要求更严格（强制全量同步或更大的数量）时，等它结束后重新同步。
+/// 进度通过 `sync://progress` 事件报告
+pub struct SyncManager {
+    in_flight: Mutex<HashMap<SyncTarget, InFlightSync>>,
+    offline: AtomicBool,
+    scheduler: Mutex<Option<watch::Sender<bool>>>,
+    wake: Notify,
+    last_run: Mutex<Option<i64>>,
+}
+
+/// 同步结束（包括被取消）时把文件夹移出进行中列表，并把结果发给等待的请求；
+/// 被取消时没有结果，等待的请求收到通道关闭
+struct InFlightGuard<'a> {
+    manager: &'a SyncManager,
+    target: SyncTarget,
+    result: Option<Result<SyncResult, String>>,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        let sync = self.manager.in_flight.lock().unwrap().remove(&self.target);
+        if let (Some(sync), Some(result)) = (sync, self.result.take()) {
+            let _ = sync.sender.send(result);
+        }
+    }
+}
+
+impl Default for SyncManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyncManager {
+    pub fn new() -> Self {
+        Self {
+            in_flight: Mutex::new(HashMap::new()),
+            offline: AtomicBool::new(false),
+            scheduler: Mutex::new(None),
+            wake: Notify::new(),
+            last_run: Mutex::new(None),
+        }
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline.load(Ordering::SeqCst)
+    }
+
+    /// 设置离线状态，恢复在线时立即执行一轮定期同步
+    pub fn set_offline(&self, offline: bool) {
+        let was_offline = self.offline.swap(offline, Ordering::SeqCst);
+        if was_offline && !offline {
+            self.wake.notify_one();
+        }
+    }
+
+    pub fn status(&self) -> SyncStatus {
+        let mut syncing: Vec<SyncTarget> = self.in_flight.lock().unwrap().keys().cloned().collect();
+        syncing.sort_by(|a, b| (&a.account_id, &a.folder).cmp(&(&b.account_id, &b.folder)));
+
+        SyncStatus {
+            running: self.scheduler.lock().unwrap().is_some(),
+            offline: self.is_offline(),
+            syncing,
+            last_run: *self.last_run.lock().unwrap(),
+        }
+    }
+
+    /// 同步一个文件夹，同一文件夹已在同步且参数能满足本次请求时等待并返回那次同步的结果
+    #[allow(clippy::too_many_arguments)]
+    pub async fn sync_folder<R: Runtime>(
+        &self,
+        app: &AppHandle<R>,
+        storage: &StorageService,
+        imap_service: &ImapService,
+        account_id: &str,
+        folder: &str,
+        limit: usize,
+        force: bool,
+    ) -> Result<SyncResult, String> {
+        if self.is_offline() {
+            return Err("当前处于离线状态".to_string());
+        }
+
+        let target = SyncTarget {
+            account_id: account_id.to_string(),
+            folder: folder.to_string(),
+        };
+
+        loop {
+            let waiting = {
+                let mut in_flight = self.in_flight.lock().unwrap();
+                match in_flight.get(&target) {
+                    Some(sync) => Some((sync.sender.subscribe(), sync.covers(limit, force))),
+                    None => {
+                        let (sender, _) = broadcast::channel(1);
+                        in_flight.insert(target.clone(), InFlightSync { sender, limit, force });
+                        None
+                    }
+                }
+            };
+
+            match waiting {
+                Some((mut receiver, true)) => {
+                    return receiver
+                        .recv()
+                        .await
+                        .map_err(|_| "同步已中断".to_string())?;
+                }
+                // 等进行中的同步结束后再发起本次同步
+                Some((mut receiver, false)) => {
+                    let _ = receiver.recv().await;
+                }
+                None => break,
+            }
+        }
+
+        let mut guard = InFlightGuard { manager: self, target: target.clone(), result: None };
+        emit_progress(app, &target, "started", None, None);
+
+        let result = SyncService::sync_folder(
+            app, storage, imap_service, account_id, folder, limit, force,
+        ).await;
+
+        match &result {
+            Ok(sync) => emit_progress(app, &target, "finished", Some(sync.emails.len()), None),
+            Err(e) => emit_progress(app, &target, "failed", None, Some(e)),
+        }
+
+        guard.result = Some(result.clone());
+        drop(guard);
+
+        result
+    }
+
+    /// 开始定期同步，已在运行时返回 false
+    pub fn start<R: Runtime>(self: &Arc<Self>, app: AppHandle<R>, storage: Arc<StorageService>) -> bool {
+        let mut scheduler = self.scheduler.lock().unwrap();
+        if scheduler.is_some() {
+            return false;
+        }
+
+        let (stop_tx, stop_rx) = watch::channel(false);
+        *scheduler = Some(stop_tx);
+
+        let manager = self.clone();
+        tauri::async_runtime::spawn(async move {
+            manager.run_scheduler(app, storage, stop_rx).await;
+        });
+
+        true
+    }
+
+    /// 停止定期同步，未在运行时返回 false；正在进行的同步会继续完成
+    pub fn stop(&self) -> bool {
+        match self.scheduler.lock().unwrap().take() {
+            Some(stop_tx) => {
+                let _ = stop_tx.send(true);
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn run_scheduler<R: Runtime>(
+        &self,
+        app: AppHandle<R>,
+        storage: Arc<StorageService>,
+        mut stop_rx: watch::Receiver<bool>,
+    ) {
+        // 每个账户上次定期同步的时间
+        let mut last_synced: HashMap<String, i64> = HashMap::new();
+
+        loop {
+            if !self.is_offline() {
+                let now = chrono::Utc::now().timestamp();
+                self.sync_due_accounts(&app, &storage, &mut last_synced, now).await;
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(SCHEDULER_TICK_SECONDS)) => {}
+                _ = self.wake.notified() => {}
+                _ = stop_rx.changed() => break,
+            }
+        }
+    }
+
+    /// 同步所有到达同步间隔的账户
+    ///
+    /// 只有全部文件夹同步成功的账户才记录同步时间，失败的账户在下一轮重试。
+    async fn sync_due_accounts<R: Runtime>(
+        &self,
+        app: &AppHandle<R>,
+        storage: &StorageService,
+        last_synced: &mut HashMap<String, i64>,
+        now: i64,
+    ) {
+        let config = match storage.get_config() {
+            Ok(config) => config.sync_config,
+            Err(e) => {
+                eprintln!("读取同步配置失败: {}", e);
+                return;
+            }
+        };
+        let accounts = match storage.list_accounts() {
+            Ok(accounts) => accounts,
+            Err(e) => {
+                eprintln!("读取账户列表失败: {}", e);
+                return;
+            }
+        };
+
+        let mut synced_any = false;
+
+        for account in accounts {
+            let interval = config.interval_for(&account.id) as i64;
+            if last_synced.get(&account.id).is_some_and(|last| now - last < interval) {
+                continue;
+            }
+            if self.is_offline() {
+                return;
+            }
+
+            let password = match storage.get_password(&account) {
+                Ok(Some(password)) => password,
+                Ok(None) => continue,
+                Err(e) => {
+                    eprintln!("读取账户密码失败 ({}): {}", account.id, e);
+                    continue;
+                }
+            };
+
+            let account_id = account.id.clone();
+            let imap_service = ImapService::new(account, password);
+            let mut succeeded = true;
+            for folder in &config.folders {
+                // 失败已通过进度事件报告
+                succeeded &= self.sync_folder(
+                    app, storage, &imap_service, &account_id, folder, SCHEDULED_SYNC_LIMIT, false,
+                ).await.is_ok();
+            }
+
+            if succeeded {
+                last_synced.insert(account_id, now);
+                synced_any = true;
+            }
+        }
+
+        if synced_any {
+            *self.last_run.lock().unwrap() = Some(now);
+        }
+    }
+}
+
+fn emit_progress<R: Runtime>(
+    app: &AppHandle<R>,
+    target: &SyncTarget,
+    status: &str,
+    new_count: Option<usize>,
+    error: Option<&str>,
+) {
+    let payload = serde_json::json!({
+        "account_id": target.account_id,
+        "folder": target.folder,
+        "status": status,
+        "new_count": new_count,
+        "error": error,
+    });
+    if let Err(e) = app.emit("sync://progress", payload) {
+        eprintln!("发送同步进度事件失败: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::imap_service::tests::{account, mailboxes, spawn_server, Mailboxes, MockMessage};
+    use crate::services::test_support::{storage, use_memory_keyring};
+    use crate::models::EmailAccount;
+
+    fn cached_uids(storage: &StorageService, account: &EmailAccount) -> Vec<u32> {
+        storage.get_cached_email_summaries(&account.id, "INBOX").unwrap()
+            .map(|cached| cached.emails.iter().map(|e| e.uid).collect())
+            .unwrap_or_default()
+    }
+
+    fn deliver(state: &Mailboxes, uid: u32) {
+        let mut state = state.lock().unwrap();
+        state.folders.get_mut("INBOX").unwrap().push(MockMessage::new(uid));
+        state.uid_next = uid + 1;
+    }
+
+    async fn wait_until_syncing(manager: &SyncManager) {
+        for _ in 0..100 {
+            if !manager.status().syncing.is_empty() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("同步没有开始");
+    }
+
+    #[test]
+    fn test_in_flight_sync_covers_only_weaker_requests() {
+        let sync = |limit, force| InFlightSync { sender: broadcast::channel(1).0, limit, force };
+
+        let incremental = sync(50, false);
+        assert!(incremental.covers(50, false));
+        assert!(incremental.covers(20, false));
+        // 强制全量同步和更大的数量需要重新同步
+        assert!(!incremental.covers(50, true));
+        assert!(!incremental.covers(100, false));
+
+        let full = sync(50, true);
+        assert!(full.covers(50, false) && full.covers(50, true));
+        assert!(!full.covers(200, true));
+    }
+
+    #[tokio::test]
+    async fn test_load_page_checks_uid_validity() {
+        let storage = storage();
+        let state = mailboxes(&[10, 20, 30, 40]);
+        let account = account("pages@example.com", spawn_server(Arc::clone(&state)));
+        let imap = ImapService::new(account.clone(), "secret".to_string());
+        let page = |offset, limit| SyncService::load_page(&storage, &imap, &account.id, "INBOX", offset, limit);
+        let uids = |emails: Vec<EmailSummary>| emails.iter().map(|e| e.uid).collect::<Vec<_>>();
+
+        // 没有缓存时先记录 UIDVALIDITY，之后的同步按增量进行
+        assert_eq!(uids(page(0, 2).await.unwrap()), vec![40, 30]);
+        let sync_state = storage.get_folder_sync_state(&account.id, "INBOX").unwrap().unwrap();
+        assert_eq!((sync_state.uid_validity, sync_state.last_uid), (7, 40));
+        assert_eq!(uids(page(2, 2).await.unwrap()), vec![20, 10]);
+
+        deliver(&state, 50);
+        let app = tauri::test::mock_app();
+        let result = SyncService::sync_folder(app.handle(), &storage, &imap, &account.id, "INBOX", 50, false)
+            .await
+            .unwrap();
+        assert!(!result.full_sync);
+        assert_eq!(uids(result.emails), vec![50]);
+        assert_eq!(cached_uids(&storage, &account), vec![50, 40, 30, 20, 10]);
+
+        // 文件夹在服务器上重建后，旧 UID 之前的页面不再合并，缓存从最新的邮件重新建立
+        {
+            let mut state = state.lock().unwrap();
+            state.uid_validity = 8;
+            state.folders.insert("INBOX".to_string(), [1, 2, 3].map(MockMessage::new).into());
+        }
+        assert!(page(5, 2).await.unwrap().is_empty());
+        assert_eq!(cached_uids(&storage, &account), vec![3, 2, 1]);
+        let sync_state = storage.get_folder_sync_state(&account.id, "INBOX").unwrap().unwrap();
+        assert_eq!((sync_state.uid_validity, sync_state.last_uid), (8, 3));
+        assert_eq!(uids(page(0, 2).await.unwrap()), vec![3, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_sync_retries_failed_accounts() {
+        use_memory_keyring();
+        let storage = storage();
+        let state = mailboxes(&[10, 20]);
+        let port = spawn_server(Arc::clone(&state));
+        let up = account("up@example.com", port);
+        let down = account("down@example.com", port);
+        storage.save_account(&up).unwrap();
+        storage.save_account(&down).unwrap();
+        storage.save_password(&up, "secret").unwrap();
+        storage.save_password(&down, "wrong").unwrap();
+
+        let app = tauri::test::mock_app();
+        let manager = SyncManager::new();
+        let mut last_synced = HashMap::new();
+
+        // 登录失败的账户不记录同步时间
+        manager.sync_due_accounts(app.handle(), &storage, &mut last_synced, 1000).await;
+        assert_eq!(last_synced, HashMap::from([(up.id.clone(), 1000)]));
+        assert_eq!(manager.status().last_run, Some(1000));
+        assert_eq!(cached_uids(&storage, &up), vec![20, 10]);
+
+        // 未到同步间隔的账户跳过，失败的账户下一轮重试
+        deliver(&state, 30);
+        storage.save_password(&down, "secret").unwrap();
+        manager.sync_due_accounts(app.handle(), &storage, &mut last_synced, 1010).await;
+        assert_eq!(last_synced[&up.id], 1000);
+        assert_eq!(last_synced[&down.id], 1010);
+        assert_eq!(cached_uids(&storage, &up), vec![20, 10]);
+        assert_eq!(cached_uids(&storage, &down), vec![30, 20, 10]);
+
+        manager.sync_due_accounts(app.handle(), &storage, &mut last_synced, 1300).await;
+        assert_eq!(last_synced[&up.id], 1300);
+        assert_eq!(cached_uids(&storage, &up), vec![30, 20, 10]);
+        assert_eq!(manager.status().last_run, Some(1300));
+    }
+
+    #[tokio::test]
+    async fn test_offline_mode_skips_sync() {
+        use_memory_keyring();
+        let storage = storage();
+        let state = mailboxes(&[10]);
+        let account = account("offline@example.com", spawn_server(Arc::clone(&state)));
+        storage.save_account(&account).unwrap();
+        storage.save_password(&account, "secret").unwrap();
+
+        let app = tauri::test::mock_app();
+        let manager = SyncManager::new();
+        manager.set_offline(true);
+        assert!(manager.status().offline);
+
+        let imap = ImapService::new(account.clone(), "secret".to_string());
+        let err = manager
+            .sync_folder(app.handle(), &storage, &imap, &account.id, "INBOX", 50, false)
+            .await
+            .unwrap_err();
+        assert!(err.contains("离线"), "{}", err);
+
+        let mut last_synced = HashMap::new();
+        manager.sync_due_accounts(app.handle(), &storage, &mut last_synced, 1000).await;
+        assert!(last_synced.is_empty());
+        assert_eq!(manager.status().last_run, None);
+        assert_eq!(state.lock().unwrap().logins, 0);
+
+        // 恢复在线时立即唤醒定期同步
+        manager.set_offline(false);
+        tokio::time::timeout(Duration::from_secs(1), manager.wake.notified()).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_syncs_share_or_wait() {
+        let storage = Arc::new(storage());
+        let state = mailboxes(&[10, 20]);
+        let account = account("waiter@example.com", spawn_server(Arc::clone(&state)));
+        state.lock().unwrap().search_delay = Some(Duration::from_millis(300));
+
+        let app = tauri::test::mock_app().handle().clone();
+        let manager = Arc::new(SyncManager::new());
+        let spawn_sync = |limit: usize, force: bool| {
+            let (manager, storage, app, account) = (manager.clone(), storage.clone(), app.clone(), account.clone());
+            tokio::spawn(async move {
+                let imap = ImapService::new(account.clone(), "secret".to_string());
+                manager.sync_folder(&app, &storage, &imap, &account.id, "INBOX", limit, force).await
+            })
+        };
+
+        // 进行中的同步能满足请求时，后来的请求共享它的结果
+        let first = spawn_sync(50, false);
+        wait_until_syncing(&manager).await;
+        let second = spawn_sync(20, false);
+        let (first, second) = (first.await.unwrap().unwrap(), second.await.unwrap().unwrap());
+        assert!(first.full_sync && second.full_sync);
+        assert_eq!(second.emails.len(), 2);
+        assert_eq!(state.lock().unwrap().searches, 1);
+
+        // 要求更严格时等进行中的同步结束后重新同步
+        deliver(&state, 30);
+        let incremental = spawn_sync(50, false);
+        wait_until_syncing(&manager).await;
+        let forced = spawn_sync(50, true);
+        let incremental = incremental.await.unwrap().unwrap();
+        let forced = forced.await.unwrap().unwrap();
+        assert!(!incremental.full_sync);
+        assert_eq!(incremental.emails.iter().map(|e| e.uid).collect::<Vec<_>>(), vec![30]);
+        assert!(forced.full_sync);
+        assert_eq!(forced.emails.len(), 3);
+        assert_eq!(state.lock().unwrap().searches, 3);
+        assert!(manager.status().syncing.is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+//! 测试共用的辅助函数：邮件和规则样例、临时存储和内存密钥链
+use crate::models::{ConditionNode, Email, EmailSummary, FilterAction, FilterRule, MatchType};
+use crate::services::StorageService;
+use chrono::{DateTime, Utc};
+use keyring::credential::{Credential, CredentialApi, CredentialBuilderApi};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, Once};
+
+/// 邮件摘要样例，日期为 2026-03-02T09:00:00Z，其余字段为空或默认值
+pub(crate) fn summary(uid: u32, subject: &str, from: &str) -> EmailSummary {
+    EmailSummary {
+        id: uid.to_string(),
+        uid,
+        subject: subject.to_string(),
+        from: from.to_string(),
+        date: "2026-03-02T09:00:00+00:00".to_string(),
+        is_read: false,
+        is_starred: false,
+        has_attachment: false,
+        category: None,
+        preview: String::new(),
+        body: String::new(),
+        size: 2048,
+        tags: Vec::new(),
+        source: None,
+        addresses: Default::default(),
+    }
+}
+
+/// 与摘要一致的邮件详情（INBOX 中），收件人、邮件头和附件为空
+pub(crate) fn detail(summary: &EmailSummary) -> Email {
+    Email {
+        id: summary.id.clone(),
+        uid: summary.uid,
+        subject: summary.subject.clone(),
+        from: summary.from.clone(),
+        to: Vec::new(),
+        cc: Vec::new(),
+        addresses: Default::default(),
+        date: DateTime::parse_from_rfc3339(&summary.date).unwrap().with_timezone(&Utc),
+        body: summary.body.clone(),
+        html_body: None,
+        folder: "INBOX".to_string(),
+        flags: Vec::new(),
+        is_read: summary.is_read,
+        is_starred: summary.is_starred,
+        category: None,
+        has_attachment: summary.has_attachment,
+        size: summary.size,
+        headers: Default::default(),
+        attachments: Vec::new(),
+        inline_resources: Vec::new(),
+    }
+}
+
+/// 已启用的过滤规则样例，作用于所有账户和文件夹
+pub(crate) fn rule(id: &str, conditions: Vec<ConditionNode>, actions: Vec<FilterAction>) -> FilterRule {
+    FilterRule {
+        id: id.to_string(),
+        name: format!("规则 {}", id),
+        conditions,
+        match_type: MatchType::All,
+        actions,
+        enabled: true,
+        priority: 0,
+        stop_processing: false,
+        account_ids: Vec::new(),
+        folders: Vec::new(),
+    }
+}
+
+/// 使用临时数据库的存储服务
+pub(crate) fn storage() -> StorageService {
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    StorageService::with_db(db).unwrap()
+}
+
+/// 内存中的密钥链，同一服务和用户名的条目共享密码
+static SECRETS: Mutex<BTreeMap<String, Vec<u8>>> = Mutex::new(BTreeMap::new());
+
+struct MemoryCredential {
+    key: String,
+}
+
+impl CredentialApi for MemoryCredential {
+    fn set_secret(&self, secret: &[u8]) -> keyring::Result<()> {
+        SECRETS.lock().unwrap().insert(self.key.clone(), secret.to_vec());
+        Ok(())
+    }
+
+    fn get_secret(&self) -> keyring::Result<Vec<u8>> {
+        SECRETS.lock().unwrap().get(&self.key).cloned().ok_or(keyring::Error::NoEntry)
+    }
+
+    fn delete_credential(&self) -> keyring::Result<()> {
+        SECRETS.lock().unwrap().remove(&self.key).map(|_| ()).ok_or(keyring::Error::NoEntry)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+struct MemoryCredentialBuilder;
+
+impl CredentialBuilderApi for MemoryCredentialBuilder {
+    fn build(&self, _target: Option<&str>, service: &str, user: &str) -> keyring::Result<Box<Credential>> {
+        Ok(Box::new(MemoryCredential { key: format!("{}:{}", service, user) }))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// 让 `save_password`/`get_password` 使用内存密钥链，不触碰系统密钥链
+pub(crate) fn use_memory_keyring() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| keyring::set_default_credential_builder(Box::new(MemoryCredentialBuilder)));
+}